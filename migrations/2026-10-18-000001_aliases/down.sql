@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE new_parts_parts (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  quantity INTEGER NOT NULL, -- quantity that is used in this BOM
+  bom_ver INTEGER NOT NULL, -- version of the bom that this is tied to
+  refdes VARCHAR NOT NULL, -- tracking the refdes
+  nostuff INTEGER NOT NULL DEFAULT 0, -- determines if stuff or no stuff
+  bom_part_id INTEGER NOT NULL, -- this is simply a part that has a BOM associated with it
+  part_id INTEGER NOT NULL, -- this table has entries that are associated with individual parts.
+  FOREIGN KEY(bom_part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE,
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+INSERT INTO new_parts_parts SELECT id, created_at, updated_at, quantity, bom_ver, refdes, nostuff, bom_part_id, part_id FROM parts_parts;
+DROP TABLE IF EXISTS parts_parts;
+ALTER TABLE new_parts_parts RENAME TO parts_parts;
+
+DROP TABLE aliases;
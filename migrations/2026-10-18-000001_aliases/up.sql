@@ -0,0 +1,13 @@
+-- Used to map an alias part number onto an existing part
+CREATE TABLE aliases (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  pn VARCHAR UNIQUE NOT NULL, -- the alias part number
+  quantity INTEGER NOT NULL DEFAULT 1, -- how many of the part one alias stands for
+  part_id INTEGER NOT NULL, -- the part that is being aliased
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Tracks which BOM lines came from an alias
+ALTER TABLE parts_parts ADD COLUMN alias_id INTEGER REFERENCES aliases(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE part_suppliers;
+DROP TABLE suppliers;
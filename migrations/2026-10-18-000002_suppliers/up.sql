@@ -0,0 +1,25 @@
+-- Distributors that parts can be bought from. i.e. DIGIKEY, MOUSER, LCSC
+CREATE TABLE suppliers (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  name VARCHAR UNIQUE NOT NULL -- name of the supplier
+);
+
+-- Supplier part numbers for a part. One per supplier.
+CREATE TABLE part_suppliers (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  supplier_pn VARCHAR NOT NULL, -- the supplier's part number
+  part_id INTEGER NOT NULL, -- the part that can be bought
+  supplier_id INTEGER NOT NULL, -- where it can be bought
+  UNIQUE(part_id, supplier_id),
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE,
+  FOREIGN KEY(supplier_id) REFERENCES suppliers(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Carry over any existing Digikey part numbers
+INSERT INTO suppliers (name) SELECT 'DIGIKEY' WHERE EXISTS (SELECT 1 FROM parts WHERE digikeypn IS NOT NULL);
+INSERT INTO part_suppliers (supplier_pn, part_id, supplier_id)
+  SELECT digikeypn, id, (SELECT id FROM suppliers WHERE name = 'DIGIKEY') FROM parts WHERE digikeypn IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Archived BOM versions are kept for history but hidden
+ALTER TABLE parts_parts ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Lines added by hand are kept when the BOM is imported again
+ALTER TABLE parts_parts ADD COLUMN manual INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,20 @@
+-- One row per reference designator of a BOM line.
+-- parts_parts.refdes is kept as the grouped view. i.e. "C1 C2 C7"
+CREATE TABLE bom_refdes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  refdes VARCHAR NOT NULL, -- a single reference designator
+  val VARCHAR, -- the part value placed here (if any)
+  line_id INTEGER NOT NULL, -- the BOM line it belongs to
+  FOREIGN KEY(line_id) REFERENCES parts_parts(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Split up the existing lines
+WITH RECURSIVE split(line_id, refdes, rest) AS (
+  SELECT id, '', trim(refdes) || ' ' FROM parts_parts
+  UNION ALL
+  SELECT line_id, substr(rest, 1, instr(rest, ' ') - 1), ltrim(substr(rest, instr(rest, ' ') + 1))
+  FROM split WHERE rest <> ''
+)
+INSERT INTO bom_refdes (refdes, line_id) SELECT refdes, line_id FROM split WHERE refdes <> '';
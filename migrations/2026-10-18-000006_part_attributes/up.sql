@@ -0,0 +1,11 @@
+-- Parametric attributes of a part. i.e. package, tolerance, voltage
+CREATE TABLE part_attributes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  name VARCHAR NOT NULL, -- lower case name. i.e. package
+  value VARCHAR NOT NULL, -- i.e. 0402 or 16V
+  part_id INTEGER NOT NULL, -- the part it describes
+  UNIQUE(part_id, name),
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
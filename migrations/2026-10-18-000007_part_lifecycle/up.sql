@@ -0,0 +1,4 @@
+-- Where a part is in its life: active, nrnd, ltb or obsolete
+ALTER TABLE parts ADD COLUMN lifecycle VARCHAR NOT NULL DEFAULT 'active';
+-- Last day the part can be ordered (if any)
+ALTER TABLE parts ADD COLUMN last_time_buy DATE;
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE new_inventories (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  quantity INTEGER NOT NULL, -- how much there are available
+  consumed INTEGER NOT NULL DEFAULT 0, -- how many that were consumed
+  unit_price REAL, -- the unit price
+  notes TEXT, -- notes
+  part_ver INTEGER NOT NULL, -- the version of the part this is referring to..
+  part_id INTEGER NOT NULL, -- the part that is associated with the inventory
+  FOREIGN KEY(part_id) REFERENCES parts(id) --only one part associated with this inventory (many to one)
+);
+
+INSERT INTO new_inventories SELECT id, created_at, updated_at, quantity, consumed, unit_price, notes, part_ver, part_id FROM inventories;
+DROP TABLE IF EXISTS inventories;
+ALTER TABLE new_inventories RENAME TO inventories;
+
+DROP TABLE part_alternates;
@@ -0,0 +1,17 @@
+-- Approved manufacturer list. Other MPNs that can be used for a part.
+CREATE TABLE part_alternates (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  mpn VARCHAR NOT NULL, -- manufacturer part number of the alternate
+  priority INTEGER NOT NULL, -- lower is used first. The part's own MPN is 0
+  part_id INTEGER NOT NULL, -- the part it can be used for
+  manufacturer_id INTEGER REFERENCES manufacturers(id) ON DELETE SET NULL, -- who makes it (if known)
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- An MPN can only be approved for one part
+CREATE UNIQUE INDEX part_alternates_manufacturer_mpn ON part_alternates (IFNULL(manufacturer_id, 0), mpn);
+
+-- Which alternate was received. Blank for the part's own MPN.
+ALTER TABLE inventories ADD COLUMN alternate_id INTEGER REFERENCES part_alternates(id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- Parts that can be used instead of the one on a BOM line. i.e. a 25V cap while the 16V one is short
+CREATE TABLE bom_substitutions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  expires DATE, -- last day it can be used (if any)
+  line_id INTEGER NOT NULL, -- the BOM line
+  part_id INTEGER NOT NULL, -- the part that can be used instead
+  build_id INTEGER, -- only for this build (if set)
+  FOREIGN KEY(line_id) REFERENCES parts_parts(id) ON DELETE CASCADE,
+  FOREIGN KEY(part_id) REFERENCES parts(id) ON DELETE CASCADE,
+  FOREIGN KEY(build_id) REFERENCES builds(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX bom_substitutions_line_part_build ON bom_substitutions (line_id, part_id, IFNULL(build_id, 0));
@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE new_parts (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (datetime('now','localtime')),
+  pn VARCHAR UNIQUE NOT NULL, -- part number
+  mpn VARCHAR NOT NULL, -- manufacturer part number
+  digikeypn VARCHAR UNIQUE, -- digikey part number
+  descr VARCHAR NOT NULL, -- description
+  ver INTEGER NOT NULL, -- version of part
+  val VARCHAR, -- stores the part value (if any)
+  mqty INTEGER NOT NULL DEFAULT 1,
+  lifecycle VARCHAR NOT NULL DEFAULT 'active',
+  last_time_buy DATE,
+  manufacturer_id INTEGER REFERENCES manufacturers(id) ON DELETE SET NULL -- who makes it (if known)
+);
+
+INSERT INTO new_parts SELECT id, created_at, updated_at, pn, mpn, digikeypn, descr, ver, val, mqty, lifecycle, last_time_buy, manufacturer_id FROM parts;
+DROP TABLE IF EXISTS parts;
+ALTER TABLE new_parts RENAME TO parts;
+
+CREATE UNIQUE INDEX parts_manufacturer_mpn ON parts (IFNULL(manufacturer_id, 0), mpn);
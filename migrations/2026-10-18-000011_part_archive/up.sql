@@ -0,0 +1,2 @@
+-- Archived parts are kept for history but hidden from listings
+ALTER TABLE parts ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
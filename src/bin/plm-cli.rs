@@ -1,5 +1,5 @@
 use clap::{crate_version, Clap};
-use plm_rs::{config, establish_connection, prompt, tables::*, Application};
+use plm_rs::{config, establish_connection, models, prompt, tables::*, Application};
 use std::io;
 
 #[derive(Clap)]
@@ -20,8 +20,26 @@ enum SubCommand {
     Build(Build),
     Inventory(Inventory),
     Bom(Bom),
+    Report(Report),
 }
 
+/// A subcommand for reports across all parts and BOMs
+#[derive(Clap)]
+struct Report {
+    #[clap(subcommand)]
+    subcmd: ReportSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum ReportSubCommand {
+    Obsolescence(ObsolescenceReport),
+}
+
+/// List parts that aren't active with the BOMs and open builds they affect
+#[derive(Clap)]
+struct ObsolescenceReport {}
+
 /// A subcommand for installing configuration to $HOME
 #[derive(Clap)]
 struct Install {}
@@ -39,6 +57,12 @@ enum BomSubCommand {
     Import(ImportBom),
     Show(ShowBom),
     Export(ExportBom),
+    Diff(DiffBom),
+    Delete(DeleteBom),
+    Archive(ArchiveBom),
+    Line(Line),
+    Refdes(Refdes),
+    Sub(Substitution),
 }
 
 /// A subcommand for importing a bom from an Eagle .sch file
@@ -49,13 +73,47 @@ struct ExportBom {
     /// Version of the part in question
     #[clap(short, long)]
     version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Compress runs of refdes. i.e. R1-R5, R8
+    #[clap(long)]
+    ranges: bool,
 }
 
-/// A subcommand for importing a bom from an Eagle .sch file
+/// A subcommand for importing a bom from an Eagle .sch, KiCad .kicad_sch/.xml or .csv file
 #[derive(Clap)]
 struct ImportBom {
-    /// Path of .sch file to be imported
-    filename: String,
+    /// Path of .sch, .kicad_sch or .xml file to be imported
+    #[clap(required_unless_present = "csv")]
+    filename: Option<String>,
+    /// Import a BOM from a .csv file. Columns are set in the config
+    #[clap(long, conflicts_with_all = &["filename", "format", "variant", "all-variants"], requires = "pn")]
+    csv: Option<String>,
+    /// Part number of the BOM imported from a .csv file
+    #[clap(long, requires = "csv")]
+    pn: Option<String>,
+    /// Description of the BOM imported from a .csv file. Defaults to the existing one
+    #[clap(long, requires = "csv")]
+    desc: Option<String>,
+    /// Schematic format (eagle, kicad, kicad-netlist). Detected from the file extension by default
+    #[clap(short, long)]
+    format: Option<String>,
+    /// Import a specific assembly variant as PN-VARIANT
+    #[clap(long)]
+    variant: Option<String>,
+    /// Import every assembly variant, each as PN-VARIANT
+    #[clap(long, conflicts_with = "variant")]
+    all_variants: bool,
+    /// Show what would be imported without changing anything
+    #[clap(long)]
+    dry_run: bool,
+    /// What to do if the BOM already exists (ask, uprev, overwrite, abort). Overrides the config
+    #[clap(long)]
+    on_existing_bom: Option<config::OnExistingBom>,
+    /// What to do if a part is different in the database (ask, update, keep, fail). Overrides the config
+    #[clap(long)]
+    on_part_change: Option<config::OnPartChange>,
 }
 
 /// A subcommand for showing a bom from pn
@@ -66,6 +124,272 @@ struct ShowBom {
     /// Version of the part in question
     #[clap(short, long)]
     version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Show sub-assemblies indented underneath
+    #[clap(long)]
+    tree: bool,
+    /// Show the total raw material with sub-assemblies flattened out
+    #[clap(long, conflicts_with = "tree")]
+    flat: bool,
+}
+
+/// Compare two versions of a BOM or a schematic against the database
+#[derive(Clap)]
+struct DiffBom {
+    /// Part number of the BOM. Taken from the schematic with --file
+    part_number: Option<String>,
+    /// Version to compare from. Defaults to the newest one before --to that isn't archived (or the latest with --file)
+    #[clap(long)]
+    from: Option<i32>,
+    /// Version to compare to. Defaults to the latest
+    #[clap(long)]
+    to: Option<i32>,
+    /// Compare a .sch, .kicad_sch or .xml file against the database before importing it
+    #[clap(long, conflicts_with = "to")]
+    file: Option<String>,
+    /// Schematic format of --file (eagle, kicad, kicad-netlist). Detected from the file extension by default
+    #[clap(short, long, requires = "file")]
+    format: Option<String>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Output format (table, csv, json)
+    #[clap(short, long, default_value = "table")]
+    output: String,
+}
+
+/// Delete a version of a BOM
+#[derive(Clap)]
+struct DeleteBom {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Version to delete
+    #[clap(short, long)]
+    version: i32,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+}
+
+/// Archive a version of a BOM. It's kept but hidden from show and build create
+#[derive(Clap)]
+struct ArchiveBom {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Version to archive
+    #[clap(short, long)]
+    version: i32,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Restore an archived version instead
+    #[clap(long)]
+    restore: bool,
+}
+
+/// Add, remove or change BOM lines by hand
+#[derive(Clap)]
+struct Line {
+    #[clap(subcommand)]
+    subcmd: LineSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum LineSubCommand {
+    Add(AddLine),
+    Remove(RemoveLine),
+    Set(SetLine),
+}
+
+/// Add a part that isn't in the schematic. i.e. screws, enclosures, labels. Kept when the BOM is imported again
+#[derive(Clap)]
+struct AddLine {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Part number of the part to add
+    line_part_number: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Quantity used
+    #[clap(short, long, default_value = "1")]
+    quantity: i32,
+    /// Reference designators
+    #[clap(short, long)]
+    refdes: Option<String>,
+    /// Don't stuff the part
+    #[clap(long)]
+    nostuff: bool,
+}
+
+/// Remove a part from a BOM
+#[derive(Clap)]
+struct RemoveLine {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Part number of the part to remove
+    line_part_number: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Refdes of the line if the part is on more than one
+    #[clap(long)]
+    line: Option<String>,
+}
+
+/// Change the quantity, refdes or no stuff of a part on a BOM
+#[derive(Clap)]
+struct SetLine {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Part number of the part to change
+    line_part_number: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Refdes of the line if the part is on more than one
+    #[clap(long)]
+    line: Option<String>,
+    /// New quantity
+    #[clap(short, long)]
+    quantity: Option<i32>,
+    /// New reference designators
+    #[clap(short, long)]
+    refdes: Option<String>,
+    /// Don't stuff the part
+    #[clap(long)]
+    nostuff: bool,
+    /// Stuff the part
+    #[clap(long, conflicts_with = "nostuff")]
+    stuff: bool,
+}
+
+/// Allow other parts to be used on a BOM line. i.e. a 25V cap while the 16V one is short
+#[derive(Clap)]
+struct Substitution {
+    #[clap(subcommand)]
+    subcmd: SubstitutionSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum SubstitutionSubCommand {
+    Add(AddSubstitution),
+    Remove(RemoveSubstitution),
+}
+
+/// Allow a part to be used instead of the one on a line
+#[derive(Clap)]
+struct AddSubstitution {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Part number of the part on the line
+    line_part_number: String,
+    /// Part number of the part that can be used instead
+    substitute: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Refdes of the line if the part is on more than one
+    #[clap(long)]
+    line: Option<String>,
+    /// Last day it can be used. YYYY-MM-DD
+    #[clap(long)]
+    expires: Option<String>,
+    /// Only this build can use it
+    #[clap(short, long)]
+    build: Option<i32>,
+}
+
+/// Stop allowing a part to be used instead of the one on a line
+#[derive(Clap)]
+struct RemoveSubstitution {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Part number of the part on the line
+    line_part_number: String,
+    /// Part number of the substitute
+    substitute: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Refdes of the line if the part is on more than one
+    #[clap(long)]
+    line: Option<String>,
+    /// Only remove the one for this build
+    #[clap(short, long)]
+    build: Option<i32>,
+}
+
+/// Show or change a single reference designator
+#[derive(Clap)]
+struct Refdes {
+    #[clap(subcommand)]
+    subcmd: RefdesSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum RefdesSubCommand {
+    Show(ShowRefdes),
+    Set(SetRefdes),
+}
+
+/// Show what is placed at a reference designator
+#[derive(Clap)]
+struct ShowRefdes {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Reference designator. i.e. R14
+    refdes: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+}
+
+/// Change the value or no stuff of a reference designator
+#[derive(Clap)]
+struct SetRefdes {
+    /// Part number of the BOM in question
+    part_number: String,
+    /// Reference designator. i.e. R14
+    refdes: String,
+    /// Version of the BOM. Defaults to the latest
+    #[clap(short, long)]
+    version: Option<i32>,
+    /// Assembly variant of the BOM
+    #[clap(long)]
+    variant: Option<String>,
+    /// Value placed here. Blank clears it
+    #[clap(long)]
+    value: Option<String>,
+    /// Don't stuff it
+    #[clap(long)]
+    nostuff: bool,
+    /// Stuff it
+    #[clap(long, conflicts_with = "nostuff")]
+    stuff: bool,
 }
 
 /// A subcommand for adding/modifying/removing parts
@@ -82,6 +406,127 @@ enum PartsSubCommand {
     Delete(DeleteParts),
     Show(ShowParts),
     Rename(RenamePart),
+    Supplier(Supplier),
+    WhereUsed(WhereUsed),
+    Search(SearchParts),
+    Attribute(PartAttribute),
+    Lifecycle(SetLifecycle),
+    Alt(Alternate),
+    Merge(MergeParts),
+    Archive(ArchivePart),
+}
+
+/// Add, remove or list approved alternate MPNs of a part
+#[derive(Clap)]
+struct Alternate {
+    #[clap(subcommand)]
+    subcmd: AlternateSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum AlternateSubCommand {
+    Add(AddAlternate),
+    Remove(RemoveAlternate),
+    List(ListAlternates),
+}
+
+/// Approve another MPN for a part
+#[derive(Clap)]
+struct AddAlternate {
+    /// Part number of the part in question
+    part_number: String,
+    /// The alternate's manufacturer part number
+    mpn: String,
+    /// Who makes the alternate
+    #[clap(long)]
+    manufacturer: Option<String>,
+    /// Lower is used first. The part's own MPN is 0. Defaults to after the last alternate
+    #[clap(long)]
+    priority: Option<i32>,
+}
+
+/// Remove an approved alternate
+#[derive(Clap)]
+struct RemoveAlternate {
+    /// Part number of the part in question
+    part_number: String,
+    /// The alternate's manufacturer part number
+    mpn: String,
+    /// Only needed if more than one manufacturer makes the MPN
+    #[clap(long)]
+    manufacturer: Option<String>,
+}
+
+/// List the approved MPNs of a part with what's on hand
+#[derive(Clap)]
+struct ListAlternates {
+    /// Part number of the part in question
+    part_number: String,
+}
+
+/// Set where a part is in its life
+#[derive(Clap)]
+struct SetLifecycle {
+    /// Part number of the part in question
+    part_number: String,
+    /// active, nrnd, ltb (last time buy) or obsolete
+    lifecycle: models::Lifecycle,
+    /// Last day it can be ordered. YYYY-MM-DD
+    #[clap(long)]
+    ltb: Option<String>,
+}
+
+/// Set or remove parametric attributes of a part
+#[derive(Clap)]
+struct PartAttribute {
+    #[clap(subcommand)]
+    subcmd: PartAttributeSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum PartAttributeSubCommand {
+    Set(SetPartAttribute),
+    Remove(RemovePartAttribute),
+}
+
+/// Add or change a parametric attribute. i.e. package 0402
+#[derive(Clap)]
+struct SetPartAttribute {
+    /// Part number of the part in question
+    part_number: String,
+    /// Name of the attribute. i.e. package, tolerance, voltage
+    name: String,
+    /// The value. i.e. 0402, 1%, 16V
+    value: String,
+}
+
+/// Remove a parametric attribute
+#[derive(Clap)]
+struct RemovePartAttribute {
+    /// Part number of the part in question
+    part_number: String,
+    /// Name of the attribute
+    name: String,
+}
+
+/// Search parts by PN, MPN, description, value or supplier PN
+#[derive(Clap)]
+struct SearchParts {
+    /// Terms that all have to match. i.e. `10k`, `mpn:GRM*`, `desc:/\d+V/` or `voltage>=16V`
+    #[clap(required = true)]
+    query: Vec<String>,
+    /// Include archived parts
+    #[clap(long)]
+    archived: bool,
+}
+
+/// List every BOM that uses a part, up to the top-level products
+#[derive(Clap)]
+struct WhereUsed {
+    /// Part number of the part in question
+    part_number: String,
 }
 
 /// Create parts manually
@@ -90,20 +535,84 @@ struct CreateParts {
     /// Create part from a .csv file
     #[clap(short, long)]
     filename: Option<String>,
+    /// What to do if a part is different in the database (ask, update, keep, fail). Overrides the config
+    #[clap(long)]
+    on_part_change: Option<config::OnPartChange>,
+}
+
+/// Delete parts manually. Parts in use need --force.
+#[derive(Clap)]
+struct DeleteParts {
+    /// Delete it even if BOMs, inventory or builds use it. They're deleted too.
+    #[clap(long)]
+    force: bool,
 }
 
-/// Delete parts manually
+/// Show all parts or the details of one
 #[derive(Clap)]
-struct DeleteParts {}
+struct ShowParts {
+    /// Show the suppliers, attributes and inventory of this part
+    part_number: Option<String>,
+    /// Include archived parts
+    #[clap(long)]
+    archived: bool,
+}
 
-/// Show all parts
+/// Archive a part. It's kept for history but hidden from show and search
 #[derive(Clap)]
-struct ShowParts {}
+struct ArchivePart {
+    /// Part number of the part in question
+    part_number: String,
+    /// Restore an archived part instead
+    #[clap(long)]
+    restore: bool,
+}
 
 /// Rename a part
 #[derive(Clap)]
 struct RenamePart {}
 
+/// Merge a duplicate part into another. Its PN becomes an alias of the one kept.
+#[derive(Clap)]
+struct MergeParts {
+    /// Part number of the part that's kept
+    keep: String,
+    /// Part number of the duplicate. It's deleted.
+    drop: String,
+}
+
+/// Add or list supplier part numbers
+#[derive(Clap)]
+struct Supplier {
+    #[clap(subcommand)]
+    subcmd: SupplierSubCommand,
+}
+
+#[derive(Clap)]
+#[clap(version = crate_version!())]
+enum SupplierSubCommand {
+    Add(AddSupplier),
+    List(ListSuppliers),
+}
+
+/// Add or change the supplier part number of a part
+#[derive(Clap)]
+struct AddSupplier {
+    /// Part number of the part in question
+    part_number: String,
+    /// Name of the supplier. i.e. DIGIKEY, MOUSER, LCSC
+    supplier: String,
+    /// The supplier's part number
+    supplier_pn: String,
+}
+
+/// List supplier part numbers
+#[derive(Clap)]
+struct ListSuppliers {
+    /// Only list the supplier part numbers of this part
+    part_number: Option<String>,
+}
+
 #[derive(Clap)]
 #[clap(version = crate_version!())]
 enum BuildSubCommand {
@@ -116,7 +625,11 @@ enum BuildSubCommand {
 
 /// Create build manually
 #[derive(Clap)]
-struct CreateBuild {}
+struct CreateBuild {
+    /// Assembly variant of the BOM to build
+    #[clap(long)]
+    variant: Option<String>,
+}
 
 /// Delete build manually
 #[derive(Clap)]
@@ -244,11 +757,18 @@ fn main() {
 
             config = config::Config {
                 database_name: db_name,
-                library_name: "your-library".to_string(),
+                library_name: None,
+                part_number_ignore_list: Vec::new(),
+                suppliers: config::default_suppliers(),
+                libraries: vec![config::LibraryConfig {
+                    name: "your-library".to_string(),
+                    ..Default::default()
+                }],
+                import_policy: Default::default(),
+                csv_columns: Default::default(),
                 attrition_config: config::AttritionConfig {
                     entries: Vec::new(),
                 },
-                part_number_ignore_list: Vec::new(),
             };
 
             // Install the config
@@ -301,8 +821,8 @@ fn main() {
     // Then run the command.
     match opts.subcmd {
         SubCommand::Build(s) => match s.subcmd {
-            BuildSubCommand::Create(_) => {
-                builds::create(&mut app);
+            BuildSubCommand::Create(a) => {
+                builds::create(&mut app, &a.variant);
             }
             // TODO: take the next argument after delete instead of needing a flag...
             BuildSubCommand::Delete(a) => {
@@ -345,33 +865,277 @@ fn main() {
                 }
             }
         },
-        // TODO: Search for a part
         SubCommand::Parts(s) => match s.subcmd {
             PartsSubCommand::Create(a) => match a.filename {
-                Some(x) => parts::create_by_csv(&mut app, &x),
+                Some(x) => {
+                    let on_part_change = a
+                        .on_part_change
+                        .unwrap_or(app.config.import_policy.on_part_change);
+                    parts::create_by_csv(&mut app, &x, on_part_change)
+                }
                 None => parts::create(&mut app),
             },
-            PartsSubCommand::Delete(_) => {
-                parts::delete(&mut app);
+            PartsSubCommand::Delete(a) => {
+                parts::delete(&mut app, a.force);
             }
-            PartsSubCommand::Show(_) => {
-                parts::show(&mut app);
+            PartsSubCommand::Show(a) => match a.part_number {
+                Some(pn) => parts::show_part(&mut app, &pn),
+                None => parts::show(&mut app, a.archived),
+            },
+            PartsSubCommand::Archive(a) => {
+                parts::archive(&mut app, &a.part_number, a.restore);
             }
             PartsSubCommand::Rename(_) => {
                 parts::rename(&mut app);
             }
+            PartsSubCommand::Merge(a) => {
+                parts::merge(&mut app, &a.keep, &a.drop);
+            }
+            PartsSubCommand::WhereUsed(a) => {
+                parts::where_used(&mut app, &a.part_number);
+            }
+            PartsSubCommand::Search(a) => {
+                parts::search(&mut app, &a.query, a.archived);
+            }
+            PartsSubCommand::Lifecycle(a) => {
+                parts::set_lifecycle(&mut app, &a.part_number, a.lifecycle, &a.ltb);
+            }
+            PartsSubCommand::Attribute(a) => match a.subcmd {
+                PartAttributeSubCommand::Set(b) => {
+                    parts::set_attribute(&mut app, &b.part_number, &b.name, &b.value);
+                }
+                PartAttributeSubCommand::Remove(b) => {
+                    parts::remove_attribute(&mut app, &b.part_number, &b.name);
+                }
+            },
+            PartsSubCommand::Alt(a) => match a.subcmd {
+                AlternateSubCommand::Add(b) => {
+                    parts::add_alternate(
+                        &mut app,
+                        &b.part_number,
+                        &b.mpn,
+                        &b.manufacturer,
+                        b.priority,
+                    );
+                }
+                AlternateSubCommand::Remove(b) => {
+                    parts::remove_alternate(&mut app, &b.part_number, &b.mpn, &b.manufacturer);
+                }
+                AlternateSubCommand::List(b) => {
+                    parts::show_alternates(&mut app, &b.part_number);
+                }
+            },
+            PartsSubCommand::Supplier(a) => match a.subcmd {
+                SupplierSubCommand::Add(b) => {
+                    parts::add_supplier(&mut app, &b.part_number, &b.supplier, &b.supplier_pn);
+                }
+                SupplierSubCommand::List(b) => {
+                    parts::show_suppliers(&mut app, &b.part_number);
+                }
+            },
+        },
+        SubCommand::Report(s) => match s.subcmd {
+            ReportSubCommand::Obsolescence(_) => {
+                report::obsolescence(&mut app);
+            }
         },
         SubCommand::Bom(s) => match s.subcmd {
             BomSubCommand::Import(a) => {
-                bom::import(&mut app, &a.filename);
+                let selection = match a.variant {
+                    Some(v) => bom::VariantSelection::Named(v),
+                    None if a.all_variants => bom::VariantSelection::All,
+                    None => bom::VariantSelection::Current,
+                };
+
+                let mut policy = app.config.import_policy;
+                if let Some(p) = a.on_existing_bom {
+                    policy.on_existing_bom = p;
+                }
+                if let Some(p) = a.on_part_change {
+                    policy.on_part_change = p;
+                }
+
+                match (a.csv, a.filename) {
+                    (Some(csv), _) => bom::import_csv(
+                        &mut app,
+                        &csv,
+                        &a.pn.unwrap_or_default(),
+                        &a.desc,
+                        &policy,
+                        a.dry_run,
+                    ),
+                    (None, Some(filename)) => bom::import(
+                        &mut app, &filename, &a.format, &selection, &policy, a.dry_run,
+                    ),
+                    (None, None) => {}
+                }
             }
             BomSubCommand::Export(a) => {
-                bom::export(&mut app, &a.name, &a.version);
+                let part_number = bom::part_number_for_variant(&a.name, &a.variant);
+                bom::export(&mut app, &part_number, &a.version, a.ranges);
             }
             BomSubCommand::Show(a) => {
                 // Note: version is borrowed as an Option
                 // not required for this command to work
-                bom::show(&mut app, &a.part_number, &a.version);
+                let part_number = bom::part_number_for_variant(&a.part_number, &a.variant);
+
+                if a.tree {
+                    bom::show_tree(&mut app, &part_number, &a.version);
+                } else if a.flat {
+                    bom::show_flat(&mut app, &part_number, &a.version);
+                } else {
+                    bom::show(&mut app, &part_number, &a.version);
+                }
+            }
+            BomSubCommand::Delete(a) => {
+                let part_number = bom::part_number_for_variant(&a.part_number, &a.variant);
+                bom::delete(&mut app, &part_number, &a.version);
+            }
+            BomSubCommand::Line(a) => match a.subcmd {
+                LineSubCommand::Add(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+                    bom::add_line(
+                        &mut app,
+                        &part_number,
+                        &b.version,
+                        &b.line_part_number,
+                        b.quantity,
+                        &b.refdes.unwrap_or_default(),
+                        b.nostuff,
+                    );
+                }
+                LineSubCommand::Remove(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+                    bom::remove_line(
+                        &mut app,
+                        &part_number,
+                        &b.version,
+                        &b.line_part_number,
+                        &b.line,
+                    );
+                }
+                LineSubCommand::Set(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+
+                    let nostuff = match (b.nostuff, b.stuff) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    };
+
+                    let change = bom::LineChange {
+                        quantity: b.quantity,
+                        refdes: b.refdes,
+                        nostuff,
+                    };
+
+                    bom::set_line(
+                        &mut app,
+                        &part_number,
+                        &b.version,
+                        &b.line_part_number,
+                        &b.line,
+                        &change,
+                    );
+                }
+            },
+            BomSubCommand::Sub(a) => match a.subcmd {
+                SubstitutionSubCommand::Add(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+
+                    let substitute = bom::Substitute {
+                        part_number: b.substitute,
+                        expires: b.expires,
+                        build_id: b.build,
+                    };
+
+                    bom::add_substitution(
+                        &mut app,
+                        &part_number,
+                        &b.version,
+                        &b.line_part_number,
+                        &b.line,
+                        &substitute,
+                    );
+                }
+                SubstitutionSubCommand::Remove(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+                    bom::remove_substitution(
+                        &mut app,
+                        &part_number,
+                        &b.version,
+                        &b.line_part_number,
+                        &b.line,
+                        &b.substitute,
+                        b.build,
+                    );
+                }
+            },
+            BomSubCommand::Refdes(a) => match a.subcmd {
+                RefdesSubCommand::Show(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+                    bom::show_refdes(&mut app, &part_number, &b.version, &b.refdes);
+                }
+                RefdesSubCommand::Set(b) => {
+                    let part_number = bom::part_number_for_variant(&b.part_number, &b.variant);
+
+                    let nostuff = match (b.nostuff, b.stuff) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    };
+
+                    let change = bom::RefdesChange {
+                        nostuff,
+                        value: b.value,
+                    };
+
+                    bom::set_refdes(&mut app, &part_number, &b.version, &b.refdes, &change);
+                }
+            },
+            BomSubCommand::Archive(a) => {
+                let part_number = bom::part_number_for_variant(&a.part_number, &a.variant);
+                bom::archive(&mut app, &part_number, &a.version, a.restore);
+            }
+            BomSubCommand::Diff(a) => {
+                let output: diff::DiffFormat = match a.output.parse() {
+                    Ok(f) => f,
+                    Err(e) => {
+                        println!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                match (a.file, a.part_number) {
+                    (Some(file), part_number) => {
+                        let variant = a.variant;
+                        let part_number =
+                            part_number.map(|pn| bom::part_number_for_variant(&pn, &variant));
+                        let selection = match variant {
+                            Some(v) => bom::VariantSelection::Named(v),
+                            None => bom::VariantSelection::Current,
+                        };
+
+                        let source = bom::open_source(&file, &a.format);
+                        diff::file(
+                            &mut app,
+                            source.as_ref(),
+                            &file,
+                            &part_number,
+                            &a.from,
+                            &selection,
+                            output,
+                        );
+                    }
+                    (None, Some(part_number)) => {
+                        let part_number = bom::part_number_for_variant(&part_number, &a.variant);
+                        diff::versions(&mut app, &part_number, &a.from, &a.to, output);
+                    }
+                    (None, None) => {
+                        println!("A part number or --file is required!");
+                        std::process::exit(1);
+                    }
+                }
             }
         },
 
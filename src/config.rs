@@ -1,5 +1,6 @@
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 #[derive(Debug, Serialize, Deserialize)]
 pub enum AttritionType {
@@ -18,20 +19,179 @@ pub struct AttritionConfig {
     pub entries: Vec<AttritionEntry>,
 }
 
+/// A library that parts are resolved from
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LibraryConfig {
+    /// Library name
+    pub name: String,
+
+    /// Renames library attributes. i.e. `MFR_PN = "MPN"`
+    #[serde(default)]
+    pub attribute_map: HashMap<String, String>,
+}
+
+/// What an import does with a BOM that already exists
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExistingBom {
+    /// Ask on stdin
+    #[default]
+    Ask,
+    /// Import as a new version
+    Uprev,
+    /// Replace the current version
+    Overwrite,
+    /// Stop with an error
+    Abort,
+}
+
+impl std::str::FromStr for OnExistingBom {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(OnExistingBom::Ask),
+            "uprev" => Ok(OnExistingBom::Uprev),
+            "overwrite" => Ok(OnExistingBom::Overwrite),
+            "abort" => Ok(OnExistingBom::Abort),
+            _ => Err(anyhow!(
+                "Unknown policy {}. Use ask, uprev, overwrite or abort",
+                s
+            )),
+        }
+    }
+}
+
+/// What an import does with a part that's different in the database
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnPartChange {
+    /// Ask on stdin
+    #[default]
+    Ask,
+    /// Update the part
+    Update,
+    /// Leave the part as it is
+    Keep,
+    /// Stop with an error
+    Fail,
+}
+
+impl std::str::FromStr for OnPartChange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(OnPartChange::Ask),
+            "update" => Ok(OnPartChange::Update),
+            "keep" => Ok(OnPartChange::Keep),
+            "fail" => Ok(OnPartChange::Fail),
+            _ => Err(anyhow!(
+                "Unknown policy {}. Use ask, update, keep or fail",
+                s
+            )),
+        }
+    }
+}
+
+/// Decides what imports do instead of asking. Useful for running unattended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportPolicy {
+    #[serde(default)]
+    pub on_existing_bom: OnExistingBom,
+
+    #[serde(default)]
+    pub on_part_change: OnPartChange,
+}
+
+/// Column headers of a CSV BOM. Matched without case.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CsvColumns {
+    /// Reference designators. i.e. `R1 R2` or `R1,R2`
+    pub refdes: String,
+    /// Quantity. Defaults to the number of reference designators
+    pub quantity: String,
+    /// Part number
+    pub pn: String,
+    /// Manufacturer part number
+    pub mpn: String,
+    /// Manufacturer. Only needed when the MPN isn't enough to find the part.
+    pub manufacturer: String,
+    /// Part description
+    pub descr: String,
+    /// Do not populate
+    pub dnp: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        CsvColumns {
+            refdes: "Refdes".to_string(),
+            quantity: "Qty".to_string(),
+            pn: "PN".to_string(),
+            mpn: "MPN".to_string(),
+            manufacturer: "Manufacturer".to_string(),
+            descr: "Description".to_string(),
+            dnp: "DNP".to_string(),
+        }
+    }
+}
+
 /// Config that can be installed locally
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     /// Name of database within config folder
     pub database_name: String,
 
-    /// Library name
-    pub library_name: String,
+    /// Library name. Used by older configs. See `libraries`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub library_name: Option<String>,
+
+    /// Ignore parts that contain one of these
+    pub part_number_ignore_list: Vec<String>,
+
+    /// Suppliers picked up from `<SUPPLIER>PN` library attributes. i.e. `DIGIKEY` reads `DIGIKEYPN`
+    #[serde(default = "default_suppliers")]
+    pub suppliers: Vec<String>,
+
+    /// Libraries to resolve parts from in priority order
+    #[serde(default)]
+    pub libraries: Vec<LibraryConfig>,
+
+    /// What imports do instead of asking
+    #[serde(default)]
+    pub import_policy: ImportPolicy,
+
+    /// Columns used by CSV BOM imports
+    #[serde(default)]
+    pub csv_columns: CsvColumns,
 
     /// Attrition config
     pub attrition_config: AttritionConfig,
+}
 
-    /// Ignore parts that contain one of these
-    pub part_number_ignore_list: Vec<String>,
+/// Digikey is the only supplier older configs know about
+pub fn default_suppliers() -> Vec<String> {
+    vec!["DIGIKEY".to_string()]
+}
+
+impl Config {
+    /// All libraries in priority order. `library_name` goes last.
+    pub fn libraries(&self) -> Vec<LibraryConfig> {
+        let mut libraries = self.libraries.clone();
+
+        if let Some(name) = &self.library_name {
+            if !libraries.iter().any(|l| &l.name == name) {
+                libraries.push(LibraryConfig {
+                    name: name.clone(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        libraries
+    }
 }
 
 /// Set config
@@ -90,3 +250,72 @@ pub fn get_default_config_path() -> anyhow::Result<PathBuf> {
     // Return it
     Ok(config_path)
 }
+
+#[test]
+fn test_config_round_trip() {
+    let mut attribute_map = HashMap::new();
+    attribute_map.insert("MFR_PN".to_string(), "MPN".to_string());
+
+    let config = Config {
+        database_name: "database.db".to_string(),
+        library_name: None,
+        part_number_ignore_list: vec!["GND".to_string()],
+        suppliers: vec!["DIGIKEY".to_string(), "MOUSER".to_string()],
+        libraries: vec![
+            LibraryConfig {
+                name: "first".to_string(),
+                attribute_map,
+            },
+            LibraryConfig {
+                name: "second".to_string(),
+                ..Default::default()
+            },
+        ],
+        import_policy: ImportPolicy {
+            on_existing_bom: OnExistingBom::Uprev,
+            on_part_change: OnPartChange::Fail,
+        },
+        csv_columns: CsvColumns {
+            pn: "Part Number".to_string(),
+            ..Default::default()
+        },
+        attrition_config: AttritionConfig {
+            entries: Vec::new(),
+        },
+    };
+
+    let config: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+
+    assert_eq!(2, config.libraries().len());
+    assert_eq!("MPN", config.libraries()[0].attribute_map["MFR_PN"]);
+    assert_eq!(OnExistingBom::Uprev, config.import_policy.on_existing_bom);
+    assert_eq!(OnPartChange::Fail, config.import_policy.on_part_change);
+    assert_eq!("Part Number", config.csv_columns.pn);
+    assert_eq!("Qty", config.csv_columns.quantity);
+    assert_eq!(vec!["DIGIKEY", "MOUSER"], config.suppliers);
+}
+
+#[test]
+fn test_config_legacy_library_name() {
+    let config = r#"
+database_name = "database.db"
+library_name = "legacy"
+part_number_ignore_list = []
+
+[[libraries]]
+name = "first"
+
+[attrition_config]
+entries = []
+"#;
+
+    let config: Config = toml::from_str(config).unwrap();
+    let names: Vec<String> = config.libraries().into_iter().map(|l| l.name).collect();
+
+    assert_eq!(vec!["first", "legacy"], names);
+
+    // Older configs ask
+    assert_eq!(ImportPolicy::default(), config.import_policy);
+    assert_eq!(CsvColumns::default(), config.csv_columns);
+    assert_eq!(default_suppliers(), config.suppliers);
+}
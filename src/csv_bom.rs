@@ -0,0 +1,281 @@
+use anyhow::anyhow;
+
+use crate::config::CsvColumns;
+use crate::schematic::{Attribute, Part, SchematicSource, VariantDef};
+
+// Used to import BOMs that come as a spreadsheet rather than a
+// schematic. i.e. mechanical or customer supplied BOMs. Each
+// row is turned into placed parts so it imports like any other.
+
+const DEFAULT_VARIANT: &str = "default";
+
+/// A single line of a CSV BOM
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Row {
+    /// Line in the file. Used for errors.
+    pub line: usize,
+    pub refdes: Vec<String>,
+    pub quantity: i32,
+    /// Blank if only the MPN is known
+    pub pn: String,
+    pub mpn: Option<String>,
+    pub manufacturer: Option<String>,
+    pub descr: Option<String>,
+    pub dnp: Option<String>,
+}
+
+/// A BOM read from a CSV file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsvBom {
+    pub pn: String,
+    pub descr: String,
+    pub rows: Vec<Row>,
+}
+
+/// Finds a column by name without case
+fn find_column(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// Gets a (non blank) value from a column
+fn get_value(record: &csv::StringRecord, column: Option<usize>) -> Option<String> {
+    column
+        .and_then(|c| record.get(c))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+}
+
+impl CsvBom {
+    /// Reads a BOM using the configured column names. Only a PN or MPN column is required.
+    pub fn from_reader<R: std::io::Read>(
+        reader: R,
+        columns: &CsvColumns,
+        pn: &str,
+        descr: &str,
+    ) -> anyhow::Result<CsvBom> {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+
+        let headers = reader.headers()?.clone();
+
+        let refdes_column = find_column(&headers, &columns.refdes);
+        let quantity_column = find_column(&headers, &columns.quantity);
+        let pn_column = find_column(&headers, &columns.pn);
+        let mpn_column = find_column(&headers, &columns.mpn);
+        let manufacturer_column = find_column(&headers, &columns.manufacturer);
+        let descr_column = find_column(&headers, &columns.descr);
+        let dnp_column = find_column(&headers, &columns.dnp);
+
+        if pn_column.is_none() && mpn_column.is_none() {
+            return Err(anyhow!(
+                "Unable to find a {} or {} column",
+                columns.pn,
+                columns.mpn
+            ));
+        }
+
+        let mut rows = Vec::new();
+
+        for (index, record) in reader.records().enumerate() {
+            let record = record?;
+
+            // Headers are line 1
+            let line = index + 2;
+
+            let refdes: Vec<String> = get_value(&record, refdes_column)
+                .unwrap_or_default()
+                .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+                .filter(|r| !r.is_empty())
+                .map(String::from)
+                .collect();
+
+            let pn = get_value(&record, pn_column).unwrap_or_default();
+            let mpn = get_value(&record, mpn_column);
+
+            // Skip blank lines, totals, etc
+            if pn.is_empty() && mpn.is_none() {
+                if refdes.is_empty() {
+                    continue;
+                }
+
+                return Err(anyhow!(
+                    "Line {} has no {} or {}",
+                    line,
+                    columns.pn,
+                    columns.mpn
+                ));
+            }
+
+            // Quantity defaults to the number of refdes
+            let quantity = match get_value(&record, quantity_column) {
+                Some(q) => q
+                    .parse::<i32>()
+                    .map_err(|_| anyhow!("Line {} has an invalid quantity: {}", line, q))?,
+                None => refdes.len().max(1) as i32,
+            };
+
+            if !refdes.is_empty() && quantity != refdes.len() as i32 {
+                return Err(anyhow!(
+                    "Line {} has a quantity of {} but {} refdes",
+                    line,
+                    quantity,
+                    refdes.len()
+                ));
+            }
+
+            rows.push(Row {
+                line,
+                refdes,
+                quantity,
+                pn,
+                mpn,
+                manufacturer: get_value(&record, manufacturer_column),
+                descr: get_value(&record, descr_column),
+                dnp: get_value(&record, dnp_column),
+            });
+        }
+
+        Ok(CsvBom {
+            pn: pn.to_string(),
+            descr: descr.to_string(),
+            rows,
+        })
+    }
+}
+
+impl SchematicSource for CsvBom {
+    /// One part per refdes. Rows without refdes are one part with MQTY set to the quantity.
+    fn parts(&self) -> Vec<Part> {
+        let mut parts = Vec::new();
+
+        for row in &self.rows {
+            let mut attributes = Vec::new();
+
+            let values = [
+                ("MPN", &row.mpn),
+                ("MANUFACTURER", &row.manufacturer),
+                ("DESC", &row.descr),
+                ("DNP", &row.dnp),
+            ];
+            for (name, value) in values.iter() {
+                if let Some(value) = value {
+                    attributes.push(Attribute {
+                        name: name.to_string(),
+                        value: value.clone(),
+                    });
+                }
+            }
+
+            let part = |name: &str, attributes: Vec<Attribute>| Part {
+                name: name.to_string(),
+                deviceset: row.pn.clone(),
+                technology: None,
+                device: "".to_string(),
+                value: None,
+                variants: Vec::new(),
+                attributes,
+                library: None,
+            };
+
+            if row.refdes.is_empty() {
+                let mut attributes = attributes.clone();
+                attributes.push(Attribute {
+                    name: "MQTY".to_string(),
+                    value: row.quantity.to_string(),
+                });
+
+                parts.push(part("", attributes));
+            } else {
+                for refdes in &row.refdes {
+                    parts.push(part(refdes, attributes.clone()));
+                }
+            }
+        }
+
+        parts
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        vec![
+            Attribute {
+                name: "PN".to_string(),
+                value: self.pn.clone(),
+            },
+            Attribute {
+                name: "DESC".to_string(),
+                value: self.descr.clone(),
+            },
+        ]
+    }
+
+    fn variants(&self) -> Vec<VariantDef> {
+        vec![VariantDef {
+            name: DEFAULT_VARIANT.to_string(),
+            current: Some("yes".to_string()),
+        }]
+    }
+
+    /// Everything comes from the rows. There are no libraries.
+    fn library_attributes(&self, _library: &str, _part_number: &str) -> Option<Vec<Attribute>> {
+        None
+    }
+}
+
+#[test]
+fn test_csv_bom_columns() {
+    let csv = "\
+Item,Designator,Quantity,Part Number,Mfr PN,Manufacturer,Description,DNP
+1,\"R1, R2\",2,RES-10K,RC0402-10K,Yageo,RES 10K,
+2,C1,,CAP-1U,,,,yes
+3,,4,SCREW-M3,,,Screw M3,
+,,,,,,Total,
+";
+
+    let columns = CsvColumns {
+        refdes: "designator".to_string(),
+        quantity: "quantity".to_string(),
+        pn: "part number".to_string(),
+        mpn: "mfr pn".to_string(),
+        ..Default::default()
+    };
+
+    let bom = CsvBom::from_reader(csv.as_bytes(), &columns, "ASSY-1", "Assembly")
+        .expect("Unable to read");
+
+    assert_eq!(3, bom.rows.len());
+    assert_eq!(vec!["R1", "R2"], bom.rows[0].refdes);
+    assert_eq!(Some("RC0402-10K".to_string()), bom.rows[0].mpn);
+    assert_eq!(Some("Yageo".to_string()), bom.rows[0].manufacturer);
+    assert_eq!(1, bom.rows[1].quantity);
+    assert_eq!(None, bom.rows[1].mpn);
+    assert_eq!(Some("yes".to_string()), bom.rows[1].dnp);
+    assert_eq!(4, bom.rows[2].quantity);
+    assert_eq!(4, bom.rows[2].line);
+
+    let parts = bom.parts();
+    let names: Vec<&str> = parts.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(vec!["R1", "R2", "C1", ""], names);
+    assert_eq!("SCREW-M3", parts[3].deviceset);
+    assert!(parts[3]
+        .attributes
+        .iter()
+        .any(|a| a.name == "MQTY" && a.value == "4"));
+}
+
+#[test]
+fn test_csv_bom_errors() {
+    let columns = CsvColumns::default();
+
+    let csv = "Refdes,Qty,PN\nR1 R2,3,RES-10K\n";
+    let error = CsvBom::from_reader(csv.as_bytes(), &columns, "ASSY-1", "").unwrap_err();
+    assert_eq!("Line 2 has a quantity of 3 but 2 refdes", error.to_string());
+
+    let csv = "Refdes,Qty,PN\nR1,one,RES-10K\n";
+    let error = CsvBom::from_reader(csv.as_bytes(), &columns, "ASSY-1", "").unwrap_err();
+    assert_eq!("Line 2 has an invalid quantity: one", error.to_string());
+
+    let csv = "Refdes,Qty\nR1,1\n";
+    assert!(CsvBom::from_reader(csv.as_bytes(), &columns, "ASSY-1", "").is_err());
+}
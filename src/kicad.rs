@@ -0,0 +1,632 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::schematic::{Attribute, Part, SchematicSource, Variant, VariantDef};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+// Used to parse KiCad 6+ .kicad_sch files and the XML
+// netlist/BOM export. Both are boiled down to a flat list
+// of placed symbols with their fields.
+
+/// A single placed symbol from a KiCad schematic
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Symbol {
+    pub reference: String,
+    pub lib_id: String,
+    pub value: String,
+    pub fields: Vec<Attribute>,
+    pub in_bom: bool,
+    pub dnp: bool,
+}
+
+impl Symbol {
+    /// Name of the symbol within its library. Used as the part number.
+    pub fn name(&self) -> &str {
+        match self.lib_id.split_once(':') {
+            Some((_, name)) => name,
+            None => &self.lib_id,
+        }
+    }
+
+    /// Library nickname of the symbol. i.e. `Device` for `Device:R`
+    pub fn library(&self) -> Option<&str> {
+        self.lib_id.split_once(':').map(|(library, _)| library)
+    }
+
+    /// Gets a (non blank) field by name
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|f| f.name == name && !f.value.trim().is_empty())
+            .map(|f| f.value.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Kicad {
+    /// Global attributes. Taken from `NAME=VALUE` title block comments.
+    pub attributes: Vec<Attribute>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Minimal s-expression tree used by .kicad_sch files
+#[derive(Clone, Debug, PartialEq)]
+pub enum SExpr {
+    Atom(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    /// Returns the name of a list. i.e. `symbol` for `(symbol ...)`
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            SExpr::List(items) => match items.first() {
+                Some(SExpr::Atom(a)) => Some(a),
+                _ => None,
+            },
+            SExpr::Atom(_) => None,
+        }
+    }
+
+    /// Child lists that have a given name
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SExpr> + 'a {
+        let items: &[SExpr] = match self {
+            SExpr::List(items) => items,
+            SExpr::Atom(_) => &[],
+        };
+
+        items.iter().filter(move |i| i.name() == Some(name))
+    }
+
+    /// First child list with a given name
+    pub fn child(&self, name: &str) -> Option<&SExpr> {
+        match self {
+            SExpr::List(items) => items.iter().find(|i| i.name() == Some(name)),
+            SExpr::Atom(_) => None,
+        }
+    }
+
+    /// Atom at position `index` (0 being the name of the list)
+    pub fn atom(&self, index: usize) -> Option<&str> {
+        match self {
+            SExpr::List(items) => match items.get(index) {
+                Some(SExpr::Atom(a)) => Some(a),
+                _ => None,
+            },
+            SExpr::Atom(_) => None,
+        }
+    }
+}
+
+/// Parses a string into an s-expression tree
+pub fn parse_sexpr(input: &str) -> anyhow::Result<SExpr> {
+    let mut stack: Vec<Vec<SExpr>> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = match stack.pop() {
+                    Some(l) => SExpr::List(l),
+                    None => return Err(anyhow!("Unexpected ')'")),
+                };
+
+                // Either add it to the parent or we're done
+                match stack.last_mut() {
+                    Some(parent) => parent.push(list),
+                    None => return Ok(list),
+                }
+            }
+            '"' => {
+                let mut atom = String::new();
+
+                // Read until the closing quote, handling escapes
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some('n') => atom.push('\n'),
+                            Some(e) => atom.push(e),
+                            None => return Err(anyhow!("Unterminated string")),
+                        },
+                        Some('"') => break,
+                        Some(c) => atom.push(c),
+                        None => return Err(anyhow!("Unterminated string")),
+                    }
+                }
+
+                match stack.last_mut() {
+                    Some(parent) => parent.push(SExpr::Atom(atom)),
+                    None => return Err(anyhow!("Atom outside of list")),
+                }
+            }
+            c if c.is_whitespace() => continue,
+            c => {
+                let mut atom = c.to_string();
+
+                // Read until whitespace or paren
+                while let Some(&n) = chars.peek() {
+                    if n.is_whitespace() || n == '(' || n == ')' {
+                        break;
+                    }
+                    atom.push(n);
+                    chars.next();
+                }
+
+                match stack.last_mut() {
+                    Some(parent) => parent.push(SExpr::Atom(atom)),
+                    None => return Err(anyhow!("Atom outside of list")),
+                }
+            }
+        }
+    }
+
+    Err(anyhow!("Unexpected end of file"))
+}
+
+/// Parses `NAME=VALUE` title block comments into attributes
+fn attributes_from_comments<'a>(comments: impl Iterator<Item = &'a str>) -> Vec<Attribute> {
+    comments
+        .filter_map(|c| c.split_once('='))
+        .map(|(name, value)| Attribute {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Global attributes from the title block comments
+fn title_block_attributes(root: &SExpr) -> Vec<Attribute> {
+    match root.child("title_block") {
+        Some(tb) => attributes_from_comments(tb.children("comment").filter_map(|c| c.atom(2))),
+        None => Vec::new(),
+    }
+}
+
+/// Opens and reads a .kicad_sch file
+fn read_sch_file(path: &Path) -> anyhow::Result<SExpr> {
+    let file = File::open(path).map_err(|e| anyhow!("Unable to open {}: {}", path.display(), e))?;
+
+    read_sch(BufReader::new(file))
+}
+
+/// Check for a `(flag yes)` style entry
+fn is_yes(expr: &SExpr, name: &str) -> Option<bool> {
+    expr.child(name).and_then(|c| c.atom(1)).map(|v| v == "yes")
+}
+
+/// Reads the s-expression tree of a .kicad_sch file
+fn read_sch<R: BufRead>(mut reader: R) -> anyhow::Result<SExpr> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    let root = parse_sexpr(&input)?;
+
+    if root.name() != Some("kicad_sch") {
+        return Err(anyhow!("Not a KiCad schematic"));
+    }
+
+    Ok(root)
+}
+
+/// Files of the hierarchical sheets placed on a schematic. KiCad 6 calls it `Sheet file`.
+fn sheet_files(root: &SExpr) -> Vec<&str> {
+    root.children("sheet")
+        .filter_map(|sheet| {
+            sheet
+                .children("property")
+                .find(|p| matches!(p.atom(1), Some("Sheetfile") | Some("Sheet file")))
+                .and_then(|p| p.atom(2))
+        })
+        .collect()
+}
+
+/// Adds the symbols placed on a sheet. (lib_symbols has the library copies)
+fn add_symbols(root: &SExpr, symbols: &mut Vec<Symbol>) {
+    for sym in root.children("symbol") {
+        let mut symbol = Symbol {
+            lib_id: sym
+                .child("lib_id")
+                .and_then(|c| c.atom(1))
+                .unwrap_or("")
+                .to_string(),
+            in_bom: is_yes(sym, "in_bom").unwrap_or(true),
+            dnp: is_yes(sym, "dnp").unwrap_or(false),
+            ..Default::default()
+        };
+
+        for property in sym.children("property") {
+            let name = property.atom(1).unwrap_or("");
+            let value = property.atom(2).unwrap_or("");
+
+            match name {
+                "Reference" => symbol.reference = value.to_string(),
+                "Value" => symbol.value = value.to_string(),
+                _ => symbol.fields.push(Attribute {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                }),
+            }
+        }
+
+        // Multi-unit symbols (U1A, U1B) show up once per unit
+        if symbols.iter().any(|s| s.reference == symbol.reference) {
+            continue;
+        }
+
+        symbols.push(symbol);
+    }
+}
+
+impl Kicad {
+    /// Parse a KiCad 6+ .kicad_sch file. Hierarchical sheets need `from_sch_file` to be found.
+    pub fn from_sch<R: BufRead>(reader: R) -> anyhow::Result<Kicad> {
+        let root = read_sch(reader)?;
+
+        if !sheet_files(&root).is_empty() {
+            return Err(anyhow!(
+                "Schematic has hierarchical sheets. Import it from a file or use the netlist export."
+            ));
+        }
+
+        let mut kicad = Kicad {
+            attributes: title_block_attributes(&root),
+            symbols: Vec::new(),
+        };
+        add_symbols(&root, &mut kicad.symbols);
+
+        Ok(kicad)
+    }
+
+    /// Parse a KiCad 6+ .kicad_sch file along with the sheets under it
+    pub fn from_sch_file(path: &Path) -> anyhow::Result<Kicad> {
+        let root = read_sch_file(path)?;
+
+        let mut kicad = Kicad {
+            attributes: title_block_attributes(&root),
+            symbols: Vec::new(),
+        };
+
+        let mut visited = vec![path.to_path_buf()];
+        kicad.add_sheet(&root, path, &mut visited)?;
+
+        Ok(kicad)
+    }
+
+    /// Adds the symbols of a sheet and then the sheets under it
+    fn add_sheet(
+        &mut self,
+        root: &SExpr,
+        path: &Path,
+        visited: &mut Vec<PathBuf>,
+    ) -> anyhow::Result<()> {
+        add_symbols(root, &mut self.symbols);
+
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        for file in sheet_files(root) {
+            let sheet = directory.join(file);
+
+            // Each use of a sheet has its own refdes. Only the netlist has them all.
+            if visited.contains(&sheet) {
+                return Err(anyhow!(
+                    "Sheet {} is used more than once. Use the netlist export instead.",
+                    file
+                ));
+            }
+            visited.push(sheet.clone());
+
+            let child = read_sch_file(&sheet)?;
+            self.add_sheet(&child, &sheet, visited)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parse a KiCad XML netlist (or the XML BOM export)
+    pub fn from_netlist<R: BufRead>(reader: R) -> anyhow::Result<Kicad> {
+        let export: Export = quick_xml::de::from_reader(reader)?;
+
+        let attributes = match export.design.and_then(|d| d.sheet.into_iter().next()) {
+            Some(sheet) => {
+                attributes_from_comments(sheet.title_block.comment.iter().map(|c| c.value.as_str()))
+            }
+            None => Vec::new(),
+        };
+
+        let symbols = export
+            .components
+            .comp
+            .into_iter()
+            .map(|comp| {
+                let has_property = |name: &str| comp.property.iter().any(|p| p.name == name);
+
+                Symbol {
+                    lib_id: format!("{}:{}", comp.libsource.lib, comp.libsource.part),
+                    in_bom: !has_property("exclude_from_bom"),
+                    dnp: has_property("dnp"),
+                    value: comp.value.clone().unwrap_or_default(),
+                    fields: comp
+                        .fields
+                        .as_ref()
+                        .map(|f| {
+                            f.field
+                                .iter()
+                                .map(|f| Attribute {
+                                    name: f.name.clone(),
+                                    value: f.value.clone().unwrap_or_default(),
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    reference: comp.reference.clone(),
+                }
+            })
+            .collect();
+
+        Ok(Kicad {
+            attributes,
+            symbols,
+        })
+    }
+}
+
+/// KiCad has no assembly variants. DNP symbols are not populated in this one.
+const DEFAULT_VARIANT: &str = "default";
+
+impl SchematicSource for Kicad {
+    fn parts(&self) -> Vec<Part> {
+        self.symbols
+            .iter()
+            // Power flags, etc
+            .filter(|s| s.in_bom && !s.reference.starts_with('#'))
+            .map(|s| {
+                let mut variants = Vec::new();
+
+                if s.dnp {
+                    variants.push(Variant {
+                        name: DEFAULT_VARIANT.to_string(),
+                        populate: Some("no".to_string()),
+                        value: None,
+                        technology: None,
+                    });
+                }
+
+                Part {
+                    name: s.reference.clone(),
+                    deviceset: s.name().to_string(),
+                    technology: None,
+                    device: "".to_string(),
+                    value: Some(s.value.clone()),
+                    variants,
+                    attributes: s.fields.clone(),
+                    library: s.library().map(String::from),
+                }
+            })
+            .collect()
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        self.attributes.clone()
+    }
+
+    fn variants(&self) -> Vec<VariantDef> {
+        vec![VariantDef {
+            name: DEFAULT_VARIANT.to_string(),
+            current: Some("yes".to_string()),
+        }]
+    }
+
+    /// KiCad copies library fields onto every placed symbol so
+    /// the fields of the first matching symbol are used.
+    fn library_attributes(&self, library: &str, part_number: &str) -> Option<Vec<Attribute>> {
+        self.symbols
+            .iter()
+            .find(|s| s.lib_id == format!("{}:{}", library, part_number))
+            .map(|s| s.fields.clone())
+    }
+}
+
+// Structs for the XML netlist export
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Export {
+    design: Option<Design>,
+    components: Components,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Design {
+    #[serde(default)]
+    sheet: Vec<Sheet>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Sheet {
+    title_block: TitleBlock,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct TitleBlock {
+    #[serde(default)]
+    comment: Vec<Comment>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Comment {
+    value: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Components {
+    #[serde(default)]
+    comp: Vec<Comp>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Comp {
+    #[serde(rename = "ref")]
+    reference: String,
+    value: Option<String>,
+    fields: Option<Fields>,
+    libsource: LibSource,
+    #[serde(default)]
+    property: Vec<Property>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Fields {
+    #[serde(default)]
+    field: Vec<Field>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Field {
+    name: String,
+    #[serde(rename = "$value")]
+    value: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct LibSource {
+    lib: String,
+    part: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Property {
+    name: String,
+}
+
+#[test]
+fn test_parse_sexpr() {
+    let expr = parse_sexpr("(symbol (lib_id \"Device:R\") (property \"Value\" \"10k \\\"A\\\"\"))")
+        .expect("Unable to parse");
+
+    assert_eq!(Some("symbol"), expr.name());
+    assert_eq!(
+        Some("Device:R"),
+        expr.child("lib_id").and_then(|c| c.atom(1))
+    );
+    assert_eq!(
+        Some("10k \"A\""),
+        expr.child("property").and_then(|c| c.atom(2))
+    );
+}
+
+#[test]
+fn test_parse_sexpr_unbalanced() {
+    assert!(parse_sexpr("(kicad_sch (symbol)").is_err());
+}
+
+#[test]
+fn test_kicad_sch_symbols() {
+    let input = r##"(kicad_sch (version 20230121)
+  (title_block (title "Sensor board") (comment 1 "PN=SENSOR-1") (comment 2 "DESC=Sensor board"))
+  (lib_symbols (symbol "wolff-den:RES-10K-0402" (property "Reference" "R")))
+  (symbol (lib_id "wolff-den:RES-10K-0402") (unit 1) (in_bom yes) (dnp no)
+    (property "Reference" "R1") (property "Value" "10k") (property "MPN" "RC0402"))
+  (symbol (lib_id "wolff-den:MCU") (unit 1) (in_bom yes) (dnp yes)
+    (property "Reference" "U1") (property "Value" "MCU"))
+  (symbol (lib_id "wolff-den:MCU") (unit 2) (in_bom yes) (dnp yes)
+    (property "Reference" "U1") (property "Value" "MCU"))
+  (symbol (lib_id "power:GND") (unit 1) (in_bom no)
+    (property "Reference" "#PWR01") (property "Value" "GND"))
+)"##;
+
+    let kicad = Kicad::from_sch(input.as_bytes()).expect("Unable to parse");
+
+    assert_eq!(3, kicad.symbols.len());
+    assert_eq!("PN", kicad.attributes[0].name);
+    assert_eq!("SENSOR-1", kicad.attributes[0].value);
+
+    let r1 = &kicad.symbols[0];
+    assert_eq!("RES-10K-0402", r1.name());
+    assert_eq!("10k", r1.value);
+    assert_eq!(Some("RC0402"), r1.field("MPN"));
+    assert!(r1.in_bom && !r1.dnp);
+
+    assert!(kicad.symbols[1].dnp);
+    assert!(!kicad.symbols[2].in_bom);
+}
+
+#[test]
+fn test_kicad_netlist_symbols() {
+    let input = r#"<?xml version="1.0" encoding="utf-8"?>
+<export version="E">
+  <design>
+    <sheet number="1" name="/" tstamps="/">
+      <title_block>
+        <title>Sensor board</title>
+        <comment number="1" value="PN=SENSOR-1"/>
+      </title_block>
+    </sheet>
+  </design>
+  <components>
+    <comp ref="R1">
+      <value>10k</value>
+      <fields>
+        <field name="MPN">RC0402</field>
+        <field name="MQTY">2</field>
+      </fields>
+      <libsource lib="wolff-den" part="RES-10K-0402" description=""/>
+      <property name="dnp"/>
+    </comp>
+  </components>
+</export>"#;
+
+    let kicad = Kicad::from_netlist(input.as_bytes()).expect("Unable to parse");
+
+    assert_eq!("SENSOR-1", kicad.attributes[0].value);
+
+    let r1 = &kicad.symbols[0];
+    assert_eq!("R1", r1.reference);
+    assert_eq!("RES-10K-0402", r1.name());
+    assert_eq!(Some("2"), r1.field("MQTY"));
+    assert!(r1.dnp && r1.in_bom);
+}
+
+#[test]
+fn test_kicad_sch_sheets() {
+    let directory = std::env::temp_dir().join(format!("plm-kicad-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let top = r#"(kicad_sch (version 20230121)
+  (title_block (comment 1 "PN=SENSOR-1"))
+  (symbol (lib_id "Device:R") (property "Reference" "R1") (property "Value" "10k"))
+  (sheet (property "Sheetname" "Power") (property "Sheetfile" "power.kicad_sch"))
+)"#;
+    let power = r#"(kicad_sch (version 20230121)
+  (symbol (lib_id "Device:C") (property "Reference" "C1") (property "Value" "1u"))
+  (sheet (property "Sheet name" "LDO") (property "Sheet file" "ldo.kicad_sch"))
+)"#;
+    let ldo = r#"(kicad_sch (version 20230121)
+  (symbol (lib_id "Regulator:LDO") (property "Reference" "U1") (property "Value" "LDO"))
+)"#;
+
+    std::fs::write(directory.join("top.kicad_sch"), top).unwrap();
+    std::fs::write(directory.join("power.kicad_sch"), power).unwrap();
+    std::fs::write(directory.join("ldo.kicad_sch"), ldo).unwrap();
+
+    let kicad = Kicad::from_sch_file(&directory.join("top.kicad_sch")).expect("Unable to parse");
+    let references: Vec<&str> = kicad.symbols.iter().map(|s| s.reference.as_str()).collect();
+    assert_eq!(vec!["R1", "C1", "U1"], references);
+    assert_eq!("SENSOR-1", kicad.attributes[0].value);
+
+    // Without the path the sheets can't be found
+    assert!(Kicad::from_sch(top.as_bytes()).is_err());
+
+    // Reused sheets only have all their refdes in the netlist
+    let reused = top.replace(
+        "(sheet",
+        "(sheet (property \"Sheetfile\" \"ldo.kicad_sch\")) (sheet",
+    );
+    let reused = reused.replace("power.kicad_sch", "ldo.kicad_sch");
+    std::fs::write(directory.join("reused.kicad_sch"), reused).unwrap();
+    assert!(Kicad::from_sch_file(&directory.join("reused.kicad_sch")).is_err());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
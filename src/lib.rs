@@ -7,10 +7,13 @@ extern crate diesel_migrations;
 extern crate prettytable;
 
 pub mod config;
+pub mod csv_bom;
+pub mod kicad;
 pub mod models;
 pub mod prompt;
 pub mod schema;
 pub mod schematic;
+pub mod search;
 pub mod tables;
 
 use diesel::prelude::*;
@@ -36,6 +39,9 @@ pub struct Application<'a> {
 // Migrate
 embed_migrations!();
 
+// Id of the last row inserted on a connection
+no_arg_sql_function!(last_insert_rowid, diesel::sql_types::Integer);
+
 pub fn establish_connection(db_name: &str) -> SqliteConnection {
     // Get text version of configpath
     let mut database_url =
@@ -99,6 +105,32 @@ pub fn delete_part(
     diesel::delete(parts::dsl::parts.filter(parts::dsl::id.eq(id))).execute(conn)
 }
 
+/// Sets the lifecycle and last time buy date of a part. No date clears it.
+pub fn set_part_lifecycle(
+    conn: &SqliteConnection,
+    id: &i32,
+    lifecycle: &UpdatePartLifecycle,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::parts;
+
+    diesel::update(parts::dsl::parts.filter(parts::dsl::id.eq(id)))
+        .set(lifecycle)
+        .execute(conn)
+}
+
+/// Archived parts are kept for history but hidden from listings
+pub fn set_part_archived(
+    conn: &SqliteConnection,
+    id: &i32,
+    is_archived: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::parts;
+
+    diesel::update(parts::dsl::parts.filter(parts::dsl::id.eq(id)))
+        .set(parts::dsl::archived.eq(is_archived))
+        .execute(conn)
+}
+
 pub fn find_part_by_pn(
     conn: &SqliteConnection,
     pn: &str,
@@ -154,6 +186,160 @@ pub fn create_bom_line_item(
         .execute(conn)
 }
 
+/// Creates a BOM line with a row for each reference designator (and value). Returns the id of the line.
+pub fn create_bom_line_with_refdes(
+    conn: &SqliteConnection,
+    part: &NewPartsParts,
+    designators: &[(String, Option<String>)],
+) -> std::result::Result<i32, diesel::result::Error> {
+    create_bom_line_item(conn, part)?;
+
+    let line_id = diesel::select(last_insert_rowid).get_result::<i32>(conn)?;
+
+    set_bom_line_refdes(conn, &line_id, designators)?;
+
+    Ok(line_id)
+}
+
+/// Replaces the reference designators of a BOM line. The line's refdes is set to the names joined up.
+pub fn set_bom_line_refdes(
+    conn: &SqliteConnection,
+    id: &i32,
+    designators: &[(String, Option<String>)],
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::{bom_refdes, parts_parts};
+
+    diesel::delete(bom_refdes::dsl::bom_refdes.filter(bom_refdes::dsl::line_id.eq(id)))
+        .execute(conn)?;
+
+    let mut names = Vec::new();
+
+    for (name, val) in designators.iter().filter(|(name, _)| !name.is_empty()) {
+        let refdes = NewBomRefdes {
+            refdes: name,
+            val: val.as_deref(),
+            line_id: id,
+        };
+
+        diesel::insert_into(bom_refdes::table)
+            .values(&refdes)
+            .execute(conn)?;
+
+        names.push(name.as_str());
+    }
+
+    diesel::update(parts_parts::dsl::parts_parts.filter(parts_parts::dsl::id.eq(id)))
+        .set(parts_parts::dsl::refdes.eq(names.join(" ")))
+        .execute(conn)
+}
+
+pub fn find_refdes_by_line_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<BomRefdes>, diesel::result::Error> {
+    use schema::bom_refdes;
+
+    bom_refdes::dsl::bom_refdes
+        .filter(bom_refdes::dsl::line_id.eq(id))
+        .order(bom_refdes::dsl::id)
+        .load::<BomRefdes>(conn)
+}
+
+/// Finds a reference designator in a BOM version along with its line
+pub fn find_bom_refdes(
+    conn: &SqliteConnection,
+    bom_id: &i32,
+    ver: &i32,
+    name: &str,
+) -> std::result::Result<(PartsPart, BomRefdes), diesel::result::Error> {
+    use schema::{bom_refdes, parts_parts};
+
+    parts_parts::table
+        .inner_join(bom_refdes::table)
+        .filter(parts_parts::dsl::bom_part_id.eq(bom_id))
+        .filter(parts_parts::dsl::bom_ver.eq(ver))
+        .filter(bom_refdes::dsl::refdes.eq(name))
+        .first::<(PartsPart, BomRefdes)>(conn)
+}
+
+pub fn update_refdes_val(
+    conn: &SqliteConnection,
+    id: &i32,
+    value: Option<&str>,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::bom_refdes;
+
+    diesel::update(bom_refdes::dsl::bom_refdes.filter(bom_refdes::dsl::id.eq(id)))
+        .set(bom_refdes::dsl::val.eq(value))
+        .execute(conn)
+}
+
+pub fn create_bom_substitution(
+    conn: &SqliteConnection,
+    substitution: &NewBomSubstitution,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::bom_substitutions;
+
+    diesel::insert_into(bom_substitutions::table)
+        .values(substitution)
+        .execute(conn)
+}
+
+pub fn find_bom_substitutions_by_line_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<BomSubstitution>, diesel::result::Error> {
+    use schema::bom_substitutions;
+
+    bom_substitutions::dsl::bom_substitutions
+        .filter(bom_substitutions::dsl::line_id.eq(id))
+        .order(bom_substitutions::dsl::id)
+        .load::<BomSubstitution>(conn)
+}
+
+pub fn delete_bom_substitution(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::bom_substitutions;
+
+    diesel::delete(
+        bom_substitutions::dsl::bom_substitutions.filter(bom_substitutions::dsl::id.eq(id)),
+    )
+    .execute(conn)
+}
+
+pub fn update_bom_line_item(
+    conn: &SqliteConnection,
+    id: &i32,
+    part: &NewPartsParts,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::parts_parts;
+
+    diesel::update(parts_parts::dsl::parts_parts.filter(parts_parts::dsl::id.eq(id)))
+        .set(part)
+        .execute(conn)
+}
+
+pub fn delete_bom_line_item(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::parts_parts;
+
+    use schema::{bom_refdes, bom_substitutions};
+
+    diesel::delete(bom_refdes::dsl::bom_refdes.filter(bom_refdes::dsl::line_id.eq(id)))
+        .execute(conn)?;
+
+    diesel::delete(
+        bom_substitutions::dsl::bom_substitutions.filter(bom_substitutions::dsl::line_id.eq(id)),
+    )
+    .execute(conn)?;
+
+    diesel::delete(parts_parts::dsl::parts_parts.filter(parts_parts::dsl::id.eq(id))).execute(conn)
+}
+
 pub fn delete_bom_list_by_id_and_ver(
     conn: &SqliteConnection,
     bom_id: &i32,
@@ -170,10 +356,468 @@ pub fn delete_bom_list_by_id_and_ver(
     // Then make sure that the bom ver is equal. Match against the ids found in the first step
     let target = parts_parts.filter(bom_ver.eq(ver)).filter(id.eq_any(query));
 
+    // The reference designators and substitutions go too
+    let lines = target.clone().select(id).load::<i32>(conn)?;
+    diesel::delete(
+        schema::bom_refdes::table.filter(schema::bom_refdes::dsl::line_id.eq_any(&lines)),
+    )
+    .execute(conn)?;
+    diesel::delete(
+        schema::bom_substitutions::table
+            .filter(schema::bom_substitutions::dsl::line_id.eq_any(&lines)),
+    )
+    .execute(conn)?;
+
     // Delete appropriately
     diesel::delete(target).execute(conn)
 }
 
+pub fn find_bom_list_by_id_and_ver(
+    conn: &SqliteConnection,
+    bom_id: &i32,
+    ver: &i32,
+) -> std::result::Result<Vec<PartsPart>, diesel::result::Error> {
+    use schema::parts_parts::dsl::*;
+
+    parts_parts
+        .filter(bom_part_id.eq(bom_id))
+        .filter(bom_ver.eq(ver))
+        .load::<PartsPart>(conn)
+}
+
+pub fn find_bom_lines_by_part_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<PartsPart>, diesel::result::Error> {
+    use schema::parts_parts;
+
+    parts_parts::dsl::parts_parts
+        .filter(parts_parts::dsl::part_id.eq(id))
+        .order((parts_parts::dsl::bom_part_id, parts_parts::dsl::bom_ver))
+        .load::<PartsPart>(conn)
+}
+
+pub fn set_bom_archived_by_id_and_ver(
+    conn: &SqliteConnection,
+    bom_id: &i32,
+    ver: &i32,
+    is_archived: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::parts_parts::dsl::*;
+
+    let target = parts_parts
+        .filter(bom_part_id.eq(bom_id))
+        .filter(bom_ver.eq(ver));
+
+    diesel::update(target)
+        .set(archived.eq(is_archived))
+        .execute(conn)
+}
+
+// Alias related
+
+pub fn create_alias(
+    conn: &SqliteConnection,
+    alias: &NewUpdateAlias,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::aliases;
+
+    diesel::insert_into(aliases::table)
+        .values(alias)
+        .execute(conn)
+}
+
+pub fn update_alias(
+    conn: &SqliteConnection,
+    id: &i32,
+    alias: &NewUpdateAlias,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::aliases;
+
+    diesel::update(aliases::dsl::aliases.filter(aliases::dsl::id.eq(id)))
+        .set(alias)
+        .execute(conn)
+}
+
+pub fn find_alias_by_pn(
+    conn: &SqliteConnection,
+    pn: &str,
+) -> std::result::Result<Alias, diesel::result::Error> {
+    use schema::aliases;
+
+    aliases::dsl::aliases
+        .filter(aliases::dsl::pn.eq(pn))
+        .first(conn)
+}
+
+pub fn find_alias_by_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Alias, diesel::result::Error> {
+    use schema::aliases;
+
+    aliases::dsl::aliases
+        .filter(aliases::dsl::id.eq(id))
+        .first(conn)
+}
+
+// Supplier related
+
+pub fn create_supplier(
+    conn: &SqliteConnection,
+    supplier: &NewUpdateSupplier,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::suppliers;
+
+    diesel::insert_into(suppliers::table)
+        .values(supplier)
+        .execute(conn)
+}
+
+pub fn find_supplier_by_name(
+    conn: &SqliteConnection,
+    name: &str,
+) -> std::result::Result<Supplier, diesel::result::Error> {
+    use schema::suppliers;
+
+    suppliers::dsl::suppliers
+        .filter(suppliers::dsl::name.eq(name))
+        .first(conn)
+}
+
+pub fn find_supplier_by_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Supplier, diesel::result::Error> {
+    use schema::suppliers;
+
+    suppliers::dsl::suppliers
+        .filter(suppliers::dsl::id.eq(id))
+        .first(conn)
+}
+
+pub fn find_suppliers(
+    conn: &SqliteConnection,
+) -> std::result::Result<Vec<Supplier>, diesel::result::Error> {
+    use schema::suppliers;
+
+    suppliers::dsl::suppliers
+        .order(suppliers::dsl::name)
+        .load::<Supplier>(conn)
+}
+
+pub fn create_part_supplier(
+    conn: &SqliteConnection,
+    entry: &NewUpdatePartSupplier,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_suppliers;
+
+    diesel::insert_into(part_suppliers::table)
+        .values(entry)
+        .execute(conn)
+}
+
+pub fn update_part_supplier_by_id(
+    conn: &SqliteConnection,
+    id: &i32,
+    entry: &NewUpdatePartSupplier,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_suppliers;
+
+    diesel::update(part_suppliers::dsl::part_suppliers.filter(part_suppliers::dsl::id.eq(id)))
+        .set(entry)
+        .execute(conn)
+}
+
+pub fn find_part_suppliers_by_part_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<PartSupplier>, diesel::result::Error> {
+    use schema::part_suppliers;
+
+    part_suppliers::dsl::part_suppliers
+        .filter(part_suppliers::dsl::part_id.eq(id))
+        .load::<PartSupplier>(conn)
+}
+
+pub fn delete_part_supplier(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_suppliers;
+
+    diesel::delete(part_suppliers::dsl::part_suppliers.filter(part_suppliers::dsl::id.eq(id)))
+        .execute(conn)
+}
+
+/// Sets a part's PN for a supplier, creating the supplier if it's new
+pub fn set_part_supplier_pn(
+    conn: &SqliteConnection,
+    part_id: &i32,
+    supplier: &str,
+    supplier_pn: &str,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_suppliers;
+
+    // Get or create the supplier
+    let supplier = match find_supplier_by_name(conn, supplier) {
+        Ok(x) => x,
+        Err(_) => {
+            create_supplier(conn, &NewUpdateSupplier { name: supplier })?;
+            find_supplier_by_name(conn, supplier)?
+        }
+    };
+
+    let entry = NewUpdatePartSupplier {
+        supplier_pn,
+        part_id,
+        supplier_id: &supplier.id,
+    };
+
+    // Update the existing PN if there is one
+    let existing = part_suppliers::dsl::part_suppliers
+        .filter(part_suppliers::dsl::part_id.eq(part_id))
+        .filter(part_suppliers::dsl::supplier_id.eq(supplier.id))
+        .first::<PartSupplier>(conn);
+
+    match existing {
+        Ok(e) => update_part_supplier_by_id(conn, &e.id, &entry),
+        Err(_) => create_part_supplier(conn, &entry),
+    }
+}
+
+pub fn find_parts_by_mpn(
+    conn: &SqliteConnection,
+    mpn: &str,
+) -> std::result::Result<Vec<Part>, diesel::result::Error> {
+    use schema::parts;
+
+    parts::dsl::parts
+        .filter(parts::dsl::mpn.eq(mpn))
+        .order(parts::dsl::pn)
+        .load::<Part>(conn)
+}
+
+/// Finds the parts with an MPN. Only the ones from the manufacturer if there is one.
+pub fn find_parts_by_mpn_and_manufacturer(
+    conn: &SqliteConnection,
+    mpn: &str,
+    manufacturer: Option<&str>,
+) -> std::result::Result<Vec<Part>, diesel::result::Error> {
+    let parts = find_parts_by_mpn(conn, mpn)?;
+
+    let manufacturer = match manufacturer {
+        Some(name) => match find_manufacturer_by_name(conn, name) {
+            Ok(x) => x,
+            Err(diesel::result::Error::NotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        },
+        None => return Ok(parts),
+    };
+
+    Ok(parts
+        .into_iter()
+        .filter(|p| p.manufacturer_id == Some(manufacturer.id))
+        .collect())
+}
+
+pub fn create_manufacturer(
+    conn: &SqliteConnection,
+    manufacturer: &NewUpdateManufacturer,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::manufacturers;
+
+    diesel::insert_into(manufacturers::table)
+        .values(manufacturer)
+        .execute(conn)
+}
+
+/// Finds a manufacturer without case. i.e. Murata and MURATA are the same.
+pub fn find_manufacturer_by_name(
+    conn: &SqliteConnection,
+    name: &str,
+) -> std::result::Result<Manufacturer, diesel::result::Error> {
+    use schema::manufacturers;
+
+    let name = name.trim().to_lowercase();
+
+    manufacturers::dsl::manufacturers
+        .load::<Manufacturer>(conn)?
+        .into_iter()
+        .find(|m| m.name.to_lowercase() == name)
+        .ok_or(diesel::result::Error::NotFound)
+}
+
+pub fn find_manufacturer_by_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Manufacturer, diesel::result::Error> {
+    use schema::manufacturers;
+
+    manufacturers::dsl::manufacturers
+        .filter(manufacturers::dsl::id.eq(id))
+        .first(conn)
+}
+
+/// Gets a manufacturer, creating it if it's new
+pub fn find_or_create_manufacturer(
+    conn: &SqliteConnection,
+    name: &str,
+) -> std::result::Result<Manufacturer, diesel::result::Error> {
+    match find_manufacturer_by_name(conn, name) {
+        Ok(x) => Ok(x),
+        Err(_) => {
+            create_manufacturer(conn, &NewUpdateManufacturer { name: name.trim() })?;
+            find_manufacturer_by_name(conn, name)
+        }
+    }
+}
+
+/// Name of a part's manufacturer. Blank if it isn't known.
+pub fn find_manufacturer_name(
+    conn: &SqliteConnection,
+    manufacturer_id: &Option<i32>,
+) -> std::result::Result<String, diesel::result::Error> {
+    match manufacturer_id {
+        Some(id) => Ok(find_manufacturer_by_id(conn, id)?.name),
+        None => Ok(String::new()),
+    }
+}
+
+/// Sets a parametric attribute of a part, replacing any existing value
+pub fn set_part_attribute(
+    conn: &SqliteConnection,
+    part_id: &i32,
+    name: &str,
+    value: &str,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_attributes;
+
+    let entry = NewUpdatePartAttribute {
+        name,
+        value,
+        part_id,
+    };
+
+    // Update the existing value if there is one
+    let existing = part_attributes::dsl::part_attributes
+        .filter(part_attributes::dsl::part_id.eq(part_id))
+        .filter(part_attributes::dsl::name.eq(name))
+        .first::<PartAttribute>(conn);
+
+    match existing {
+        Ok(e) => diesel::update(
+            part_attributes::dsl::part_attributes.filter(part_attributes::dsl::id.eq(e.id)),
+        )
+        .set(&entry)
+        .execute(conn),
+        Err(_) => diesel::insert_into(part_attributes::table)
+            .values(&entry)
+            .execute(conn),
+    }
+}
+
+pub fn find_part_attributes_by_part_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<PartAttribute>, diesel::result::Error> {
+    use schema::part_attributes;
+
+    part_attributes::dsl::part_attributes
+        .filter(part_attributes::dsl::part_id.eq(id))
+        .order(part_attributes::dsl::name)
+        .load::<PartAttribute>(conn)
+}
+
+pub fn delete_part_attribute(
+    conn: &SqliteConnection,
+    part_id: &i32,
+    name: &str,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_attributes;
+
+    diesel::delete(
+        part_attributes::dsl::part_attributes
+            .filter(part_attributes::dsl::part_id.eq(part_id))
+            .filter(part_attributes::dsl::name.eq(name)),
+    )
+    .execute(conn)
+}
+
+// Approved manufacturer list related
+
+pub fn create_part_alternate(
+    conn: &SqliteConnection,
+    alternate: &NewUpdatePartAlternate,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_alternates;
+
+    diesel::insert_into(part_alternates::table)
+        .values(alternate)
+        .execute(conn)
+}
+
+/// Alternates of a part in the order they're used
+pub fn find_part_alternates_by_part_id(
+    conn: &SqliteConnection,
+    part_id: &i32,
+) -> std::result::Result<Vec<PartAlternate>, diesel::result::Error> {
+    use schema::part_alternates;
+
+    part_alternates::dsl::part_alternates
+        .filter(part_alternates::dsl::part_id.eq(part_id))
+        .order((part_alternates::dsl::priority, part_alternates::dsl::id))
+        .load::<PartAlternate>(conn)
+}
+
+pub fn find_part_alternate_by_id(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<PartAlternate, diesel::result::Error> {
+    use schema::part_alternates;
+
+    part_alternates::dsl::part_alternates
+        .filter(part_alternates::dsl::id.eq(id))
+        .first(conn)
+}
+
+/// Finds the alternates with an MPN. Only the ones from the manufacturer if there is one.
+pub fn find_part_alternates_by_mpn_and_manufacturer(
+    conn: &SqliteConnection,
+    mpn: &str,
+    manufacturer: Option<&str>,
+) -> std::result::Result<Vec<PartAlternate>, diesel::result::Error> {
+    use schema::part_alternates;
+
+    let alternates = part_alternates::dsl::part_alternates
+        .filter(part_alternates::dsl::mpn.eq(mpn))
+        .load::<PartAlternate>(conn)?;
+
+    let manufacturer = match manufacturer {
+        Some(name) => match find_manufacturer_by_name(conn, name) {
+            Ok(x) => x,
+            Err(diesel::result::Error::NotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        },
+        None => return Ok(alternates),
+    };
+
+    Ok(alternates
+        .into_iter()
+        .filter(|a| a.manufacturer_id == Some(manufacturer.id))
+        .collect())
+}
+
+pub fn delete_part_alternate(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::part_alternates;
+
+    diesel::delete(part_alternates::dsl::part_alternates.filter(part_alternates::dsl::id.eq(id)))
+        .execute(conn)
+}
+
 // Build related
 
 pub fn create_build(
@@ -212,6 +856,20 @@ pub fn find_builds_by_pn(
         .load::<Build>(conn)
 }
 
+pub fn find_open_builds_by_id_and_ver(
+    conn: &SqliteConnection,
+    id: &i32,
+    ver: &i32,
+) -> std::result::Result<Vec<Build>, diesel::result::Error> {
+    use schema::builds;
+
+    builds::dsl::builds
+        .filter(builds::dsl::part_id.eq(id))
+        .filter(builds::dsl::part_ver.eq(ver))
+        .filter(builds::dsl::complete.eq(0))
+        .load::<Build>(conn)
+}
+
 pub fn find_build_by_id(
     conn: &SqliteConnection,
     id: &i32,
@@ -268,6 +926,42 @@ pub fn find_inventories_by_part_id(
         .load::<Inventory>(conn)
 }
 
+/// Inventory of a part in the order it's used. The part's own MPN first, then approved
+/// alternates by priority. Oldest first within each.
+pub fn find_inventories_in_use_order(
+    conn: &SqliteConnection,
+    id: &i32,
+) -> std::result::Result<Vec<Inventory>, diesel::result::Error> {
+    let alternates = find_part_alternates_by_part_id(conn, id)?;
+    let mut entries = find_inventories_by_part_id(conn, id)?;
+
+    entries.sort_by_key(|e| {
+        let priority = e
+            .alternate_id
+            .and_then(|a| alternates.iter().find(|x| x.id == a))
+            .map(|a| a.priority)
+            .unwrap_or(0);
+
+        (priority, e.id)
+    });
+
+    Ok(entries)
+}
+
+/// Inventory of an alternate that's no longer approved counts as the part's own MPN
+pub fn clear_inventory_alternate(
+    conn: &SqliteConnection,
+    alternate_id: &i32,
+) -> std::result::Result<usize, diesel::result::Error> {
+    use schema::inventories;
+
+    diesel::update(
+        inventories::dsl::inventories.filter(inventories::dsl::alternate_id.eq(alternate_id)),
+    )
+    .set(inventories::dsl::alternate_id.eq(None::<i32>))
+    .execute(conn)
+}
+
 pub fn test_connection() -> SqliteConnection {
     // Start a connection from memory
     let conn = SqliteConnection::establish(":memory:").expect("Unable to establish db in memory!");
@@ -296,7 +990,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Create the part
@@ -326,7 +1022,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Create the part
@@ -338,7 +1036,9 @@ mod part_tests {
             mpn: "ABCD-ND",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Do it again
@@ -359,7 +1059,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Create the part
@@ -371,7 +1073,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Do it again
@@ -392,7 +1096,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1U 10V 0402",
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Create the part
@@ -407,7 +1113,9 @@ mod part_tests {
             mpn: "ABCD",
             descr: "CAP 0.1 10V 0402 GOOD", // Only changing this guy
             ver: &1,
+            val: None,
             mqty: &1,
+            manufacturer_id: None,
         };
 
         // Update the part
@@ -419,10 +1127,139 @@ mod part_tests {
         // Make sure these guys are equal
         assert_eq!(part.descr, found.descr);
     }
+
+    #[test]
+    fn set_part_supplier_pn_creates_and_updates() {
+        use super::*;
+
+        let conn = test_connection();
+
+        // Create NewUpdatePart instance
+        let part = NewUpdatePart {
+            pn: "CAP-0.1U-10V-0402",
+            mpn: "ABCD",
+            descr: "CAP 0.1U 10V 0402",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+
+        // Create the part
+        create_part(&conn, &part).expect("Error creating part!");
+        let found = find_part_by_pn(&conn, &part.pn).expect("Error getting part back.");
+
+        // Two suppliers, then change one of them
+        set_part_supplier_pn(&conn, &found.id, "DIGIKEY", "1-ABCD-ND").unwrap();
+        set_part_supplier_pn(&conn, &found.id, "MOUSER", "123-ABCD").unwrap();
+        set_part_supplier_pn(&conn, &found.id, "DIGIKEY", "2-ABCD-ND").unwrap();
+
+        // Only one entry per supplier
+        let entries = find_part_suppliers_by_part_id(&conn, &found.id).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(find_suppliers(&conn).unwrap().len(), 2);
+
+        let digikey = find_supplier_by_name(&conn, "DIGIKEY").unwrap();
+        let entry = entries
+            .iter()
+            .find(|e| e.supplier_id == digikey.id)
+            .unwrap();
+        assert_eq!(entry.supplier_pn, "2-ABCD-ND");
+    }
 }
 
 /* START: Inventory Related Tests */
-mod inventory_tests {}
+mod inventory_tests {
+
+    #[test]
+    fn alternates_are_used_in_order() {
+        use super::*;
+
+        let conn = test_connection();
+
+        let part = NewUpdatePart {
+            pn: "CAP-0.1U-10V-0402",
+            mpn: "ABCD",
+            descr: "CAP 0.1U 10V 0402",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+        create_part(&conn, &part).expect("Error creating part!");
+        let found = find_part_by_pn(&conn, &part.pn).expect("Error getting part back.");
+
+        let murata = find_or_create_manufacturer(&conn, "Murata").unwrap();
+
+        // Added out of order
+        for (mpn, priority, manufacturer_id) in [("EFGH", 2, None), ("IJKL", 1, Some(&murata.id))] {
+            let alternate = NewUpdatePartAlternate {
+                mpn,
+                priority: &priority,
+                part_id: &found.id,
+                manufacturer_id,
+            };
+            create_part_alternate(&conn, &alternate).expect("Error creating alternate!");
+        }
+
+        let alternates = find_part_alternates_by_part_id(&conn, &found.id).unwrap();
+        let mpns: Vec<&str> = alternates.iter().map(|a| a.mpn.as_str()).collect();
+        assert_eq!(vec!["IJKL", "EFGH"], mpns);
+
+        // Only from the manufacturer if there is one
+        assert_eq!(
+            1,
+            find_part_alternates_by_mpn_and_manufacturer(&conn, "IJKL", Some("MURATA"))
+                .unwrap()
+                .len()
+        );
+        assert!(
+            find_part_alternates_by_mpn_and_manufacturer(&conn, "IJKL", Some("Yageo"))
+                .unwrap()
+                .is_empty()
+        );
+
+        // Stock of each, received in the wrong order
+        for (quantity, alternate_id) in [
+            (5, Some(&alternates[1].id)),
+            (3, None),
+            (4, Some(&alternates[0].id)),
+            (2, None),
+        ] {
+            let entry = NewUpdateInventoryEntry {
+                quantity: &quantity,
+                consumed: &0,
+                unit_price: None,
+                notes: None,
+                part_ver: &1,
+                part_id: &found.id,
+                alternate_id,
+            };
+            create_inventory(&conn, &entry).expect("Error creating inventory!");
+        }
+
+        // The part's own MPN first (oldest first), then by priority
+        let quantities: Vec<i32> = find_inventories_in_use_order(&conn, &found.id)
+            .unwrap()
+            .iter()
+            .map(|i| i.quantity)
+            .collect();
+        assert_eq!(vec![3, 2, 4, 5], quantities);
+
+        // No longer approved. What's left counts as the part's own MPN.
+        clear_inventory_alternate(&conn, &alternates[1].id).unwrap();
+        delete_part_alternate(&conn, &alternates[1].id).unwrap();
+
+        let entries = find_inventories_by_part_id(&conn, &found.id).unwrap();
+        assert!(entries[0].alternate_id.is_none());
+        assert_eq!(
+            1,
+            find_part_alternates_by_part_id(&conn, &found.id)
+                .unwrap()
+                .len()
+        );
+    }
+}
 
 /* START: Build Related Tests */
 mod build_tests {}
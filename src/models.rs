@@ -1,8 +1,8 @@
 use crate::schema::*;
 
-use chrono::NaiveDateTime;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime};
 
-// TODO: Add manufacturer information
 #[derive(Identifiable, Queryable)]
 pub struct Part {
     pub id: i32,
@@ -15,6 +15,72 @@ pub struct Part {
     pub ver: i32,
     pub val: Option<String>,
     pub mqty: i32,
+    pub lifecycle: String,
+    pub last_time_buy: Option<NaiveDate>,
+    pub manufacturer_id: Option<i32>,
+    pub archived: i32,
+}
+
+impl Part {
+    /// Unknown states are treated as active
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.lifecycle.parse().unwrap_or_default()
+    }
+}
+
+/// Where a part is in its life
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lifecycle {
+    #[default]
+    Active,
+    /// Not recommended for new designs
+    Nrnd,
+    /// Last time buy
+    Ltb,
+    Obsolete,
+}
+
+impl Lifecycle {
+    /// How it's stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lifecycle::Active => "active",
+            Lifecycle::Nrnd => "nrnd",
+            Lifecycle::Ltb => "ltb",
+            Lifecycle::Obsolete => "obsolete",
+        }
+    }
+}
+
+impl std::fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Lifecycle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "active" => Ok(Lifecycle::Active),
+            "nrnd" => Ok(Lifecycle::Nrnd),
+            "ltb" | "last-time-buy" => Ok(Lifecycle::Ltb),
+            "obsolete" => Ok(Lifecycle::Obsolete),
+            _ => Err(anyhow!(
+                "Unknown lifecycle {}. Use active, nrnd, ltb or obsolete",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, AsChangeset)]
+#[table_name = "parts"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UpdatePartLifecycle<'a> {
+    pub lifecycle: &'a str,
+    pub last_time_buy: Option<NaiveDate>,
 }
 
 #[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
@@ -24,10 +90,12 @@ pub struct NewUpdatePart<'a> {
     pub mpn: &'a str,
     pub descr: &'a str,
     pub ver: &'a i32,
+    pub val: Option<&'a str>,
     pub mqty: &'a i32,
+    pub manufacturer_id: Option<&'a i32>,
 }
 
-#[derive(Identifiable, Queryable, Debug)]
+#[derive(Identifiable, Queryable, Debug, Clone)]
 pub struct PartsPart {
     pub id: i32,
     pub created_at: NaiveDateTime,
@@ -38,6 +106,9 @@ pub struct PartsPart {
     pub nostuff: i32,
     pub bom_part_id: i32,
     pub part_id: i32,
+    pub alias_id: Option<i32>,
+    pub archived: i32,
+    pub manual: i32,
 }
 
 #[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
@@ -49,6 +120,165 @@ pub struct NewPartsParts<'a> {
     pub nostuff: &'a i32,
     pub bom_part_id: &'a i32,
     pub part_id: &'a i32,
+    pub alias_id: Option<&'a i32>,
+    pub manual: &'a i32,
+}
+
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[table_name = "bom_refdes"]
+pub struct BomRefdes {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub refdes: String,
+    pub val: Option<String>,
+    pub line_id: i32,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable)]
+#[table_name = "bom_refdes"]
+pub struct NewBomRefdes<'a> {
+    pub refdes: &'a str,
+    pub val: Option<&'a str>,
+    pub line_id: &'a i32,
+}
+
+/// A part that can be used instead of the one on a BOM line
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "bom_substitutions"]
+pub struct BomSubstitution {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub expires: Option<NaiveDate>,
+    pub line_id: i32,
+    pub part_id: i32,
+    pub build_id: Option<i32>,
+}
+
+impl BomSubstitution {
+    /// Whether it can be used on a day. Ones for a build can't be used for anything else.
+    pub fn applies(&self, build_id: Option<&i32>, today: NaiveDate) -> bool {
+        self.expires.is_none_or(|d| today <= d)
+            && self.build_id.is_none_or(|b| Some(&b) == build_id)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable)]
+#[table_name = "bom_substitutions"]
+pub struct NewBomSubstitution<'a> {
+    pub expires: Option<&'a NaiveDate>,
+    pub line_id: &'a i32,
+    pub part_id: &'a i32,
+    pub build_id: Option<&'a i32>,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "aliases"]
+pub struct Alias {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub pn: String,
+    pub quantity: i32,
+    pub part_id: i32,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "aliases"]
+pub struct NewUpdateAlias<'a> {
+    pub pn: &'a str,
+    pub quantity: &'a i32,
+    pub part_id: &'a i32,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "suppliers"]
+pub struct Supplier {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub name: String,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "suppliers"]
+pub struct NewUpdateSupplier<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "manufacturers"]
+pub struct Manufacturer {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub name: String,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "manufacturers"]
+pub struct NewUpdateManufacturer<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "part_alternates"]
+pub struct PartAlternate {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub mpn: String,
+    pub priority: i32,
+    pub part_id: i32,
+    pub manufacturer_id: Option<i32>,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "part_alternates"]
+pub struct NewUpdatePartAlternate<'a> {
+    pub mpn: &'a str,
+    pub priority: &'a i32,
+    pub part_id: &'a i32,
+    pub manufacturer_id: Option<&'a i32>,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "part_suppliers"]
+pub struct PartSupplier {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub supplier_pn: String,
+    pub part_id: i32,
+    pub supplier_id: i32,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "part_suppliers"]
+pub struct NewUpdatePartSupplier<'a> {
+    pub supplier_pn: &'a str,
+    pub part_id: &'a i32,
+    pub supplier_id: &'a i32,
+}
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "part_attributes"]
+pub struct PartAttribute {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub name: String,
+    pub value: String,
+    pub part_id: i32,
+}
+
+#[derive(Eq, PartialEq, Debug, Insertable, AsChangeset)]
+#[table_name = "part_attributes"]
+pub struct NewUpdatePartAttribute<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub part_id: &'a i32,
 }
 
 // TODO: use as unit
@@ -64,6 +294,7 @@ pub struct Inventory {
     pub notes: Option<String>,
     pub part_ver: i32,
     pub part_id: i32,
+    pub alternate_id: Option<i32>,
 }
 
 #[derive(Debug, Insertable, AsChangeset)]
@@ -75,6 +306,8 @@ pub struct NewUpdateInventoryEntry<'a> {
     pub notes: Option<&'a str>,
     pub part_ver: &'a i32,
     pub part_id: &'a i32,
+    /// The approved alternate that was received (if not the part's own MPN)
+    pub alternate_id: Option<&'a i32>,
 }
 
 #[derive(Identifiable, Queryable)]
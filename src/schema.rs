@@ -1,5 +1,39 @@
 use diesel::table;
 
+table! {
+    aliases (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        pn -> Text,
+        quantity -> Integer,
+        part_id -> Integer,
+    }
+}
+
+table! {
+    bom_refdes (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        refdes -> Text,
+        val -> Nullable<Text>,
+        line_id -> Integer,
+    }
+}
+
+table! {
+    bom_substitutions (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        expires -> Nullable<Date>,
+        line_id -> Integer,
+        part_id -> Integer,
+        build_id -> Nullable<Integer>,
+    }
+}
+
 table! {
     builds (id) {
         id -> Integer,
@@ -26,6 +60,7 @@ table! {
         notes -> Nullable<Text>,
         part_ver -> Integer,
         part_id -> Integer,
+        alternate_id -> Nullable<Integer>,
     }
 }
 
@@ -41,6 +76,53 @@ table! {
         ver -> Integer,
         val -> Nullable<Text>,
         mqty -> Integer,
+        lifecycle -> Text,
+        last_time_buy -> Nullable<Date>,
+        manufacturer_id -> Nullable<Integer>,
+        archived -> Integer,
+    }
+}
+
+table! {
+    manufacturers (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        name -> Text,
+    }
+}
+
+table! {
+    part_alternates (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        mpn -> Text,
+        priority -> Integer,
+        part_id -> Integer,
+        manufacturer_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    part_attributes (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        name -> Text,
+        value -> Text,
+        part_id -> Integer,
+    }
+}
+
+table! {
+    part_suppliers (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        supplier_pn -> Text,
+        part_id -> Integer,
+        supplier_id -> Integer,
     }
 }
 
@@ -55,10 +137,47 @@ table! {
         nostuff -> Integer,
         bom_part_id -> Integer,
         part_id -> Integer,
+        alias_id -> Nullable<Integer>,
+        archived -> Integer,
+        manual -> Integer,
+    }
+}
+
+table! {
+    suppliers (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        name -> Text,
     }
 }
 
+joinable!(aliases -> parts (part_id));
+joinable!(bom_refdes -> parts_parts (line_id));
+joinable!(bom_substitutions -> builds (build_id));
+joinable!(bom_substitutions -> parts (part_id));
+joinable!(bom_substitutions -> parts_parts (line_id));
 joinable!(builds -> parts (part_id));
+joinable!(inventories -> part_alternates (alternate_id));
 joinable!(inventories -> parts (part_id));
+joinable!(part_alternates -> manufacturers (manufacturer_id));
+joinable!(part_alternates -> parts (part_id));
+joinable!(part_attributes -> parts (part_id));
+joinable!(parts -> manufacturers (manufacturer_id));
+joinable!(part_suppliers -> parts (part_id));
+joinable!(part_suppliers -> suppliers (supplier_id));
 
-allow_tables_to_appear_in_same_query!(builds, inventories, parts, parts_parts,);
+allow_tables_to_appear_in_same_query!(
+    aliases,
+    bom_refdes,
+    bom_substitutions,
+    builds,
+    inventories,
+    manufacturers,
+    part_alternates,
+    part_attributes,
+    part_suppliers,
+    parts,
+    parts_parts,
+    suppliers,
+);
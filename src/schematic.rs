@@ -4,6 +4,22 @@ use serde::Deserialize;
 // Has all the necessary hooks to get the proper
 // Information from the files!
 
+/// Anything a BOM can be imported from. The Eagle schematic
+/// implements this but so can other formats or in-memory fixtures.
+pub trait SchematicSource {
+    /// All placed parts
+    fn parts(&self) -> Vec<Part>;
+
+    /// Global attributes. (PN and DESC for the BOM)
+    fn attributes(&self) -> Vec<Attribute>;
+
+    /// Assembly variant definitions
+    fn variants(&self) -> Vec<VariantDef>;
+
+    /// Attributes of a part number within a library. None if not found.
+    fn library_attributes(&self, library: &str, part_number: &str) -> Option<Vec<Attribute>>;
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Eagle {
     pub drawing: Drawing,
@@ -99,8 +115,15 @@ pub struct Part {
     pub deviceset: String,
     pub technology: Option<String>,
     pub device: String,
+    pub value: Option<String>,
     #[serde(rename = "variant", default)]
     pub variants: Vec<Variant>,
+    /// Instance attributes. These override the library.
+    #[serde(rename = "attribute", default)]
+    pub attributes: Vec<Attribute>,
+    /// Library the part comes from if the format ties it to one. i.e. KiCad's nickname
+    #[serde(skip)]
+    pub library: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -110,3 +133,45 @@ pub struct Variant {
     pub value: Option<String>,
     pub technology: Option<String>,
 }
+
+impl SchematicSource for Eagle {
+    fn parts(&self) -> Vec<Part> {
+        self.drawing.schematic.parts.part.clone()
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        self.drawing.schematic.attributes.attribute.clone()
+    }
+
+    fn variants(&self) -> Vec<VariantDef> {
+        self.drawing.schematic.variantdefs.variantdef.clone()
+    }
+
+    fn library_attributes(&self, library: &str, part_number: &str) -> Option<Vec<Attribute>> {
+        // Check if it's the library we care about.
+        let library = self
+            .drawing
+            .schematic
+            .libraries
+            .library
+            .iter()
+            .find(|l| l.name == library)?;
+
+        for deviceset in &library.devicesets.deviceset {
+            for device in &deviceset.devices.device {
+                // Every new technology creates a new part.
+                for technology in &device.technologies.technology {
+                    let library_part_number =
+                        format!("{}{}{}", deviceset.name, technology.name, device.name);
+
+                    // Check if found.
+                    if library_part_number == part_number {
+                        return Some(technology.attribute.clone().unwrap_or_default());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
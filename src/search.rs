@@ -0,0 +1,431 @@
+use anyhow::anyhow;
+use regex::{Regex, RegexBuilder};
+
+// Used by `parts search`. A query is a list of terms that all have
+// to match. A term is a substring, a glob (`GRM*`) or a regex
+// (`/^RC0402/`) and can be limited to a field with `field:`.
+// Parametric attributes are compared with `name=value`, `name>=16V`,
+// etc. Values with SI prefixes are compared as numbers.
+
+/// Fields a term can be limited to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Field {
+    Pn,
+    Mpn,
+    Manufacturer,
+    Descr,
+    Val,
+    Supplier,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name.to_lowercase().as_str() {
+            "pn" => Some(Field::Pn),
+            "mpn" => Some(Field::Mpn),
+            "manufacturer" | "mfr" => Some(Field::Manufacturer),
+            "desc" | "descr" => Some(Field::Descr),
+            "val" | "value" => Some(Field::Val),
+            "supplier" | "spn" => Some(Field::Supplier),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of a part that can be searched
+#[derive(Clone, Debug, Default)]
+pub struct Searchable<'a> {
+    pub pn: &'a str,
+    pub mpn: &'a str,
+    pub manufacturer: Option<&'a str>,
+    pub descr: &'a str,
+    pub val: Option<&'a str>,
+    pub suppliers: Vec<&'a str>,
+    /// Parametric attributes as (name, value)
+    pub attributes: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Searchable<'a> {
+    /// Values of one field. Parts can have many supplier PNs.
+    fn values(&self, field: Field) -> Vec<&'a str> {
+        match field {
+            Field::Pn => vec![self.pn],
+            Field::Mpn => vec![self.mpn],
+            Field::Manufacturer => self.manufacturer.into_iter().collect(),
+            Field::Descr => vec![self.descr],
+            Field::Val => self.val.into_iter().collect(),
+            Field::Supplier => self.suppliers.clone(),
+        }
+    }
+}
+
+/// How a parametric attribute is compared
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A single search term
+#[derive(Debug)]
+pub enum Term {
+    /// A pattern. No field searches all of them.
+    Text {
+        field: Option<Field>,
+        pattern: Regex,
+    },
+    /// A parametric attribute. i.e. `voltage>=16V`
+    Attribute {
+        name: String,
+        comparison: Comparison,
+        value: String,
+    },
+}
+
+impl Term {
+    /// Parses `[field:]pattern` or `name<comparison>value`
+    pub fn parse(term: &str) -> anyhow::Result<Term> {
+        // Regexes can have a `:` or `=` in them
+        let split = if term.starts_with('/') {
+            None
+        } else {
+            term.char_indices()
+                .find(|&(i, c)| matches!(c, ':' | '=' | '<' | '>') || term[i..].starts_with("!="))
+        };
+
+        let (field, pattern) = match split {
+            Some((i, ':')) => {
+                let name = &term[..i];
+                match Field::from_name(name) {
+                    Some(field) => (Some(field), &term[i + 1..]),
+                    None => return Err(anyhow!("Unknown field {} in {}", name, term)),
+                }
+            }
+            Some((i, _)) => return Term::parse_attribute(term, i),
+            None => (None, term),
+        };
+
+        if pattern.is_empty() {
+            return Err(anyhow!("Nothing to search for in {}", term));
+        }
+
+        Ok(Term::Text {
+            field,
+            pattern: to_regex(pattern).map_err(|e| anyhow!("Invalid pattern {}: {}", term, e))?,
+        })
+    }
+
+    /// Parses the `name<comparison>value` at `i`
+    fn parse_attribute(term: &str, i: usize) -> anyhow::Result<Term> {
+        let name = term[..i].trim();
+        let rest = &term[i..];
+
+        let comparisons = [
+            (">=", Comparison::Ge),
+            ("<=", Comparison::Le),
+            ("!=", Comparison::Ne),
+            ("==", Comparison::Eq),
+            ("=", Comparison::Eq),
+            (">", Comparison::Gt),
+            ("<", Comparison::Lt),
+        ];
+
+        let (comparison, value) = comparisons
+            .iter()
+            .find_map(|(op, comparison)| rest.strip_prefix(op).map(|v| (*comparison, v.trim())))
+            .ok_or_else(|| anyhow!("Invalid comparison in {}", term))?;
+
+        if name.is_empty() {
+            return Err(anyhow!("No attribute name in {}", term));
+        }
+
+        if value.is_empty() {
+            return Err(anyhow!("Nothing to search for in {}", term));
+        }
+
+        // Check now rather than on every part
+        let numeric = !matches!(comparison, Comparison::Eq | Comparison::Ne);
+        if numeric && parse_quantity(value).is_none() {
+            return Err(anyhow!("{} isn't a number in {}", value, term));
+        }
+
+        Ok(Term::Attribute {
+            name: name.to_lowercase(),
+            comparison,
+            value: value.to_string(),
+        })
+    }
+
+    pub fn matches(&self, part: &Searchable) -> bool {
+        match self {
+            Term::Text { field, pattern } => {
+                let fields = match field {
+                    Some(field) => vec![*field],
+                    None => vec![
+                        Field::Pn,
+                        Field::Mpn,
+                        Field::Manufacturer,
+                        Field::Descr,
+                        Field::Val,
+                        Field::Supplier,
+                    ],
+                };
+
+                // Attribute values are searched when there's no field
+                let attributes = part
+                    .attributes
+                    .iter()
+                    .filter(|_| field.is_none())
+                    .map(|(_, value)| *value);
+
+                fields
+                    .into_iter()
+                    .flat_map(|field| part.values(field))
+                    .chain(attributes)
+                    .any(|value| pattern.is_match(value))
+            }
+            Term::Attribute {
+                name,
+                comparison,
+                value,
+            } => part
+                .attributes
+                .iter()
+                .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+                .any(|(_, v)| compare(v, *comparison, value)),
+        }
+    }
+}
+
+/// Compares an attribute value. Numbers (with SI prefixes) are compared
+/// as numbers and anything else without case. `=` can be a glob.
+fn compare(value: &str, comparison: Comparison, wanted: &str) -> bool {
+    let ordering = match (parse_quantity(value), parse_quantity(wanted)) {
+        (Some(value), Some(wanted)) => value.partial_cmp(&wanted),
+        _ => {
+            let equal = match to_regex(wanted) {
+                Ok(pattern) if wanted.contains(['*', '?']) => pattern.is_match(value),
+                _ => value.trim().eq_ignore_ascii_case(wanted),
+            };
+
+            return match comparison {
+                Comparison::Eq => equal,
+                Comparison::Ne => !equal,
+                _ => false,
+            };
+        }
+    };
+
+    let ordering = match ordering {
+        Some(x) => x,
+        None => return false,
+    };
+
+    match comparison {
+        Comparison::Eq => ordering.is_eq(),
+        Comparison::Ne => ordering.is_ne(),
+        Comparison::Lt => ordering.is_lt(),
+        Comparison::Le => ordering.is_le(),
+        Comparison::Gt => ordering.is_gt(),
+        Comparison::Ge => ordering.is_ge(),
+    }
+}
+
+/// SI prefixes. R is used as a decimal point for resistors. i.e. 4R7
+fn prefix_multiplier(prefix: char) -> Option<f64> {
+    match prefix {
+        'p' => Some(1e-12),
+        'n' => Some(1e-9),
+        'u' | 'µ' => Some(1e-6),
+        'm' => Some(1e-3),
+        'R' | 'r' => Some(1.0),
+        'k' | 'K' => Some(1e3),
+        'M' => Some(1e6),
+        'G' => Some(1e9),
+        _ => None,
+    }
+}
+
+/// Parses a value like 16V, 100nF, 4k7 or -40C. Units are ignored.
+pub fn parse_quantity(value: &str) -> Option<f64> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, value.strip_prefix('+').unwrap_or(&value)),
+    };
+
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+
+    if end == 0 {
+        return None;
+    }
+
+    let mut number = value[..end].to_string();
+    let mut rest = &value[end..];
+    let mut multiplier = 1.0;
+
+    if let Some(prefix) = rest.chars().next() {
+        if let Some(m) = prefix_multiplier(prefix) {
+            // The prefix can be the decimal point. i.e. 4k7
+            let after = &rest[prefix.len_utf8()..];
+            let decimals = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+
+            if decimals > 0 {
+                if number.contains('.') {
+                    return None;
+                }
+
+                number.push('.');
+                number.push_str(&after[..decimals]);
+            }
+
+            multiplier = m;
+            rest = &after[decimals..];
+        }
+    }
+
+    // Whatever is left has to be a unit
+    if !rest
+        .chars()
+        .all(|c| c.is_alphabetic() || c == '%' || c == '°' || c == 'Ω')
+    {
+        return None;
+    }
+
+    number.parse::<f64>().ok().map(|n| sign * n * multiplier)
+}
+
+/// Turns a substring, glob or `/regex/` into a regex without case
+fn to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let expression = if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        pattern[1..pattern.len() - 1].to_string()
+    } else if pattern.contains(['*', '?']) {
+        glob_to_regex(pattern)
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexBuilder::new(&expression)
+        .case_insensitive(true)
+        .build()
+}
+
+/// Globs match the whole value. `*` is anything and `?` is one character.
+fn glob_to_regex(glob: &str) -> String {
+    let mut expression = String::from("^");
+
+    for c in glob.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            c => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    expression.push('$');
+    expression
+}
+
+/// A search. Every term has to match.
+#[derive(Debug)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn parse(terms: &[String]) -> anyhow::Result<Query> {
+        let terms = terms
+            .iter()
+            .map(|t| Term::parse(t.trim()))
+            .collect::<anyhow::Result<Vec<Term>>>()?;
+
+        Ok(Query { terms })
+    }
+
+    pub fn matches(&self, part: &Searchable) -> bool {
+        self.terms.iter().all(|t| t.matches(part))
+    }
+}
+
+#[test]
+fn test_search_terms() {
+    let part = Searchable {
+        pn: "CAP-1U-0402",
+        mpn: "GRM155R61A105KE15D",
+        manufacturer: Some("Murata"),
+        descr: "CAP CER 1UF 10V X5R 0402",
+        val: Some("1u"),
+        suppliers: vec!["490-3890-1-ND"],
+        attributes: vec![("package", "0402"), ("voltage", "10V")],
+    };
+
+    let matches = |terms: &[&str]| {
+        let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+        Query::parse(&terms).unwrap().matches(&part)
+    };
+
+    // Substrings anywhere, without case
+    assert!(matches(&["x5r"]));
+    assert!(matches(&["3890"]));
+    assert!(!matches(&["x7r"]));
+
+    // Globs match the whole field
+    assert!(matches(&["mpn:GRM*"]));
+    assert!(!matches(&["mpn:*R61"]));
+    assert!(matches(&["mpn:*R61*"]));
+    assert!(!matches(&["pn:GRM*"]));
+    assert!(matches(&["pn:CAP-?U-*"]));
+    assert!(matches(&["mfr:murata"]));
+    assert!(!matches(&["manufacturer:yageo"]));
+
+    // Regexes
+    assert!(matches(&["desc:/\\d+V/"]));
+    assert!(matches(&["/^cap-1u/"]));
+    assert!(matches(&["val:/^1u$/"]));
+
+    // Every term has to match
+    assert!(matches(&["mpn:GRM*", "supplier:490-*"]));
+    assert!(!matches(&["mpn:GRM*", "val:10u"]));
+
+    // Parametric attributes
+    assert!(matches(&["package=0402"]));
+    assert!(matches(&["PACKAGE=04*"]));
+    assert!(!matches(&["package!=0402"]));
+    assert!(matches(&["voltage>=6.3V", "voltage<=10"]));
+    assert!(!matches(&["voltage>=16V"]));
+    assert!(!matches(&["tolerance<=10%"]));
+}
+
+#[test]
+fn test_parse_quantity() {
+    assert_eq!(Some(16.0), parse_quantity("16V"));
+    assert_eq!(Some(4700.0), parse_quantity("4k7"));
+    assert_eq!(Some(4.7), parse_quantity("4R7"));
+    assert_eq!(Some(-40.0), parse_quantity("-40 °C"));
+    assert_eq!(Some(1.0), parse_quantity("1%"));
+    assert!((parse_quantity("100nF").unwrap() - 1e-7).abs() < 1e-15);
+    assert_eq!(None, parse_quantity("X5R"));
+    assert_eq!(None, parse_quantity("-40~85C"));
+}
+
+#[test]
+fn test_search_errors() {
+    let error = Term::parse("color:red").unwrap_err();
+    assert_eq!("Unknown field color in color:red", error.to_string());
+
+    assert!(Term::parse("mpn:").is_err());
+    assert!(Term::parse("/(/").is_err());
+    assert!(Term::parse("=16V").is_err());
+
+    let error = Term::parse("voltage>=high").unwrap_err();
+    assert_eq!("high isn't a number in voltage>=high", error.to_string());
+}
@@ -1,4 +1,6 @@
 pub mod bom;
 pub mod builds;
+pub mod diff;
 pub mod inventory;
 pub mod parts;
+pub mod report;
@@ -2,7 +2,9 @@ extern crate diesel;
 extern crate quick_xml;
 extern crate serde;
 
-use crate::schematic::VariantDef;
+use crate::config::{ImportPolicy, LibraryConfig, OnExistingBom, OnPartChange};
+use crate::csv_bom::{self, CsvBom};
+use crate::schematic::{SchematicSource, VariantDef};
 use crate::*;
 use prettytable::Table;
 use quick_xml::de::from_reader;
@@ -11,18 +13,26 @@ use self::diesel::prelude::*;
 
 use chrono::Utc;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
 use std::io::{BufReader, BufWriter};
-use std::{
-    fs::File,
-    io::{StdinLock, Stdout},
-};
+use std::path::Path;
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Default)]
 struct LineItem {
     name: String,
+    /// Each refdes in the name along with its value
+    designators: Vec<(String, Option<String>)>,
     pn: String,
     quantity: i32,
     nostuff: i32,
+    /// Instance overrides
+    mqty: Option<i32>,
+    mpn: Option<String>,
+    descr: Option<String>,
+    manufacturer: Option<String>,
+    /// Only set if the format ties parts to a library. i.e. KiCad
+    library: Option<String>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -32,7 +42,15 @@ struct SimplePart {
     descr: String,
     ver: i32,
     mqty: i32,
-    nostuff: i32,
+    library: Option<String>,
+    alias: Option<String>,
+    val: Option<String>,
+    manufacturer: Option<String>,
+    /// A library MQTY that isn't a number. Reported by validate_parts.
+    invalid_mqty: Option<String>,
+    suppliers: Vec<(String, String)>,
+    /// Parametric attributes. i.e. (package, 0402)
+    attributes: Vec<(String, String)>,
 }
 
 #[derive(Serialize)]
@@ -41,33 +59,28 @@ struct BomEntry {
     quantity: i32,
     refdes: String,
     mpn: String,
+    manufacturer: String,
     descr: String,
+    val: String,
     ver: i32,
     inventory_qty: i32,
     no_stuff: i32,
 }
 
-/// Helper function that prints a list of SimpleParts
-fn print_simple_part_list(list: &[SimplePart]) {
+/// Helper function that prints which library each part was resolved from
+fn print_library_report(list: &[SimplePart]) {
     let mut table = Table::new();
-    table.add_row(row![
-        "PART NUMBER",
-        "MPN",
-        "DESCRIPTION",
-        "MULTI QUANTITY",
-        "VERSION",
-        "NO STUFF"
-    ]);
+    table.add_row(row!["PART NUMBER", "LIBRARY"]);
 
     for part in list {
-        table.add_row(row![
-            part.pn,
-            part.mpn,
-            part.descr,
-            part.mqty,
-            part.ver,
-            part.nostuff
-        ]);
+        // Parts with an MPN on the instance don't need a library
+        let library = match &part.library {
+            Some(library) => library.as_str(),
+            None if !part.mpn.is_empty() => "-",
+            None => "UNRESOLVED",
+        };
+
+        table.add_row(row![part.pn, library]);
     }
 
     table.printstd();
@@ -87,106 +100,159 @@ fn print_simple_part_list(list: &[SimplePart]) {
 // }
 
 /// Updates a SimplePart based on attribute data from the library.
+/// Libraries are searched in order and the first one with the part wins.
 fn get_simplepart_from_library(
     item: &LineItem,
-    eagle: &schematic::Eagle,
-    library_name: &str,
+    source: &dyn SchematicSource,
+    libraries: &[LibraryConfig],
+    suppliers: &[String],
 ) -> SimplePart {
-    // Flags
-    // let mut found = false;
-    // let mut is_alias = false;
-
-    // Quantity local
-    // let mut mqty_temp: i32 = 1;
     let mut part = SimplePart {
         pn: item.pn.clone(),
-        nostuff: item.nostuff,
-        mqty: item.quantity,
+        mqty: 1,
         ..Default::default()
     };
 
-    for library in &eagle.drawing.schematic.libraries.library {
-        // Check if it's the library we care about.
-        if library.name == library_name {
-            'outer: for deviceset in &library.devicesets.deviceset {
-                for device in &deviceset.devices.device {
-                    // Every new technology creates a new part.
-                    for technology in &device.technologies.technology {
-                        let library_part_number =
-                            format!("{}{}{}", deviceset.name, technology.name, device.name);
-
-                        // Check if found.
-                        if library_part_number == item.pn {
-                            match &technology.attribute {
-                                Some(attributes) => {
-                                    // Get the attributes we care about.
-                                    for attribute in attributes {
-                                        // Blank value check
-                                        if attribute.value.is_empty() {
-                                            continue;
-                                        }
-
-                                        if attribute.name == "MPN" {
-                                            part.mpn = attribute.value.clone();
-                                        } else if attribute.name == "DIGIKEYPN" {
-                                            // part.digikeypn = &attribute.name;
-                                        } else if attribute.name == "DESC" {
-                                            part.descr = attribute.value.clone();
-                                        } else if attribute.name == "MQTY" {
-                                            // Convert to int
-                                            part.mqty = attribute
-                                                .value
-                                                .trim()
-                                                .parse()
-                                                .expect("Unable to convert mqty");
-                                        } else if attribute.name == "ALIAS" {
-                                            println!("Alias!");
-                                            // // Set flag
-                                            // is_alias = true;
-
-                                            // // Get the part that this is aliasing..
-                                            // let alias = find_part_by_pn(&app.conn, &attribute.value);
-
-                                            // // Sort it out
-                                            // let alias = match alias {
-                                            //     Ok(x) => x,
-                                            //     Err(_) => {
-                                            //         println!("Unable to find alias {}!", attribute.value);
-                                            //         std::process::exit(1);
-                                            //     }
-                                            // };
-
-                                            // // Clone these bits so the live on
-                                            // part.pn = alias.pn.clone();
-                                            // part.mpn = alias.mpn.clone();
-                                            // part.descr = alias.descr.clone();
-                                            // part.ver = alias.ver;
-                                            // part.mqty = alias.mqty;
-                                        }
-                                    }
-                                }
-                                None => (),
-                            };
-
-                            // Break the outer loop since we found it
-                            break 'outer;
-                        }
-                    }
-                }
-            }
-        };
+    // Parts tied to a library are only looked up there. A configured library of the
+    // same name still maps its attributes.
+    let own: Vec<LibraryConfig> = match &item.library {
+        Some(name) => vec![libraries
+            .iter()
+            .find(|l| &l.name == name)
+            .cloned()
+            .unwrap_or_else(|| LibraryConfig {
+                name: name.clone(),
+                ..Default::default()
+            })],
+        None => Vec::new(),
+    };
+    let libraries = if item.library.is_some() {
+        &own
+    } else {
+        libraries
+    };
+
+    // Find the first library that has this part
+    let found = libraries.iter().find_map(|library| {
+        source
+            .library_attributes(&library.name, &item.pn)
+            .map(|attributes| (library, attributes))
+    });
+
+    if let Some((library, attributes)) = found {
+        part.library = Some(library.name.clone());
+        apply_library_attributes(&mut part, library, suppliers, attributes);
+    }
+
+    // Instance attributes win over the library
+    if let Some(mpn) = &item.mpn {
+        part.mpn = mpn.clone();
+    }
+
+    if let Some(descr) = &item.descr {
+        part.descr = descr.clone();
+    }
+
+    if item.manufacturer.is_some() {
+        part.manufacturer = item.manufacturer.clone();
+    }
+
+    // The schematic value wins too. Different ones are found by find_conflicting_values.
+    if let Some(value) = item.designators.iter().find_map(|(_, v)| v.clone()) {
+        part.val = Some(value);
     }
 
     // Return it
     part
+}
+
+/// Sets the attributes we care about from a library entry
+fn apply_library_attributes(
+    part: &mut SimplePart,
+    library: &LibraryConfig,
+    suppliers: &[String],
+    attributes: Vec<schematic::Attribute>,
+) {
+    // Get the attributes we care about.
+    for attribute in attributes {
+        // Blank value check
+        if attribute.value.is_empty() {
+            continue;
+        }
+
+        // Use the mapped name if there is one
+        let name = library
+            .attribute_map
+            .get(&attribute.name)
+            .unwrap_or(&attribute.name);
 
-    // Set the quantity if it's *not* an alias
-    // if !is_alias {
-    //     part.mqty = mqty_temp;
-    // } else {
-    //     // If it is an alias, update mqty
-    //     item.quantity = mqty_temp;
-    // }
+        if name == "MPN" {
+            part.mpn = attribute.value.clone();
+        } else if let Some(supplier) = supplier_from_attribute(name, suppliers) {
+            part.suppliers
+                .push((supplier.to_string(), attribute.value.trim().to_string()));
+        } else if name == "DESC" {
+            part.descr = attribute.value.clone();
+        } else if name == "MQTY" {
+            // Convert to int
+            match attribute.value.trim().parse() {
+                Ok(mqty) => part.mqty = mqty,
+                Err(_) => part.invalid_mqty = Some(attribute.value.trim().to_string()),
+            }
+        } else if name == "ALIAS" {
+            part.alias = Some(attribute.value.clone());
+        } else if name == "MANUFACTURER" {
+            part.manufacturer = Some(attribute.value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("VALUE") {
+            part.val = Some(attribute.value.trim().to_string());
+        } else if is_parametric_attribute(name) {
+            part.attributes
+                .push((name.to_lowercase(), attribute.value.trim().to_string()));
+        }
+    }
+}
+
+/// Everything not used for something else is a parametric attribute. Except the schematic
+/// tool's own fields and part numbers of suppliers that aren't configured.
+fn is_parametric_attribute(name: &str) -> bool {
+    let name = name.to_uppercase();
+
+    // Reference is KiCad's refdes prefix
+    let tool_field = matches!(
+        name.as_str(),
+        "DNP" | "NOSTUFF" | "REFERENCE" | "FOOTPRINT" | "DATASHEET" | "DESCRIPTION"
+    ) || name.starts_with("KI_")
+        || name.starts_with("SIM.");
+
+    !tool_field && !name.ends_with("PN")
+}
+
+/// Gets the configured supplier of a `<SUPPLIER>PN` attribute. i.e. DIGIKEYPN is DIGIKEY
+fn supplier_from_attribute<'a>(name: &str, suppliers: &'a [String]) -> Option<&'a str> {
+    let supplier = name.strip_suffix("PN")?;
+    suppliers
+        .iter()
+        .find(|s| s.as_str() == supplier)
+        .map(|s| s.as_str())
+}
+
+/// Gets a (non blank) instance attribute
+fn instance_attribute<'a>(part: &'a schematic::Part, name: &str) -> Option<&'a str> {
+    part.attributes
+        .iter()
+        .find(|a| a.name == name && !a.value.trim().is_empty())
+        .map(|a| a.value.trim())
+}
+
+/// Checks for a DNP or NOSTUFF instance attribute. Blank, "no", "false" and "0" don't count.
+fn is_instance_nostuff(part: &schematic::Part) -> bool {
+    part.attributes.iter().any(|a| {
+        (a.name == "DNP" || a.name == "NOSTUFF")
+            && !matches!(
+                a.value.trim().to_lowercase().as_str(),
+                "" | "no" | "false" | "0"
+            )
+    })
 }
 
 /// Using a list of parts, this function determines the line items for a BOM
@@ -208,9 +274,10 @@ fn get_line_items_from_parts(
 
         // Technology is optional. So need to do a match here.
         let mut technology = part.technology.clone().unwrap_or_default();
+        let mut value = part.value.clone();
 
         // Check if it's no stuff. If so skip over adding it.
-        let mut nostuff = 0;
+        let mut nostuff = is_instance_nostuff(part) as i32;
         for var in &part.variants {
             if var.name == variant.name {
                 // Only update the technology if it exists!
@@ -219,6 +286,11 @@ fn get_line_items_from_parts(
                     None => technology,
                 };
 
+                // Same with the value
+                if var.value.is_some() {
+                    value = var.value.clone();
+                }
+
                 // Set no stuff
                 if var.populate == Some("no".to_string()) {
                     nostuff = 1;
@@ -229,26 +301,41 @@ fn get_line_items_from_parts(
         // Concatinate all the elements to form the actual part number
         let part_number = format!("{}{}{}", part.deviceset, technology, part.device,);
 
+        // Instance MQTY overrides MQTY from the library. Invalid ones are found by find_invalid_mqty.
+        let mqty = instance_attribute(part, "MQTY").and_then(|m| m.parse().ok());
+
+        let value = value.filter(|v| !v.is_empty());
+
         // Create temp line item
         let item = LineItem {
             name: part.name.clone(),
+            designators: vec![(part.name.clone(), value)],
             pn: part_number,
             quantity: 1,
             nostuff,
+            mqty,
+            mpn: instance_attribute(part, "MPN").map(String::from),
+            descr: instance_attribute(part, "DESC").map(String::from),
+            manufacturer: instance_attribute(part, "MANUFACTURER").map(String::from),
+            library: part.library.clone(),
         };
 
-        // TODO: Check if part has attribute (MQTY). This overrides MQTY from the library.
-
-        // Check if list has
+        // Check if list has. Overrides have to match too.
         let mut found = false;
-        if let Some(entry) = list
-            .iter_mut()
-            .find(|part| part.pn == item.pn && part.nostuff == item.nostuff)
-        {
+        if let Some(entry) = list.iter_mut().find(|part| {
+            part.pn == item.pn
+                && part.library == item.library
+                && part.nostuff == item.nostuff
+                && part.mqty == item.mqty
+                && part.mpn == item.mpn
+                && part.descr == item.descr
+                && part.manufacturer == item.manufacturer
+        }) {
             found = true;
 
             // Increase the quantity
             entry.name = format!("{} {}", entry.name, item.name);
+            entry.designators.extend(item.designators.clone());
             entry.quantity += 1;
         }
 
@@ -263,75 +350,142 @@ fn get_line_items_from_parts(
     list
 }
 
-/// Compares a 'new' part with an existing Part. Prompts for user input.
-/// Responds witha boolean value of whether or not to update.
-fn prompt_to_update_part(
-    prompt: &mut prompt::Prompt<StdinLock, Stdout>,
-    new: &models::NewUpdatePart,
-    existing: &models::Part,
-) -> bool {
-    // Check for changes and ask if want to update.
-    if new.mpn != existing.mpn || new.descr != existing.descr || *new.ver != existing.ver {
-        let question = format!("{} found! Would you like to update it?", existing.pn);
+/// Instance MQTY attributes that aren't numbers
+fn find_invalid_mqty(parts: &[schematic::Part]) -> Vec<String> {
+    parts
+        .iter()
+        .filter_map(|part| {
+            let mqty = instance_attribute(part, "MQTY")?;
 
-        // Create the table
-        let mut table = Table::new();
-        table.add_row(row!["", "pn", "mpn", "decr", "mqty", "ver"]);
-        table.add_row(row![
-            "Current:",
-            existing.pn,
-            existing.mpn,
-            existing.descr,
-            existing.mqty,
-            existing.ver
-        ]);
-        table.add_row(row![
-            "Change to:",
-            new.pn,
-            new.mpn,
-            new.descr,
-            new.mqty,
-            new.ver
-        ]);
-        table.printstd();
+            match mqty.parse::<i32>() {
+                Ok(_) => None,
+                Err(_) => Some(format!("{} has an invalid MQTY: {}", part.name, mqty)),
+            }
+        })
+        .collect()
+}
+
+/// Instance overrides can't turn one part number into different parts
+fn find_conflicting_overrides(items: &[(LineItem, SimplePart)]) -> Vec<String> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut reported: Vec<&str> = Vec::new();
+
+    for (index, (item, part)) in items.iter().enumerate() {
+        if part.alias.is_some() || reported.contains(&part.pn.as_str()) {
+            continue;
+        }
+
+        let other = items[index + 1..].iter().find(|(_, other)| {
+            other.pn == part.pn
+                && (other.mpn != part.mpn
+                    || other.descr != part.descr
+                    || other.manufacturer != part.manufacturer)
+        });
 
-        // Return response to question
-        return prompt.ask_yes_no_question(&question);
+        if let Some((other_item, _)) = other {
+            errors.push(format!(
+                "{} has a different MPN, DESC or MANUFACTURER on {} and {}. Use another part number.",
+                part.pn, item.name, other_item.name
+            ));
+            reported.push(&part.pn);
+        }
     }
 
-    // Otherwise return false
-    false
+    errors
 }
 
-/// Function used to import parts from file
-pub fn import(app: &mut crate::Application, filename: &str) {
-    use crate::schema::parts::dsl::*;
+/// Placements of one part number need the same value to be one part. Blank values don't count.
+fn find_conflicting_values(items: &[(LineItem, SimplePart)]) -> Vec<String> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut reported: Vec<&str> = Vec::new();
 
-    // Open the file
-    let file = File::open(filename);
+    // (PN, refdes, value) of the first placement with a value
+    let mut first: Vec<(&str, &str, &str)> = Vec::new();
 
-    // Make sure it's valid
-    let file = match file {
-        Ok(x) => x,
-        Err(_) => {
-            println!("Unable to open {}", filename);
-            std::process::exit(1);
+    for (item, part) in items.iter().filter(|(_, p)| p.alias.is_none()) {
+        for (refdes, value) in &item.designators {
+            let value = match value {
+                Some(v) => v.as_str(),
+                None => continue,
+            };
+
+            match first.iter().find(|(pn, _, _)| *pn == part.pn) {
+                None => first.push((&part.pn, refdes, value)),
+                Some((pn, other, other_value)) => {
+                    if *other_value != value && !reported.contains(pn) {
+                        errors.push(format!(
+                            "{} has the value {} on {} and {} on {}. Use another part number.",
+                            pn, other_value, other, value, refdes
+                        ));
+                        reported.push(pn);
+                    }
+                }
+            }
         }
-    };
+    }
 
-    let file = BufReader::new(file);
-    let eagle: schematic::Eagle = from_reader(file).expect("error parsing xml");
+    errors
+}
+
+/// Supported schematic formats for import
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SchematicFormat {
+    Eagle,
+    KicadSch,
+    KicadNetlist,
+}
+
+impl std::str::FromStr for SchematicFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "eagle" => Ok(SchematicFormat::Eagle),
+            "kicad" | "kicad_sch" => Ok(SchematicFormat::KicadSch),
+            "kicad-netlist" | "kicad-xml" => Ok(SchematicFormat::KicadNetlist),
+            _ => Err(anyhow::anyhow!(
+                "Unknown format {}. Use eagle, kicad or kicad-netlist",
+                s
+            )),
+        }
+    }
+}
 
-    // println!("{:?}",eagle);
-    // let mut list: Vec<String> = [].to_vec();
+impl SchematicFormat {
+    /// Determines the format from a file extension
+    pub fn from_filename(filename: &str) -> Option<SchematicFormat> {
+        let extension = std::path::Path::new(filename)
+            .extension()?
+            .to_string_lossy()
+            .to_lowercase();
 
+        match extension.as_str() {
+            "sch" => Some(SchematicFormat::Eagle),
+            "kicad_sch" => Some(SchematicFormat::KicadSch),
+            "xml" | "net" => Some(SchematicFormat::KicadNetlist),
+            _ => None,
+        }
+    }
+}
+
+/// Makes sure every aliased part exists. Returns the part numbers that don't.
+fn find_missing_alias_targets(conn: &SqliteConnection, parts: &[SimplePart]) -> Vec<String> {
+    parts
+        .iter()
+        .filter_map(|p| p.alias.as_ref())
+        .filter(|target| find_part_by_pn(conn, target).is_err())
+        .cloned()
+        .collect()
+}
+
+/// Gets the BOM part number and description from the global attributes
+fn get_bom_pn_and_desc(attributes: &[schematic::Attribute]) -> anyhow::Result<(String, String)> {
     let mut found = false;
     let mut bom_pn = "".to_string();
     let mut bom_desc = "".to_string();
-    let mut revision = 1;
 
     // Parses it to make sure it has a global variable defining the part # for the assembly
-    for attribute in &eagle.drawing.schematic.attributes.attribute {
+    for attribute in attributes {
         // Get the part description
         if attribute.name == "DESC" {
             bom_desc = attribute.value.clone();
@@ -353,330 +507,4312 @@ pub fn import(app: &mut crate::Application, filename: &str) {
 
     // Error if PN is not found
     if !found {
-        println!("Please add PN attribute to schematic!");
-        std::process::exit(1);
+        return Err(anyhow::anyhow!("Please add PN attribute to schematic!"));
     }
 
-    // Get the variant list
-    let mut variant: Option<VariantDef> = None;
-    for v in &eagle.drawing.schematic.variantdefs.variantdef {
-        // Get the current def
-        if v.current == Some("yes".to_string()) {
-            println!("Variant: {}", v.name);
-            variant = Some(v.clone());
-            break;
-        }
-    }
+    Ok((bom_pn, bom_desc))
+}
 
-    // Error if not active variant
-    // TODO: or prompt for variant..
-    let variant = match variant {
-        Some(v) => v,
-        None => {
-            println!("Error: no active variant!");
+/// Function used to import parts from file
+pub fn import(
+    app: &mut crate::Application,
+    filename: &str,
+    format: &Option<String>,
+    selection: &VariantSelection,
+    policy: &ImportPolicy,
+    dry_run: bool,
+) {
+    let source = open_source(filename, format);
+
+    import_source(app, source.as_ref(), selection, policy, dry_run);
+}
+
+/// Imports a BOM from a CSV file. Rows without an MPN use the part in the database.
+pub fn import_csv(
+    app: &mut crate::Application,
+    filename: &str,
+    part_number: &str,
+    descr: &Option<String>,
+    policy: &ImportPolicy,
+    dry_run: bool,
+) {
+    // An existing BOM keeps its description
+    let descr = match (descr, find_part_by_pn(&app.conn, part_number)) {
+        (Some(d), _) => d.clone(),
+        (None, Ok(existing)) => existing.descr,
+        (None, Err(_)) => {
+            println!(
+                "{} is a new BOM. Use --desc to set the description.",
+                part_number
+            );
             std::process::exit(1);
         }
     };
 
-    // Serach for it and make sure that it matches
-    let res = find_part_by_pn(&app.conn, &bom_pn);
+    // Open the file
+    let file = match File::open(filename) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to open {}", filename);
+            std::process::exit(1);
+        }
+    };
 
-    // TODO: Check if there is inventory by part number for this part
+    let mut source = match CsvBom::from_reader(file, &app.config.csv_columns, part_number, &descr) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Unable to read {}: {}", filename, e);
+            std::process::exit(1);
+        }
+    };
 
-    // TODO: if no inventory, create 0 inventory
+    let errors = fill_csv_rows(&app.conn, &mut source.rows);
+    if !errors.is_empty() {
+        println!("ERRORS:");
+        for error in &errors {
+            println!("  {}", error);
+        }
+        println!("No changes have been made.");
+        std::process::exit(1);
+    }
 
-    // Skip a line
-    println!();
+    import_source(app, &source, &VariantSelection::Current, policy, dry_run);
+}
 
-    // Different actions depending if it exists
-    match res {
-        Ok(bom) => {
-            let question = format!("BOM {} found! Would you like to update it?", bom_pn);
-            let yes = app.prompt.ask_yes_no_question(&question);
+/// Fills in whatever a CSV row is missing from the part in the database
+fn fill_csv_rows(conn: &SqliteConnection, rows: &mut [csv_bom::Row]) -> Vec<String> {
+    let mut errors = Vec::new();
 
-            // If it already exists error/ask to update. Then runs the update routine instead
-            if yes {
-                // Ask if a new revision is requred
-                let question =
-                    format!("BOM {} found! Would you like to up-rev the design?", bom_pn);
-                let yes = app.prompt.ask_yes_no_question(&question);
+    for row in rows.iter_mut() {
+        // Only the MPN is known
+        if row.pn.is_empty() {
+            let mpn = row.mpn.clone().unwrap_or_default();
 
-                if yes {
-                    // Increment the version
-                    revision = bom.ver + 1;
+            let found = find_parts_by_mpn_and_manufacturer(conn, &mpn, row.manufacturer.as_deref())
+                .unwrap_or_default();
 
-                    // Save the revision
-                    diesel::update(parts)
-                        .set(ver.eq(revision))
-                        .filter(id.eq(bom.id))
-                        .execute(&app.conn)
-                        .expect("Unable to update BOM revision!");
-                } else {
-                    // Remove all previous BOM entries.
-                    delete_bom_list_by_id_and_ver(&app.conn, &bom.id, &bom.ver)
-                        .expect("Unable to delete previous entries");
+            match found.as_slice() {
+                [existing] => row.pn = existing.pn.clone(),
+                [] => {
+                    errors.push(format!(
+                        "Line {} has no part number and {} isn't in the database",
+                        row.line, mpn
+                    ));
+                    continue;
+                }
+                _ => {
+                    errors.push(format!(
+                        "Line {} has no part number and {} is made by more than one manufacturer",
+                        row.line, mpn
+                    ));
+                    continue;
                 }
-            } else {
-                // Exit then!
-                std::process::exit(0);
             }
         }
-        Err(_) => {
-            // Create new BOM part
-            let part = models::NewUpdatePart {
-                pn: &bom_pn,
-                mpn: &bom_pn,
-                descr: &bom_desc,
-                ver: &revision,
-                mqty: &1,
-            };
 
-            create_part(&app.conn, &part).expect("Unable to create BOM part!");
+        if let Ok(existing) = find_part_by_pn(conn, &row.pn) {
+            if row.manufacturer.is_none() && existing.manufacturer_id.is_some() {
+                row.manufacturer = find_manufacturer_name(conn, &existing.manufacturer_id).ok();
+            }
+
+            row.mpn.get_or_insert(existing.mpn);
+            row.descr.get_or_insert(existing.descr);
         }
     }
 
-    println!("\nPARTS LIST:");
-    let list = get_line_items_from_parts(
-        &eagle.drawing.schematic.parts.part,
-        &variant,
-        &app.config.part_number_ignore_list,
-    );
-
-    // Vector of SimpleParts
-    let mut simple_part_list: Vec<SimplePart> = Vec::new();
+    errors
+}
 
-    // Get MPN, DigikeyPn from Library exerpts
-    for item in list {
-        // Set part attributes from library
-        let part = get_simplepart_from_library(&item, &eagle, &app.config.library_name);
+/// Opens a schematic of the provided format (or figured out from the extension)
+pub fn open_source(filename: &str, format: &Option<String>) -> Box<dyn SchematicSource> {
+    // Use the provided format or figure it out from the extension
+    let format = match format {
+        Some(f) => f.parse(),
+        None => SchematicFormat::from_filename(filename)
+            .ok_or_else(|| anyhow::anyhow!("Unknown file type. Use --format to set it.")),
+    };
 
-        if part.mpn.is_empty() {
-            println!("Manufacturer part number must be set for {}", part.pn);
+    let format = match format {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{}", e);
             std::process::exit(1);
         }
+    };
 
-        // Add to list
-        simple_part_list.push(part.clone());
+    // Open the file
+    let file = File::open(filename);
 
-        // Find part
-        let existing = find_part_by_pn(&app.conn, &part.pn);
+    // Make sure it's valid
+    let file = match file {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to open {}", filename);
+            std::process::exit(1);
+        }
+    };
 
-        // Create update object
-        let npart = models::NewUpdatePart {
-            pn: &part.pn,
-            mpn: &part.mpn,
-            descr: &part.descr,
-            ver: &part.ver,
-            mqty: &part.mqty,
-        };
+    let file = BufReader::new(file);
 
-        // Not found, create
-        match existing {
-            Ok(e) => {
-                // Check if can be updated
-                if prompt_to_update_part(&mut app.prompt, &npart, &e) {
-                    update_part(&app.conn, &e.id, &npart).expect("Error updating part!");
-                }
-            }
-            Err(_) => {
-                println!("Creating: {:?}", npart);
-                create_part(&app.conn, &npart).expect("Unable to create part!");
+    let source: Box<dyn SchematicSource> = match format {
+        SchematicFormat::Eagle => {
+            let eagle: schematic::Eagle = from_reader(file).expect("error parsing xml");
+            Box::new(eagle)
+        }
+        // Sheets are opened relative to the file
+        SchematicFormat::KicadSch => match kicad::Kicad::from_sch_file(Path::new(filename)) {
+            Ok(x) => Box::new(x),
+            Err(e) => {
+                println!("Unable to read {}: {}", filename, e);
+                std::process::exit(1);
             }
+        },
+        SchematicFormat::KicadNetlist => {
+            Box::new(kicad::Kicad::from_netlist(file).expect("error parsing kicad netlist"))
         }
+    };
 
-        // Get the part ID
-        let line_item = find_part_by_pn(&app.conn, &npart.pn);
-        let bom_item = find_part_by_pn(&app.conn, &bom_pn);
+    source
+}
 
-        // Create BOM association between the part and the
-        // BOM info like QTY, REFDES, NOSTUFF
+/// Which assembly variant(s) to import
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VariantSelection {
+    /// The variant marked as current in the schematic. Imported as PN
+    Current,
+    /// A variant by name. Imported as PN-NAME
+    Named(String),
+    /// Every variant. Each imported as PN-NAME
+    All,
+}
 
-        if let (Ok(li), Ok(bi)) = (line_item, bom_item) {
-            // Create the new relationship
-            let relationship = models::NewPartsParts {
-                quantity: &item.quantity,
-                bom_ver: &bi.ver,
-                refdes: &item.name,
-                nostuff: &item.nostuff,
-                bom_part_id: &bi.id,
-                part_id: &li.id,
-            };
+/// Part number of the BOM for a specific variant
+pub fn variant_part_number(part_number: &str, variant: &str) -> String {
+    format!("{}-{}", part_number, variant)
+}
 
-            // Push them to the DB
-            create_bom_line_item(&app.conn, &relationship)
-                .expect("Unable to add new BOM line item.");
-        }
+/// Resolves a part number with an optional variant selector
+pub fn part_number_for_variant(part_number: &str, variant: &Option<String>) -> String {
+    match variant {
+        Some(v) => variant_part_number(part_number, v),
+        None => part_number.to_string(),
     }
+}
 
-    // Print out in simple part list format
-    print_simple_part_list(&simple_part_list);
+/// Picks the variants to import along with the BOM part number for each
+fn select_variants(
+    variants: &[VariantDef],
+    selection: &VariantSelection,
+    bom_pn: &str,
+) -> anyhow::Result<Vec<(VariantDef, String)>> {
+    match selection {
+        VariantSelection::Current => variants
+            .iter()
+            .find(|v| v.current == Some("yes".to_string()))
+            .map(|v| vec![(v.clone(), bom_pn.to_string())])
+            .ok_or_else(|| anyhow::anyhow!("no active variant!")),
+        VariantSelection::Named(name) => variants
+            .iter()
+            .find(|v| &v.name == name)
+            .map(|v| vec![(v.clone(), variant_part_number(bom_pn, &v.name))])
+            .ok_or_else(|| {
+                let names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+                anyhow::anyhow!(
+                    "variant {} not found! Available: {}",
+                    name,
+                    names.join(", ")
+                )
+            }),
+        VariantSelection::All => Ok(variants
+            .iter()
+            .map(|v| (v.clone(), variant_part_number(bom_pn, &v.name)))
+            .collect()),
+    }
 }
 
-/// Function used to show parts in BOM
-pub fn show(app: &mut crate::Application, part_number: &str, version: &Option<i32>) {
-    use crate::schema::*;
+/// Imports a BOM from any schematic source. Nothing is written if there are any errors
+/// and everything is written at once otherwise. A dry run only shows the plan.
+pub fn import_source(
+    app: &mut crate::Application,
+    source: &dyn SchematicSource,
+    selection: &VariantSelection,
+    policy: &ImportPolicy,
+    dry_run: bool,
+) {
+    let mut plan = plan_import(&app.conn, &app.config, source, selection);
+    apply_policy(&mut plan, policy);
 
-    // Find the part
-    let part = find_part_by_pn(&app.conn, &part_number);
+    print_plan(&plan);
 
-    if part.is_err() {
-        println!("{} was not found!", part_number);
+    if !plan.errors.is_empty() {
+        println!("\nERRORS:");
+        for error in &plan.errors {
+            println!("  {}", error);
+        }
+        println!("No changes have been made.");
         std::process::exit(1);
     }
 
-    // Transform the response into a Part
-    let part = part.unwrap();
+    if dry_run {
+        println!("\nDry run. No changes have been made.");
+        return;
+    }
 
-    // Create the table
-    let mut table = Table::new();
+    confirm_plan(app, &mut plan, policy);
 
-    // Then either use the provided version or the latest
-    let ver = match version {
-        Some(x) => x,
-        None => &part.ver,
-    };
+    if let Err(e) = apply_plan(&app.conn, &plan) {
+        println!("Unable to import: {}. No changes have been made.", e);
+        std::process::exit(1);
+    }
+}
 
-    // Get all the parts related to this BOM
-    let mut results = parts_parts::dsl::parts_parts
-        .filter(parts_parts::dsl::bom_part_id.eq(part.id))
-        .filter(parts_parts::dsl::bom_ver.eq(ver))
-        .load::<models::PartsPart>(&app.conn)
-        .expect("Error loading parts");
+/// What an import does with a BOM part
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BomAction {
+    /// New BOM part at version 1
+    Create,
+    /// Exists at this version. Up-rev or overwrite is asked before applying.
+    Undecided(i32),
+    /// Replace the lines of this version
+    Overwrite(i32),
+    /// Write the lines as this new version
+    Revise(i32),
+    /// Leave the BOM alone
+    Skip,
+}
 
-    // Sort the results by refdes
-    results.sort_by(|a, b| a.refdes.cmp(&b.refdes));
+/// What an import does with a line item part
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PartAction {
+    Create,
+    /// Exists and matches
+    Unchanged,
+    /// Exists but is different. (field, current, new) for each change. Asked before applying.
+    Update(Vec<(&'static str, String, String)>),
+    /// Exists but is different and is left alone
+    Keep,
+}
 
-    println!("Displaying {} parts", results.len());
+/// A line item part and what happens to it
+#[derive(Debug)]
+struct PartPlan {
+    part: SimplePart,
+    action: PartAction,
+}
 
-    println!(
-        "Part Number: {} BOM Id: {} Version: {}",
-        part.pn, part.id, ver
-    );
+/// A BOM and everything that gets written for it
+#[derive(Debug)]
+struct BomPlan {
+    pn: String,
+    descr: String,
+    action: BomAction,
+    /// Parts that aren't aliases. One per part number.
+    parts: Vec<PartPlan>,
+    lines: Vec<(LineItem, SimplePart)>,
+    /// Lines added by hand. Kept when overwriting or up-rev'ing.
+    manual: Vec<ManualLine>,
+    /// Lines with parts that aren't active and manual lines of parts in the schematic.
+    /// These don't stop the import.
+    warnings: Vec<String>,
+}
 
-    table.add_row(row![
-        "QUANTITY",
-        "REFDES",
-        "PN",
-        "MPN",
-        "DESC",
-        "VER",
-        "INVENTORY QTY",
-        "NO STUFF"
-    ]);
-    for entry in results {
-        let details = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to get details!");
+/// A line added by hand to the current version of a BOM
+#[derive(Debug)]
+struct ManualLine {
+    line: models::PartsPart,
+    pn: String,
+    designators: Vec<(String, Option<String>)>,
+}
 
-        // Get inventory info
-        let inventory = inventories::dsl::inventories
-            .filter(inventories::dsl::part_id.eq(entry.part_id))
-            .load::<models::Inventory>(&app.conn)
-            .expect("Error loading parts");
+/// Everything an import does, worked out before anything is written
+#[derive(Debug, Default)]
+struct ImportPlan {
+    boms: Vec<BomPlan>,
+    errors: Vec<String>,
+}
 
-        let mut inventory_qty = 0;
+/// Works out what importing a schematic does along with anything that would stop it
+fn plan_import(
+    conn: &SqliteConnection,
+    config: &config::Config,
+    source: &dyn SchematicSource,
+    selection: &VariantSelection,
+) -> ImportPlan {
+    let mut plan = ImportPlan::default();
 
-        // Tally inventory
-        for item in inventory {
-            // Increment quantity
-            inventory_qty += item.quantity;
+    let (bom_pn, bom_desc) = match get_bom_pn_and_desc(&source.attributes()) {
+        Ok(x) => x,
+        Err(e) => {
+            plan.errors.push(e.to_string());
+            return plan;
         }
+    };
 
-        table.add_row(row![
-            entry.quantity,
-            entry.refdes,
-            details.pn,
-            details.mpn,
-            details.descr,
-            details.ver,
-            inventory_qty,
-            entry.nostuff,
-        ]);
-    }
-    table.printstd();
-}
+    // Get the variant(s) to import
+    let variants = match select_variants(&source.variants(), selection, &bom_pn) {
+        Ok(v) => v,
+        Err(e) => {
+            plan.errors.push(e.to_string());
+            return plan;
+        }
+    };
 
-/// Function used to export BOM to CSV
-pub fn export(app: &mut crate::Application, part_number: &str, version: &Option<i32>) {
-    use crate::schema::*;
+    let parts = source.parts();
 
-    // Find the part
-    let part = find_part_by_pn(&app.conn, &part_number);
+    plan.errors.extend(find_invalid_mqty(&parts));
 
-    if part.is_err() {
-        println!("{} was not found!", part_number);
-        std::process::exit(1);
-    }
+    // Each variant is its own BOM
+    for (variant, variant_pn) in variants {
+        println!("Variant: {} BOM: {}", variant.name, variant_pn);
 
-    // Transform the response into a Part
-    let part = part.unwrap();
+        let items = resolve_items(conn, config, source, &parts, &variant);
 
-    // Then either use the provided version or the latest
-    let ver = match version {
-        Some(x) => x,
-        None => &part.ver,
-    };
+        // Show where everything came from
+        let parts: Vec<SimplePart> = items.iter().map(|(_, p)| p.clone()).collect();
+        print_library_report(&parts);
 
-    // Get all the parts related to this BOM
-    let mut results = parts_parts::dsl::parts_parts
-        .filter(parts_parts::dsl::bom_part_id.eq(part.id))
-        .filter(parts_parts::dsl::bom_ver.eq(ver))
-        .load::<models::PartsPart>(&app.conn)
-        .expect("Error loading parts");
+        let errors = validate_parts(conn, &parts)
+            .into_iter()
+            .chain(find_conflicting_overrides(&items))
+            .chain(find_conflicting_values(&items));
+        for error in errors {
+            if !plan.errors.contains(&error) {
+                plan.errors.push(error);
+            }
+        }
 
-    // Sort the results by refdes
-    results.sort_by(|a, b| {
-        let first = a.refdes.chars().next().unwrap();
-        let second = b.refdes.chars().next().unwrap();
-        first.cmp(&second)
-    });
+        // Variant BOMs get the variant name in the description
+        let descr = if variant_pn == bom_pn {
+            bom_desc.clone()
+        } else {
+            format!("{} ({})", bom_desc, variant.name)
+        };
 
-    // Create filename
-    let filename = format!("{}-v{}-{}.csv", part_number, ver, Utc::now().to_rfc3339());
+        let (action, manual) = match find_part_by_pn(conn, &variant_pn) {
+            Ok(bom) => (BomAction::Undecided(bom.ver), find_manual_lines(conn, &bom)),
+            Err(_) => (BomAction::Create, Vec::new()),
+        };
 
-    // File operations
-    let file = File::create(&filename).unwrap();
-    let file = BufWriter::new(file);
+        // Manual lines are kept even if the schematic has the part now. Only the user knows
+        // if they're extras or if the schematic replaces them.
+        let mut warnings: Vec<String> = manual
+            .iter()
+            .filter(|manual| {
+                items
+                    .iter()
+                    .any(|(_, part)| part.alias.as_ref().unwrap_or(&part.pn) == &manual.pn)
+            })
+            .map(|manual| {
+                format!(
+                    "{} is in the schematic and on a manual line ({}x). Both are kept.",
+                    manual.pn, manual.line.quantity
+                )
+            })
+            .collect();
 
-    // Create CSV writer
-    let mut wtr = csv::Writer::from_writer(file);
+        // Aliases point at an existing part instead of creating one
+        let mut part_plans: Vec<PartPlan> = Vec::new();
+        for part in parts.into_iter().filter(|p| p.alias.is_none()) {
+            if part_plans.iter().all(|p| p.part.pn != part.pn) {
+                part_plans.push(plan_part(conn, part));
+            }
+        }
 
-    for entry in results {
-        let details = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to get details!");
+        // Only parts already in the database have a lifecycle
+        warnings.extend(
+            items
+                .iter()
+                .filter(|(item, _)| item.nostuff == 0)
+                .filter_map(|(item, part)| {
+                    let existing =
+                        find_part_by_pn(conn, part.alias.as_ref().unwrap_or(&part.pn)).ok()?;
+                    lifecycle_status(&existing)
+                        .map(|status| format!("{} is {} ({})", existing.pn, status, item.name))
+                }),
+        );
 
-        // Get inventory info
-        let inventory = inventories::dsl::inventories
-            .filter(inventories::dsl::part_id.eq(entry.part_id))
-            .load::<models::Inventory>(&app.conn)
-            .expect("Error loading parts");
+        plan.boms.push(BomPlan {
+            pn: variant_pn,
+            descr,
+            action,
+            parts: part_plans,
+            lines: items,
+            manual,
+            warnings,
+        });
+    }
 
-        let mut inventory_qty = 0;
+    plan
+}
 
-        // Tally inventory
-        for item in inventory {
-            // Increment quantity
-            inventory_qty += item.quantity;
+/// Gets the manual lines of the current version of a BOM
+fn find_manual_lines(conn: &SqliteConnection, bom: &models::Part) -> Vec<ManualLine> {
+    let lines = find_bom_list_by_id_and_ver(conn, &bom.id, &bom.ver).unwrap_or_default();
+
+    lines
+        .into_iter()
+        .filter(|line| line.manual == 1)
+        .filter_map(|line| {
+            let part = find_part_by_id(conn, &line.part_id).ok()?;
+            let designators = find_refdes_by_line_id(conn, &line.id)
+                .ok()?
+                .into_iter()
+                .map(|r| (r.refdes, r.val))
+                .collect();
+
+            Some(ManualLine {
+                line,
+                pn: part.pn,
+                designators,
+            })
+        })
+        .collect()
+}
+
+/// Everything wrong with a list of parts that would stop them from being imported
+fn validate_parts(conn: &SqliteConnection, list: &[SimplePart]) -> Vec<String> {
+    use crate::schema::parts;
+
+    let mut errors = Vec::new();
+
+    // Aliases use MQTY too
+    for part in list {
+        if let Some(mqty) = &part.invalid_mqty {
+            errors.push(format!(
+                "{} has an invalid MQTY in {}: {}",
+                part.pn,
+                part.library.as_deref().unwrap_or_default(),
+                mqty
+            ));
         }
+    }
 
-        // Then pop it into a serializeable struct
-        let line = BomEntry {
-            quantity: entry.quantity,
-            refdes: entry.refdes,
-            pn: details.pn,
-            mpn: details.mpn,
-            descr: details.descr,
-            ver: details.ver,
-            inventory_qty,
-            no_stuff: entry.nostuff,
+    for part in list.iter().filter(|p| p.alias.is_none()) {
+        // Parts with an MPN on the instance don't need a library
+        if part.mpn.is_empty() {
+            match &part.library {
+                Some(library) => errors.push(format!(
+                    "Manufacturer part number must be set for {} in {}",
+                    part.pn, library
+                )),
+                None => errors.push(format!("Unable to find {} in any library", part.pn)),
+            }
+            continue;
+        }
+
+        // MPNs are unique for each manufacturer. A new manufacturer can't clash with anything.
+        let manufacturer_id = match &part.manufacturer {
+            Some(name) => find_manufacturer_by_name(conn, name)
+                .ok()
+                .map(|m| Some(m.id)),
+            None => Some(
+                find_part_by_pn(conn, &part.pn)
+                    .ok()
+                    .and_then(|p| p.manufacturer_id),
+            ),
         };
 
-        wtr.serialize(line).expect("Unable to serialize.");
-        wtr.flush().expect("Unable to flush");
+        if let Some(manufacturer_id) = manufacturer_id {
+            let existing = parts::dsl::parts
+                .filter(parts::dsl::mpn.eq(&part.mpn))
+                .filter(parts::dsl::pn.ne(&part.pn))
+                .load::<models::Part>(conn)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|p| p.manufacturer_id == manufacturer_id);
+
+            if let Some(existing) = existing {
+                errors.push(format!(
+                    "{} has MPN {} which is already used by {}",
+                    part.pn, part.mpn, existing.pn
+                ));
+            }
+        }
+
+        // Including within the schematic
+        let other = list.iter().find(|p| {
+            p.alias.is_none()
+                && p.mpn == part.mpn
+                && p.pn != part.pn
+                && same_manufacturer(&p.manufacturer, &part.manufacturer)
+        });
+
+        if let Some(other) = other {
+            let (first, second) = if other.pn < part.pn {
+                (&other.pn, &part.pn)
+            } else {
+                (&part.pn, &other.pn)
+            };
+
+            let error = format!("{} and {} have the same MPN {}", first, second, part.mpn);
+
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
     }
 
-    println!("Inventory list exported to {}", filename);
+    // KiCad libraries can have symbols with the same name
+    for part in list {
+        let other = list
+            .iter()
+            .find(|p| p.pn == part.pn && p.library != part.library);
+
+        if let Some(other) = other {
+            let mut libraries = [
+                part.library.clone().unwrap_or_default(),
+                other.library.clone().unwrap_or_default(),
+            ];
+            libraries.sort();
+
+            let error = format!(
+                "{} is in more than one library ({} and {})",
+                part.pn, libraries[0], libraries[1]
+            );
+
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    }
+
+    // Aliases must point at something that exists
+    for target in find_missing_alias_targets(conn, list) {
+        errors.push(format!(
+            "Alias target {} not found! Create it first.",
+            target
+        ));
+    }
+
+    errors
 }
 
-// pub fn delete(part_number: &str, version: &i32) {
-//   // TODO: confirm exists
-//   // TODO: confirm delete
-//   // TODO delete query
-// }
+/// Manufacturer names are compared without case
+fn same_manufacturer(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Compares a part with what's in the database
+fn plan_part(conn: &SqliteConnection, mut part: SimplePart) -> PartPlan {
+    let existing = match find_part_by_pn(conn, &part.pn) {
+        Ok(x) => x,
+        Err(_) => {
+            return PartPlan {
+                part,
+                action: PartAction::Create,
+            }
+        }
+    };
+
+    // MQTY only comes from a library
+    if part.library.is_none() {
+        part.mqty = existing.mqty;
+    }
+
+    let mut changes = Vec::new();
+
+    if existing.mpn != part.mpn {
+        changes.push(("mpn", existing.mpn, part.mpn.clone()));
+    }
+
+    if existing.descr != part.descr {
+        changes.push(("descr", existing.descr, part.descr.clone()));
+    }
+
+    if existing.mqty != part.mqty {
+        changes.push(("mqty", existing.mqty.to_string(), part.mqty.to_string()));
+    }
+
+    // A part without a manufacturer keeps the one in the database
+    if let Some(manufacturer) = &part.manufacturer {
+        let current = find_manufacturer_name(conn, &existing.manufacturer_id).unwrap_or_default();
+
+        if !current.eq_ignore_ascii_case(manufacturer) {
+            changes.push(("manufacturer", current, manufacturer.clone()));
+        }
+    }
+
+    // A part without a value keeps the one in the database
+    if part.val.is_some() && existing.val != part.val {
+        changes.push((
+            "val",
+            existing.val.unwrap_or_default(),
+            part.val.clone().unwrap_or_default(),
+        ));
+    }
+
+    // Supplier PNs and attributes the library doesn't have are left alone
+    let suppliers: Vec<(String, String)> = find_part_suppliers_by_part_id(conn, &existing.id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|s| {
+            Some((
+                find_supplier_by_id(conn, &s.supplier_id).ok()?.name,
+                s.supplier_pn,
+            ))
+        })
+        .collect();
+    changes.extend(diff_pairs("supplier", &suppliers, &part.suppliers));
+
+    let attributes: Vec<(String, String)> = find_part_attributes_by_part_id(conn, &existing.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|a| (a.name, a.value))
+        .collect();
+    changes.extend(diff_pairs("attribute", &attributes, &part.attributes));
+
+    // The version belongs to the part, not the schematic
+    part.ver = existing.ver;
+
+    let action = if changes.is_empty() {
+        PartAction::Unchanged
+    } else {
+        PartAction::Update(changes)
+    };
+
+    PartPlan { part, action }
+}
+
+/// Changes to (name, value) pairs. i.e. `supplier: DIGIKEY 311-10K → DIGIKEY 311-10KLR`
+fn diff_pairs(
+    field: &'static str,
+    current: &[(String, String)],
+    new: &[(String, String)],
+) -> Vec<(&'static str, String, String)> {
+    new.iter()
+        .filter_map(|(name, value)| {
+            let from = current.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+
+            if from == Some(value) {
+                return None;
+            }
+
+            let from = from.map(|v| format!("{} {}", name, v)).unwrap_or_default();
+            Some((field, from, format!("{} {}", name, value)))
+        })
+        .collect()
+}
+
+/// Lists part changes as `field: from → to`
+fn describe_changes(changes: &[(&str, String, String)], separator: &str) -> String {
+    let changes: Vec<String> = changes
+        .iter()
+        .map(|(field, from, to)| format!("{}: {} → {}", field, from, to))
+        .collect();
+
+    changes.join(separator)
+}
+
+/// Prints what an import is going to do
+fn print_plan(plan: &ImportPlan) {
+    for bom in &plan.boms {
+        let action = match bom.action {
+            BomAction::Create => "new BOM".to_string(),
+            BomAction::Undecided(ver) => format!(
+                "exists at version {}. Up-rev to {} or overwrite",
+                ver,
+                ver + 1
+            ),
+            BomAction::Overwrite(ver) => format!("overwrite version {}", ver),
+            BomAction::Revise(ver) => format!("new version {}", ver),
+            BomAction::Skip => "skip".to_string(),
+        };
+
+        println!("\nBOM: {} ({}) {}", bom.pn, bom.descr, action);
+
+        // Parts
+        let mut table = Table::new();
+        table.add_row(row![
+            "ACTION",
+            "PN",
+            "MPN",
+            "MANUFACTURER",
+            "DESC",
+            "VAL",
+            "MQTY",
+            "CHANGES"
+        ]);
+
+        for plan in &bom.parts {
+            let (action, changes) = match &plan.action {
+                PartAction::Create => ("create", String::new()),
+                PartAction::Unchanged => ("unchanged", String::new()),
+                PartAction::Update(changes) => ("update", describe_changes(changes, "\n")),
+                PartAction::Keep => ("keep", String::new()),
+            };
+
+            table.add_row(row![
+                action,
+                plan.part.pn,
+                plan.part.mpn,
+                plan.part.manufacturer.as_deref().unwrap_or_default(),
+                plan.part.descr,
+                plan.part.val.as_deref().unwrap_or_default(),
+                plan.part.mqty,
+                changes
+            ]);
+        }
+
+        table.printstd();
+
+        // Lines
+        let mut table = Table::new();
+        table.add_row(row!["QUANTITY", "REFDES", "PN", "NO STUFF", "ALIAS"]);
+
+        for (item, part) in &bom.lines {
+            let (pn, alias) = match &part.alias {
+                Some(target) => (target.clone(), format!("{} → {}×", part.pn, part.mqty)),
+                None => (part.pn.clone(), "".to_string()),
+            };
+
+            table.add_row(row![item.quantity, item.name, pn, item.nostuff, alias]);
+        }
+
+        // Only kept if the BOM is overwritten or up-rev'd
+        if !matches!(bom.action, BomAction::Create | BomAction::Skip) {
+            for manual in &bom.manual {
+                let pn = format!("{} (manual)", manual.pn);
+                let line = &manual.line;
+                table.add_row(row![line.quantity, line.refdes, pn, line.nostuff, ""]);
+            }
+        }
+
+        table.printstd();
+
+        for warning in &bom.warnings {
+            println!("Warning: {}", warning);
+        }
+    }
+}
+
+/// Decides what the policy can. Anything it doesn't allow is an error.
+fn apply_policy(plan: &mut ImportPlan, policy: &ImportPolicy) {
+    for bom in &mut plan.boms {
+        if let BomAction::Undecided(ver) = bom.action {
+            match policy.on_existing_bom {
+                OnExistingBom::Ask => {}
+                OnExistingBom::Uprev => bom.action = BomAction::Revise(ver + 1),
+                OnExistingBom::Overwrite => bom.action = BomAction::Overwrite(ver),
+                OnExistingBom::Abort => plan
+                    .errors
+                    .push(format!("BOM {} already exists at version {}", bom.pn, ver)),
+            }
+        }
+
+        for part in &mut bom.parts {
+            if let PartAction::Update(changes) = &part.action {
+                match policy.on_part_change {
+                    OnPartChange::Ask | OnPartChange::Update => {}
+                    OnPartChange::Keep => part.action = PartAction::Keep,
+                    OnPartChange::Fail => {
+                        let error = format!(
+                            "{} is different in the database ({})",
+                            part.part.pn,
+                            describe_changes(changes, ", ")
+                        );
+
+                        if !plan.errors.contains(&error) {
+                            plan.errors.push(error);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Asks about anything in the plan that hasn't been decided yet
+fn confirm_plan(app: &mut crate::Application, plan: &mut ImportPlan, policy: &ImportPolicy) {
+    // Parts can be in more than one BOM. Only ask once.
+    let mut answers: HashMap<String, bool> = HashMap::new();
+
+    for bom in &mut plan.boms {
+        if let BomAction::Undecided(ver) = bom.action {
+            let question = format!("BOM {} found! Would you like to update it?", bom.pn);
+
+            bom.action = if !app.prompt.ask_yes_no_question(&question) {
+                BomAction::Skip
+            } else {
+                let question =
+                    format!("BOM {} found! Would you like to up-rev the design?", bom.pn);
+
+                if app.prompt.ask_yes_no_question(&question) {
+                    BomAction::Revise(ver + 1)
+                } else {
+                    BomAction::Overwrite(ver)
+                }
+            };
+        }
+
+        // Nothing else matters if this one is skipped
+        if bom.action == BomAction::Skip {
+            continue;
+        }
+
+        // Updates are already decided otherwise
+        if policy.on_part_change != OnPartChange::Ask {
+            continue;
+        }
+
+        for part in &mut bom.parts {
+            if let PartAction::Update(_) = part.action {
+                let update = match answers.get(&part.part.pn) {
+                    Some(x) => *x,
+                    None => {
+                        let question =
+                            format!("{} found! Would you like to update it?", part.part.pn);
+                        let answer = app.prompt.ask_yes_no_question(&question);
+                        answers.insert(part.part.pn.clone(), answer);
+                        answer
+                    }
+                };
+
+                if !update {
+                    part.action = PartAction::Keep;
+                }
+            }
+        }
+    }
+}
+
+/// Writes everything in the plan. All or nothing.
+fn apply_plan(
+    conn: &SqliteConnection,
+    plan: &ImportPlan,
+) -> std::result::Result<(), diesel::result::Error> {
+    conn.transaction(|| {
+        // Parts shared between BOMs are only written once
+        let mut written: Vec<&str> = Vec::new();
+
+        for bom in &plan.boms {
+            // Substitutions of an overwritten version stay with their part
+            let mut substitutions: Vec<(i32, models::BomSubstitution)> = Vec::new();
+
+            let revision = match bom.action {
+                BomAction::Create => {
+                    // Create new BOM part
+                    let part = models::NewUpdatePart {
+                        pn: &bom.pn,
+                        mpn: &bom.pn,
+                        descr: &bom.descr,
+                        ver: &1,
+                        val: None,
+                        mqty: &1,
+                        manufacturer_id: None,
+                    };
+
+                    create_part(conn, &part)?;
+                    1
+                }
+                BomAction::Revise(revision) => {
+                    use crate::schema::parts::dsl::*;
+
+                    // Save the revision
+                    diesel::update(parts)
+                        .set(ver.eq(revision))
+                        .filter(pn.eq(&bom.pn))
+                        .execute(conn)?;
+                    revision
+                }
+                BomAction::Overwrite(revision) => {
+                    // Remove all previous BOM entries.
+                    let existing = find_part_by_pn(conn, &bom.pn)?;
+                    for line in find_bom_list_by_id_and_ver(conn, &existing.id, &revision)? {
+                        for substitution in find_bom_substitutions_by_line_id(conn, &line.id)? {
+                            substitutions.push((line.part_id, substitution));
+                        }
+                    }
+                    delete_bom_list_by_id_and_ver(conn, &existing.id, &revision)?;
+                    revision
+                }
+                BomAction::Undecided(_) | BomAction::Skip => continue,
+            };
+
+            let bom_part = find_part_by_pn(conn, &bom.pn)?;
+
+            for plan in &bom.parts {
+                if written.contains(&plan.part.pn.as_str()) {
+                    continue;
+                }
+                written.push(&plan.part.pn);
+
+                let part = &plan.part;
+
+                // Only parts that get written need the manufacturer
+                let manufacturer_id = match (&part.manufacturer, &plan.action) {
+                    (Some(name), PartAction::Create | PartAction::Update(_)) => {
+                        Some(find_or_create_manufacturer(conn, name)?.id)
+                    }
+                    _ => None,
+                };
+
+                // Create update object
+                let npart = models::NewUpdatePart {
+                    pn: &part.pn,
+                    mpn: &part.mpn,
+                    descr: &part.descr,
+                    ver: &part.ver,
+                    val: part.val.as_deref(),
+                    mqty: &part.mqty,
+                    manufacturer_id: manufacturer_id.as_ref(),
+                };
+
+                match plan.action {
+                    PartAction::Create => {
+                        println!("Creating: {:?}", npart);
+                        create_part(conn, &npart)?;
+                    }
+                    PartAction::Update(_) => {
+                        let existing = find_part_by_pn(conn, &part.pn)?;
+                        update_part(conn, &existing.id, &npart)?;
+                    }
+                    PartAction::Unchanged | PartAction::Keep => continue,
+                }
+
+                // Save the supplier part numbers
+                let line_item = find_part_by_pn(conn, &part.pn)?;
+                for (supplier, supplier_pn) in &part.suppliers {
+                    set_part_supplier_pn(conn, &line_item.id, supplier, supplier_pn)?;
+                }
+
+                // And the parametric attributes
+                for (name, value) in &part.attributes {
+                    set_part_attribute(conn, &line_item.id, name, value)?;
+                }
+            }
+
+            // Create BOM association between the part and the
+            // BOM info like QTY, REFDES, NOSTUFF
+            for (item, part) in &bom.lines {
+                let (line_item, alias) = match &part.alias {
+                    Some(target) => {
+                        let target = find_part_by_pn(conn, target)?;
+                        let alias = write_alias(conn, part, &target)?;
+                        (target, Some(alias))
+                    }
+                    None => (find_part_by_pn(conn, &part.pn)?, None),
+                };
+
+                let relationship = models::NewPartsParts {
+                    quantity: &item.quantity,
+                    bom_ver: &revision,
+                    refdes: &item.name,
+                    nostuff: &item.nostuff,
+                    bom_part_id: &bom_part.id,
+                    part_id: &line_item.id,
+                    alias_id: alias.as_ref().map(|a| &a.id),
+                    manual: &0,
+                };
+
+                // Push them to the DB
+                create_bom_line_with_refdes(conn, &relationship, &item.designators)?;
+            }
+
+            // Lines added by hand stay
+            for manual in &bom.manual {
+                let line = &manual.line;
+                let relationship = models::NewPartsParts {
+                    quantity: &line.quantity,
+                    bom_ver: &revision,
+                    refdes: &line.refdes,
+                    nostuff: &line.nostuff,
+                    bom_part_id: &bom_part.id,
+                    part_id: &line.part_id,
+                    alias_id: line.alias_id.as_ref(),
+                    manual: &1,
+                };
+
+                create_bom_line_with_refdes(conn, &relationship, &manual.designators)?;
+            }
+
+            // Dropped if the part isn't on the BOM anymore
+            let lines = find_bom_list_by_id_and_ver(conn, &bom_part.id, &revision)?;
+            let mut moved: Vec<(i32, i32, Option<i32>)> = Vec::new();
+
+            for (part_id, substitution) in &substitutions {
+                if let Some(line) = lines.iter().find(|l| l.part_id == *part_id) {
+                    // A part on more than one line only needs it once
+                    let key = (line.id, substitution.part_id, substitution.build_id);
+                    if moved.contains(&key) {
+                        continue;
+                    }
+                    moved.push(key);
+
+                    let substitution = models::NewBomSubstitution {
+                        expires: substitution.expires.as_ref(),
+                        line_id: &line.id,
+                        part_id: &substitution.part_id,
+                        build_id: substitution.build_id.as_ref(),
+                    };
+
+                    create_bom_substitution(conn, &substitution)?;
+                }
+            }
+
+            println!("Saved {} version {}", bom.pn, revision);
+        }
+
+        Ok(())
+    })
+}
+
+/// Creates or updates the alias for a library part
+fn write_alias(
+    conn: &SqliteConnection,
+    part: &SimplePart,
+    target: &models::Part,
+) -> std::result::Result<models::Alias, diesel::result::Error> {
+    let alias = models::NewUpdateAlias {
+        pn: &part.pn,
+        quantity: &part.mqty,
+        part_id: &target.id,
+    };
+
+    // Create or update the alias
+    match find_alias_by_pn(conn, &part.pn) {
+        Ok(existing) => update_alias(conn, &existing.id, &alias)?,
+        Err(_) => create_alias(conn, &alias)?,
+    };
+
+    let alias = find_alias_by_pn(conn, &part.pn)?;
+
+    println!("Alias: {} -> {}x {}", alias.pn, alias.quantity, target.pn);
+
+    Ok(alias)
+}
+
+/// Builds the line items of a variant and looks them up in the libraries
+fn resolve_items(
+    conn: &SqliteConnection,
+    config: &config::Config,
+    source: &dyn SchematicSource,
+    parts: &[schematic::Part],
+    variant: &VariantDef,
+) -> Vec<(LineItem, SimplePart)> {
+    let list = get_line_items_from_parts(parts, variant, &config.part_number_ignore_list);
+
+    // Get MPN, DigikeyPn from Library exerpts
+    let libraries = config.libraries();
+    list.into_iter()
+        .map(|mut item| {
+            let mut part =
+                get_simplepart_from_library(&item, source, &libraries, &config.suppliers);
+            apply_stored_alias(conn, &mut part);
+
+            // Each placement counts for MQTY parts
+            item.quantity *= item.mqty.unwrap_or(part.mqty);
+
+            (item, part)
+        })
+        .collect()
+}
+
+/// Part numbers that were merged into another part are aliases of it now
+fn apply_stored_alias(conn: &SqliteConnection, part: &mut SimplePart) {
+    if part.alias.is_some() || find_part_by_pn(conn, &part.pn).is_ok() {
+        return;
+    }
+
+    let target = find_alias_by_pn(conn, &part.pn)
+        .and_then(|alias| Ok((find_part_by_id(conn, &alias.part_id)?, alias.quantity)));
+
+    if let Ok((target, quantity)) = target {
+        part.alias = Some(target.pn);
+        part.mqty = quantity;
+    }
+}
+
+/// A BOM line as it is (or would be) in the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct BomLine {
+    pub pn: String,
+    pub quantity: i32,
+    pub refdes: String,
+    pub nostuff: i32,
+}
+
+/// Gets the lines of a BOM version from the database
+pub fn lines_from_db(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+) -> std::result::Result<Vec<BomLine>, diesel::result::Error> {
+    let mut lines = Vec::new();
+
+    for entry in find_bom_list_by_id_and_ver(conn, bom_part_id, bom_ver)? {
+        let part = find_part_by_id(conn, &entry.part_id)?;
+
+        lines.push(BomLine {
+            pn: part.pn,
+            quantity: entry.quantity,
+            refdes: entry.refdes,
+            nostuff: entry.nostuff,
+        });
+    }
+
+    Ok(lines)
+}
+
+/// Gets the lines of a BOM version that were added by hand
+pub fn manual_lines_from_db(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+) -> std::result::Result<Vec<BomLine>, diesel::result::Error> {
+    let mut lines = Vec::new();
+
+    for entry in find_bom_list_by_id_and_ver(conn, bom_part_id, bom_ver)? {
+        if entry.manual == 0 {
+            continue;
+        }
+
+        let part = find_part_by_id(conn, &entry.part_id)?;
+
+        lines.push(BomLine {
+            pn: part.pn,
+            quantity: entry.quantity,
+            refdes: entry.refdes,
+            nostuff: entry.nostuff,
+        });
+    }
+
+    Ok(lines)
+}
+
+/// Gets the lines a schematic would import as, along with the BOM part number.
+/// Aliases are resolved to their target like they are on import.
+pub fn lines_from_source(
+    app: &crate::Application,
+    source: &dyn SchematicSource,
+    selection: &VariantSelection,
+) -> anyhow::Result<(String, Vec<BomLine>)> {
+    let (bom_pn, _) = get_bom_pn_and_desc(&source.attributes())?;
+
+    // Only one variant can be compared at a time
+    let (variant, variant_pn) = match select_variants(&source.variants(), selection, &bom_pn)?
+        .into_iter()
+        .next()
+    {
+        Some(x) => x,
+        None => return Err(anyhow::anyhow!("no variants found!")),
+    };
+
+    let parts = source.parts();
+    if let Some(error) = find_invalid_mqty(&parts).into_iter().next() {
+        return Err(anyhow::anyhow!(error));
+    }
+
+    let items = resolve_items(&app.conn, &app.config, source, &parts, &variant);
+    if let Some((_, part)) = items.iter().find(|(_, p)| p.invalid_mqty.is_some()) {
+        return Err(anyhow::anyhow!(
+            "{} has an invalid MQTY: {}",
+            part.pn,
+            part.invalid_mqty.as_deref().unwrap_or_default()
+        ));
+    }
+
+    let lines = items
+        .into_iter()
+        .map(|(item, part)| BomLine {
+            pn: part.alias.unwrap_or(part.pn),
+            quantity: item.quantity,
+            refdes: item.name,
+            nostuff: item.nostuff,
+        })
+        .collect();
+
+    Ok((variant_pn, lines))
+}
+
+/// A line of a multi-level BOM
+pub struct ExplodedLine {
+    /// 0 for lines of the top level BOM
+    pub level: usize,
+    pub entry: models::PartsPart,
+    pub part: models::Part,
+    /// Quantity needed for one of the top level assembly
+    pub total: i32,
+    /// Set if the lines that follow are this part's own BOM
+    pub exploded: bool,
+}
+
+/// Walks a BOM and every sub-assembly BOM (at their latest version that isn't archived) depth first.
+/// No stuff lines are listed but not walked. A sub-assembly that contains itself is only walked once.
+pub fn explode(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+) -> std::result::Result<Vec<ExplodedLine>, diesel::result::Error> {
+    let mut lines = Vec::new();
+    let mut path = vec![*bom_part_id];
+
+    explode_into(conn, bom_part_id, bom_ver, 1, &mut path, &mut lines)?;
+
+    Ok(lines)
+}
+
+fn explode_into(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+    multiplier: i32,
+    path: &mut Vec<i32>,
+    lines: &mut Vec<ExplodedLine>,
+) -> std::result::Result<(), diesel::result::Error> {
+    let mut entries = find_bom_list_by_id_and_ver(conn, bom_part_id, bom_ver)?;
+    entries.sort_by(|a, b| a.refdes.cmp(&b.refdes));
+
+    for entry in entries {
+        let part = find_part_by_id(conn, &entry.part_id)?;
+        let total = entry.quantity * multiplier;
+        let assembly_ver = assembly_version(conn, &part)?
+            .filter(|_| entry.nostuff == 0 && !path.contains(&part.id));
+
+        let part_id = part.id;
+
+        lines.push(ExplodedLine {
+            level: path.len() - 1,
+            entry,
+            part,
+            total,
+            exploded: assembly_ver.is_some(),
+        });
+
+        if let Some(assembly_ver) = assembly_ver {
+            path.push(part_id);
+            explode_into(conn, &part_id, &assembly_ver, total, path, lines)?;
+            path.pop();
+        }
+    }
+
+    Ok(())
+}
+
+/// The version a sub-assembly is built at. The same one `build create` uses.
+/// None if the part has no BOM that isn't archived.
+fn assembly_version(
+    conn: &SqliteConnection,
+    part: &models::Part,
+) -> std::result::Result<Option<i32>, diesel::result::Error> {
+    let ver = active_version(conn, part)?;
+    let lines = find_bom_list_by_id_and_ver(conn, &part.id, &ver)?;
+
+    Ok(Some(ver).filter(|_| lines.iter().any(|l| l.archived == 0)))
+}
+
+/// Describes a part that isn't active or is archived. i.e. `obsolete (last time buy 2026-01-31)`
+pub fn lifecycle_status(part: &models::Part) -> Option<String> {
+    if part.archived == 1 {
+        return Some("archived".to_string());
+    }
+
+    let lifecycle = part.lifecycle();
+
+    if lifecycle == models::Lifecycle::Active {
+        return None;
+    }
+
+    Some(match part.last_time_buy {
+        Some(date) => format!("{} (last time buy {})", lifecycle, date),
+        None => lifecycle.to_string(),
+    })
+}
+
+/// Lists the lines of a BOM and its sub-assemblies with parts that aren't active.
+/// No stuff lines aren't built so they're skipped.
+pub fn lifecycle_warnings(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+) -> std::result::Result<Vec<String>, diesel::result::Error> {
+    let warnings = explode(conn, bom_part_id, bom_ver)?
+        .iter()
+        .filter(|line| line.entry.nostuff == 0)
+        .filter_map(|line| {
+            lifecycle_status(&line.part)
+                .map(|status| format!("{} is {} ({})", line.part.pn, status, line.entry.refdes))
+        })
+        .collect();
+
+    Ok(warnings)
+}
+
+/// Prints a warning for each line with a part that isn't active
+pub fn print_lifecycle_warnings(conn: &SqliteConnection, bom_part_id: &i32, bom_ver: &i32) {
+    let warnings =
+        lifecycle_warnings(conn, bom_part_id, bom_ver).expect("Unable to check lifecycles!");
+
+    for warning in warnings {
+        println!("Warning: {}", warning);
+    }
+}
+
+/// A BOM version that uses a part, directly or through sub-assemblies
+pub struct Usage {
+    /// 0 for BOMs that use the part directly. Higher levels use the one above.
+    pub level: usize,
+    pub entry: models::PartsPart,
+    pub bom: models::Part,
+    /// Builds in progress of this BOM version
+    pub open_builds: Vec<models::Build>,
+    /// Not used by any other BOM
+    pub top_level: bool,
+}
+
+/// Finds every BOM version that uses a part and walks up through the sub-assemblies to the
+/// top-level products. Archived and no stuff lines are shown but not walked up from.
+pub fn where_used(
+    conn: &SqliteConnection,
+    part_id: &i32,
+) -> std::result::Result<Vec<Usage>, diesel::result::Error> {
+    let mut usages = Vec::new();
+    let mut path = vec![*part_id];
+
+    where_used_into(conn, part_id, 0, &mut path, &mut usages)?;
+
+    Ok(usages)
+}
+
+fn where_used_into(
+    conn: &SqliteConnection,
+    part_id: &i32,
+    level: usize,
+    path: &mut Vec<i32>,
+    usages: &mut Vec<Usage>,
+) -> std::result::Result<(), diesel::result::Error> {
+    let entries = find_bom_lines_by_part_id(conn, part_id)?;
+
+    // Versions of a BOM come one after another. Walk up once per BOM after the last one.
+    for (index, entry) in entries.iter().enumerate() {
+        let bom = find_part_by_id(conn, &entry.bom_part_id)?;
+        let open_builds = find_open_builds_by_id_and_ver(conn, &bom.id, &entry.bom_ver)?;
+        let bom_id = bom.id;
+
+        usages.push(Usage {
+            level,
+            entry: entry.clone(),
+            bom,
+            open_builds,
+            top_level: false,
+        });
+
+        let last = entries
+            .get(index + 1)
+            .is_none_or(|next| next.bom_part_id != bom_id);
+
+        if !last || path.contains(&bom_id) {
+            continue;
+        }
+
+        let used = entries
+            .iter()
+            .any(|e| e.bom_part_id == bom_id && e.archived == 0 && e.nostuff == 0);
+
+        let start = usages.len();
+
+        if used {
+            path.push(bom_id);
+            where_used_into(conn, &bom_id, level + 1, path, usages)?;
+            path.pop();
+        }
+
+        // Nothing above it
+        if usages.len() == start {
+            for usage in usages.iter_mut().rev() {
+                if usage.level != level || usage.bom.id != bom_id {
+                    break;
+                }
+                usage.top_level = true;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Something used up by a build
+#[derive(Debug, PartialEq)]
+pub struct Requirement {
+    pub part_id: i32,
+    pub quantity: i32,
+    /// Parts the BOM line allows instead, in the order they're used
+    pub substitutes: Vec<i32>,
+}
+
+/// Works out what gets used to build `quantity` of a BOM. Sub-assemblies come out of `stock`
+/// (part id to quantity on hand, filled in from inventory as needed) first and only the remainder
+/// is exploded into their own BOM. Substitutions for other builds or that have expired are left out.
+pub fn get_requirements(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+    quantity: i32,
+    build_id: Option<&i32>,
+    stock: &mut HashMap<i32, i32>,
+) -> std::result::Result<Vec<Requirement>, diesel::result::Error> {
+    let mut requirements: Vec<Requirement> = Vec::new();
+
+    let mut context = RequirementContext {
+        build_id,
+        today: chrono::Local::now().naive_local().date(),
+        path: vec![*bom_part_id],
+    };
+
+    add_requirements(
+        conn,
+        bom_part_id,
+        bom_ver,
+        quantity,
+        &mut context,
+        stock,
+        &mut requirements,
+    )?;
+
+    Ok(requirements)
+}
+
+/// Where the requirements are being worked out
+struct RequirementContext<'a> {
+    /// Which substitutions can be used
+    build_id: Option<&'a i32>,
+    today: chrono::NaiveDate,
+    /// BOMs above this one. Used to stop loops.
+    path: Vec<i32>,
+}
+
+fn add_requirements(
+    conn: &SqliteConnection,
+    bom_part_id: &i32,
+    bom_ver: &i32,
+    quantity: i32,
+    context: &mut RequirementContext,
+    stock: &mut HashMap<i32, i32>,
+    requirements: &mut Vec<Requirement>,
+) -> std::result::Result<(), diesel::result::Error> {
+    for entry in find_bom_list_by_id_and_ver(conn, bom_part_id, bom_ver)? {
+        // Skip if nostuff is set
+        if entry.nostuff == 1 {
+            continue;
+        }
+
+        let mut needed = entry.quantity * quantity;
+
+        let part = find_part_by_id(conn, &entry.part_id)?;
+        let assembly_ver =
+            assembly_version(conn, &part)?.filter(|_| !context.path.contains(&part.id));
+
+        if let Some(assembly_ver) = assembly_ver {
+            // Use what's already built
+            let on_hand = match stock.get(&part.id) {
+                Some(x) => *x,
+                None => find_inventories_by_part_id(conn, &part.id)?
+                    .iter()
+                    .map(|x| x.quantity)
+                    .sum(),
+            };
+
+            let used = on_hand.min(needed).max(0);
+            stock.insert(part.id, on_hand - used);
+            needed -= used;
+
+            if used > 0 {
+                add_requirement(requirements, part.id, used, Vec::new());
+            }
+
+            // Then build the rest
+            if needed > 0 {
+                context.path.push(part.id);
+                add_requirements(
+                    conn,
+                    &part.id,
+                    &assembly_ver,
+                    needed,
+                    context,
+                    stock,
+                    requirements,
+                )?;
+                context.path.pop();
+            }
+        } else {
+            let substitutes = find_bom_substitutions_by_line_id(conn, &entry.id)?
+                .iter()
+                .filter(|s| s.applies(context.build_id, context.today))
+                .map(|s| s.part_id)
+                .collect();
+
+            add_requirement(requirements, part.id, needed, substitutes);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lines with the same part and substitutes are added up
+fn add_requirement(
+    requirements: &mut Vec<Requirement>,
+    part_id: i32,
+    quantity: i32,
+    substitutes: Vec<i32>,
+) {
+    match requirements
+        .iter_mut()
+        .find(|r| r.part_id == part_id && r.substitutes == substitutes)
+    {
+        Some(r) => r.quantity += quantity,
+        None => requirements.push(Requirement {
+            part_id,
+            quantity,
+            substitutes,
+        }),
+    }
+}
+
+/// Shows a BOM with its sub-assemblies indented underneath
+pub fn show_tree(app: &mut crate::Application, part_number: &str, version: &Option<i32>) {
+    let (part, ver) = find_bom_part(app, part_number, version);
+
+    let lines = explode(&app.conn, &part.id, &ver).expect("Error loading parts");
+
+    println!(
+        "Part Number: {} BOM Id: {} Version: {}",
+        part.pn, part.id, ver
+    );
+
+    // Create the table
+    let mut table = Table::new();
+
+    table.add_row(row![
+        "QUANTITY", "TOTAL", "REFDES", "PN", "MPN", "DESC", "VER", "NO STUFF"
+    ]);
+    for line in lines {
+        let pn = format!("{}{}", "  ".repeat(line.level), line.part.pn);
+
+        table.add_row(row![
+            line.entry.quantity,
+            line.total,
+            line.entry.refdes,
+            pn,
+            line.part.mpn,
+            line.part.descr,
+            line.part.ver,
+            line.entry.nostuff,
+        ]);
+    }
+    table.printstd();
+}
+
+/// Shows the total raw material for a BOM with all sub-assemblies flattened out
+pub fn show_flat(app: &mut crate::Application, part_number: &str, version: &Option<i32>) {
+    let (part, ver) = find_bom_part(app, part_number, version);
+
+    let lines = explode(&app.conn, &part.id, &ver).expect("Error loading parts");
+
+    // Total up everything that's stuffed and isn't built from something else
+    let mut totals: Vec<(models::Part, i32)> = Vec::new();
+
+    for line in lines {
+        // Sub-assemblies are counted by what they're made of
+        if line.entry.nostuff == 1 || line.exploded {
+            continue;
+        }
+
+        match totals.iter_mut().find(|(p, _)| p.id == line.part.id) {
+            Some((_, total)) => *total += line.total,
+            None => totals.push((line.part, line.total)),
+        }
+    }
+
+    totals.sort_by(|a, b| a.0.pn.cmp(&b.0.pn));
+
+    println!("Raw material for Part Number: {} Version: {}", part.pn, ver);
+
+    // Create the table
+    let mut table = Table::new();
+
+    table.add_row(row!["QUANTITY", "PN", "MPN", "DESC", "INVENTORY QTY"]);
+    for (details, total) in totals {
+        let inventory_qty: i32 = find_inventories_by_part_id(&app.conn, &details.id)
+            .expect("Error loading inventory")
+            .iter()
+            .map(|x| x.quantity)
+            .sum();
+
+        table.add_row(row![
+            total,
+            details.pn,
+            details.mpn,
+            details.descr,
+            inventory_qty
+        ]);
+    }
+    table.printstd();
+}
+
+/// Finds a BOM part and the version to use (the latest that isn't archived unless given).
+/// Archived versions can still be given. Exits if every version is archived.
+fn find_bom_part(
+    app: &mut crate::Application,
+    part_number: &str,
+    version: &Option<i32>,
+) -> (models::Part, i32) {
+    let part = match find_part_by_pn(&app.conn, part_number) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("{} was not found!", part_number);
+            std::process::exit(1);
+        }
+    };
+
+    let ver = match version {
+        Some(x) => return (part, *x),
+        None => active_version(&app.conn, &part).expect("Unable to get BOM versions!"),
+    };
+
+    if is_archived(&app.conn, &part.id, &ver).expect("Unable to get BOM versions!") {
+        println!(
+            "Every version of {} is archived! Pick one with --version.",
+            part.pn
+        );
+        std::process::exit(1);
+    }
+
+    (part, ver)
+}
+
+/// Checks if a BOM version has been archived
+pub fn is_archived(
+    conn: &SqliteConnection,
+    part_id: &i32,
+    ver: &i32,
+) -> std::result::Result<bool, diesel::result::Error> {
+    let lines = find_bom_list_by_id_and_ver(conn, part_id, ver)?;
+
+    Ok(!lines.is_empty() && lines.iter().all(|l| l.archived == 1))
+}
+
+/// Latest version of a BOM that hasn't been archived. Falls back to the part version.
+pub fn active_version(
+    conn: &SqliteConnection,
+    part: &models::Part,
+) -> std::result::Result<i32, diesel::result::Error> {
+    use crate::schema::parts_parts::dsl::*;
+
+    let latest: Option<i32> = parts_parts
+        .select(diesel::dsl::max(bom_ver))
+        .filter(bom_part_id.eq(part.id))
+        .filter(archived.eq(0))
+        .first(conn)?;
+
+    Ok(latest.unwrap_or(part.ver))
+}
+
+/// Newest version of a BOM below `below` that hasn't been archived or deleted
+pub fn previous_version(
+    conn: &SqliteConnection,
+    part: &models::Part,
+    below: &i32,
+) -> std::result::Result<Option<i32>, diesel::result::Error> {
+    use crate::schema::parts_parts::dsl::*;
+
+    parts_parts
+        .select(diesel::dsl::max(bom_ver))
+        .filter(bom_part_id.eq(part.id))
+        .filter(archived.eq(0))
+        .filter(bom_ver.lt(below))
+        .first(conn)
+}
+
+/// Function used to show parts in BOM
+pub fn show(app: &mut crate::Application, part_number: &str, version: &Option<i32>) {
+    use crate::schema::*;
+
+    // Find the part and either use the provided version or the latest
+    let (part, ver) = find_bom_part(app, part_number, version);
+
+    // Create the table
+    let mut table = Table::new();
+
+    // Get all the parts related to this BOM
+    let mut results = parts_parts::dsl::parts_parts
+        .filter(parts_parts::dsl::bom_part_id.eq(part.id))
+        .filter(parts_parts::dsl::bom_ver.eq(ver))
+        .load::<models::PartsPart>(&app.conn)
+        .expect("Error loading parts");
+
+    // Sort the results by refdes
+    results.sort_by(|a, b| a.refdes.cmp(&b.refdes));
+
+    println!("Displaying {} parts", results.len());
+
+    println!(
+        "Part Number: {} BOM Id: {} Version: {}",
+        part.pn, part.id, ver
+    );
+
+    table.add_row(row![
+        "QUANTITY",
+        "REFDES",
+        "PN",
+        "MPN",
+        "DESC",
+        "VAL",
+        "VER",
+        "INVENTORY QTY",
+        "NO STUFF",
+        "MANUAL",
+        "SUBSTITUTES"
+    ]);
+    for entry in results {
+        let details = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to get details!");
+        let substitutes = describe_substitutions(&app.conn, &entry.id);
+
+        // Get inventory info
+        let inventory = inventories::dsl::inventories
+            .filter(inventories::dsl::part_id.eq(entry.part_id))
+            .load::<models::Inventory>(&app.conn)
+            .expect("Error loading parts");
+
+        let mut inventory_qty = 0;
+
+        // Tally inventory
+        for item in inventory {
+            // Increment quantity
+            inventory_qty += item.quantity;
+        }
+
+        // Show what the alias stands for
+        let pn = match entry.alias_id {
+            Some(alias_id) => {
+                let alias = find_alias_by_id(&app.conn, &alias_id).expect("Unable to get alias!");
+                format!("{} → {}× {}", alias.pn, alias.quantity, details.pn)
+            }
+            None => details.pn,
+        };
+
+        table.add_row(row![
+            entry.quantity,
+            entry.refdes,
+            pn,
+            details.mpn,
+            details.descr,
+            details.val.unwrap_or_default(),
+            details.ver,
+            inventory_qty,
+            entry.nostuff,
+            entry.manual,
+            substitutes.join("\n"),
+        ]);
+    }
+    table.printstd();
+
+    print_lifecycle_warnings(&app.conn, &part.id, &ver);
+}
+
+/// Substitutions of a BOM line. i.e. `CAP-1U-25V until 2026-12-31 build 4`
+fn describe_substitutions(conn: &SqliteConnection, line_id: &i32) -> Vec<String> {
+    let today = chrono::Local::now().naive_local().date();
+
+    find_bom_substitutions_by_line_id(conn, line_id)
+        .expect("Unable to get substitutions!")
+        .iter()
+        .map(|s| {
+            let part = find_part_by_id(conn, &s.part_id).expect("Unable to get details!");
+            let mut text = part.pn;
+
+            if let Some(expires) = s.expires {
+                text.push_str(&format!(" until {}", expires));
+
+                if expires < today {
+                    text.push_str(" (expired)");
+                }
+            }
+
+            if let Some(build_id) = s.build_id {
+                text.push_str(&format!(" build {}", build_id));
+            }
+
+            text
+        })
+        .collect()
+}
+
+/// Function used to export BOM to CSV
+pub fn export(
+    app: &mut crate::Application,
+    part_number: &str,
+    version: &Option<i32>,
+    ranges: bool,
+) {
+    use crate::schema::*;
+
+    // Find the part and either use the provided version or the latest
+    let (part, ver) = find_bom_part(app, part_number, version);
+
+    // Get all the parts related to this BOM
+    let mut results = parts_parts::dsl::parts_parts
+        .filter(parts_parts::dsl::bom_part_id.eq(part.id))
+        .filter(parts_parts::dsl::bom_ver.eq(ver))
+        .load::<models::PartsPart>(&app.conn)
+        .expect("Error loading parts");
+
+    // Sort the results by refdes. Lines without one go first.
+    results.sort_by_key(|a| a.refdes.chars().next());
+
+    // Create filename
+    let filename = format!("{}-v{}-{}.csv", part_number, ver, Utc::now().to_rfc3339());
+
+    // File operations
+    let file = File::create(&filename).unwrap();
+    let file = BufWriter::new(file);
+
+    // Create CSV writer
+    let mut wtr = csv::Writer::from_writer(file);
+
+    for entry in results {
+        let details = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to get details!");
+
+        // Get inventory info
+        let inventory = inventories::dsl::inventories
+            .filter(inventories::dsl::part_id.eq(entry.part_id))
+            .load::<models::Inventory>(&app.conn)
+            .expect("Error loading parts");
+
+        let mut inventory_qty = 0;
+
+        // Tally inventory
+        for item in inventory {
+            // Increment quantity
+            inventory_qty += item.quantity;
+        }
+
+        let refdes = if ranges {
+            compress_refdes(&entry.refdes)
+        } else {
+            entry.refdes
+        };
+
+        // Then pop it into a serializeable struct
+        let line = BomEntry {
+            quantity: entry.quantity,
+            refdes,
+            pn: details.pn,
+            manufacturer: find_manufacturer_name(&app.conn, &details.manufacturer_id)
+                .expect("Unable to get manufacturer!"),
+            mpn: details.mpn,
+            descr: details.descr,
+            val: details.val.unwrap_or_default(),
+            ver: details.ver,
+            inventory_qty,
+            no_stuff: entry.nostuff,
+        };
+
+        wtr.serialize(line).expect("Unable to serialize.");
+        wtr.flush().expect("Unable to flush");
+    }
+
+    println!("Inventory list exported to {}", filename);
+}
+
+/// Finds a BOM version along with its lines. Exits if either doesn't exist.
+fn find_bom_version(
+    app: &mut crate::Application,
+    part_number: &str,
+    version: &i32,
+) -> (models::Part, Vec<models::PartsPart>) {
+    let part = match find_part_by_pn(&app.conn, part_number) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("{} was not found!", part_number);
+            std::process::exit(1);
+        }
+    };
+
+    let mut lines =
+        find_bom_list_by_id_and_ver(&app.conn, &part.id, version).expect("Error loading parts");
+
+    if lines.is_empty() {
+        println!("{} version {} has no BOM!", part.pn, version);
+        std::process::exit(1);
+    }
+
+    // Sort the results by refdes
+    lines.sort_by(|a, b| a.refdes.cmp(&b.refdes));
+
+    (part, lines)
+}
+
+/// Exits if a BOM version is archived. Archived versions are only changed once restored.
+fn check_archived(app: &mut crate::Application, part: &models::Part, version: &i32) {
+    if is_archived(&app.conn, &part.id, version).expect("Unable to get BOM versions!") {
+        println!(
+            "{} version {} is archived! Restore it with `bom archive --restore` first.",
+            part.pn, version
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Exits if there are builds in progress for a BOM version
+fn check_open_builds(app: &mut crate::Application, part: &models::Part, version: &i32) {
+    let builds = find_open_builds_by_id_and_ver(&app.conn, &part.id, version)
+        .expect("Unable to get builds!");
+
+    if !builds.is_empty() {
+        let ids: Vec<String> = builds.iter().map(|b| b.id.to_string()).collect();
+        println!(
+            "{} version {} is used by open build(s) {}. Complete or delete them first.",
+            part.pn,
+            version,
+            ids.join(", ")
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Deletes a version of a BOM. Refuses if there are open builds of it.
+pub fn delete(app: &mut crate::Application, part_number: &str, version: &i32) {
+    let (part, lines) = find_bom_version(app, part_number, version);
+
+    check_open_builds(app, &part, version);
+
+    // Show what's going away
+    let mut table = Table::new();
+
+    table.add_row(row!["QUANTITY", "REFDES", "PN", "NO STUFF"]);
+    for entry in &lines {
+        let details = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to get details!");
+        table.add_row(row![
+            entry.quantity,
+            entry.refdes,
+            details.pn,
+            entry.nostuff
+        ]);
+    }
+    table.printstd();
+
+    let question = format!(
+        "Would you like to delete these {} lines from {} version {}?",
+        lines.len(),
+        part.pn,
+        version
+    );
+
+    if !app.prompt.ask_yes_no_question(&question) {
+        return;
+    }
+
+    delete_bom_list_by_id_and_ver(&app.conn, &part.id, version)
+        .expect("Unable to delete BOM version!");
+
+    // The part points at the latest version that's left
+    if *version == part.ver {
+        use crate::schema::parts_parts::dsl::*;
+
+        let latest: Option<i32> = parts_parts
+            .select(diesel::dsl::max(bom_ver))
+            .filter(bom_part_id.eq(part.id))
+            .first(&app.conn)
+            .expect("Unable to get BOM versions!");
+
+        if let Some(latest) = latest {
+            let update = models::NewUpdatePart {
+                pn: &part.pn,
+                mpn: &part.mpn,
+                descr: &part.descr,
+                ver: &latest,
+                val: None,
+                mqty: &part.mqty,
+                manufacturer_id: None,
+            };
+
+            update_part(&app.conn, &part.id, &update).expect("Unable to update BOM revision!");
+        }
+    }
+
+    println!("Deleted {} version {}", part.pn, version);
+}
+
+/// Splits up a list of reference designators. i.e. `R1 R2` or `R1,R2`
+fn split_refdes(refdes: &str) -> Vec<(String, Option<String>)> {
+    refdes
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|r| !r.is_empty())
+        .map(|r| (r.to_string(), None))
+        .collect()
+}
+
+/// Finds a part to put on a BOM. Exits if it doesn't exist.
+fn find_line_part(app: &mut crate::Application, part_number: &str) -> models::Part {
+    match find_part_by_pn(&app.conn, part_number) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("{} was not found!", part_number);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Finds the line of a part in a BOM version. Parts on more than one line are picked by refdes.
+fn find_bom_line(
+    app: &mut crate::Application,
+    bom: &models::Part,
+    version: &i32,
+    part: &models::Part,
+    refdes: &Option<String>,
+) -> models::PartsPart {
+    let mut lines: Vec<models::PartsPart> =
+        find_bom_list_by_id_and_ver(&app.conn, &bom.id, version)
+            .expect("Error loading parts")
+            .into_iter()
+            .filter(|line| line.part_id == part.id)
+            .filter(|line| refdes.as_ref().is_none_or(|r| &line.refdes == r))
+            .collect();
+
+    match lines.len() {
+        0 => {
+            println!("{} is not on {} version {}!", part.pn, bom.pn, version);
+            std::process::exit(1);
+        }
+        1 => lines.remove(0),
+        _ => {
+            let refdes: Vec<String> = lines.iter().map(|l| format!("\"{}\"", l.refdes)).collect();
+            println!(
+                "{} is on more than one line of {} version {}. Pick one with --line: {}",
+                part.pn,
+                bom.pn,
+                version,
+                refdes.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Adds a part to a BOM version by hand. Manual lines are kept when the BOM is imported again.
+pub fn add_line(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    part_number: &str,
+    quantity: i32,
+    refdes: &str,
+    nostuff: bool,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    check_archived(app, &bom, &ver);
+    check_open_builds(app, &bom, &ver);
+
+    let part = find_line_part(app, part_number);
+
+    if part.id == bom.id {
+        println!("{} can't be on its own BOM!", part.pn);
+        std::process::exit(1);
+    }
+
+    let lines = find_bom_list_by_id_and_ver(&app.conn, &bom.id, &ver).expect("Error loading parts");
+
+    if lines.iter().any(|line| line.part_id == part.id) {
+        println!(
+            "{} is already on {} version {}. Use bom line set to change it.",
+            part.pn, bom.pn, ver
+        );
+        std::process::exit(1);
+    }
+
+    let line = models::NewPartsParts {
+        quantity: &quantity,
+        bom_ver: &ver,
+        refdes,
+        nostuff: &(nostuff as i32),
+        bom_part_id: &bom.id,
+        part_id: &part.id,
+        alias_id: None,
+        manual: &1,
+    };
+
+    create_bom_line_with_refdes(&app.conn, &line, &split_refdes(refdes))
+        .expect("Unable to add line!");
+
+    println!(
+        "Added {}x {} to {} version {}",
+        quantity, part.pn, bom.pn, ver
+    );
+}
+
+/// Removes a part from a BOM version
+pub fn remove_line(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    part_number: &str,
+    refdes: &Option<String>,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    check_archived(app, &bom, &ver);
+    check_open_builds(app, &bom, &ver);
+
+    let part = find_line_part(app, part_number);
+    let line = find_bom_line(app, &bom, &ver, &part, refdes);
+
+    let question = format!(
+        "Would you like to remove {}x {} ({}) from {} version {}?",
+        line.quantity, part.pn, line.refdes, bom.pn, ver
+    );
+
+    if !app.prompt.ask_yes_no_question(&question) {
+        return;
+    }
+
+    delete_bom_line_item(&app.conn, &line.id).expect("Unable to remove line!");
+
+    println!("Removed {} from {} version {}", part.pn, bom.pn, ver);
+
+    if line.manual == 0 {
+        println!("Note: importing the schematic over this version brings it back.");
+    }
+}
+
+/// Changes to a BOM line. Anything left as None stays the same.
+#[derive(Debug, Default)]
+pub struct LineChange {
+    pub quantity: Option<i32>,
+    pub refdes: Option<String>,
+    pub nostuff: Option<bool>,
+}
+
+/// Changes the quantity, refdes or no stuff of a line in a BOM version
+pub fn set_line(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    part_number: &str,
+    line_refdes: &Option<String>,
+    change: &LineChange,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    check_archived(app, &bom, &ver);
+    check_open_builds(app, &bom, &ver);
+
+    let part = find_line_part(app, part_number);
+    let line = find_bom_line(app, &bom, &ver, &part, line_refdes);
+
+    let update = models::NewPartsParts {
+        quantity: change.quantity.as_ref().unwrap_or(&line.quantity),
+        bom_ver: &line.bom_ver,
+        refdes: change.refdes.as_ref().unwrap_or(&line.refdes),
+        nostuff: &change.nostuff.map_or(line.nostuff, |n| n as i32),
+        bom_part_id: &line.bom_part_id,
+        part_id: &line.part_id,
+        alias_id: line.alias_id.as_ref(),
+        manual: &line.manual,
+    };
+
+    update_bom_line_item(&app.conn, &line.id, &update).expect("Unable to update line!");
+
+    // Values stay with their refdes
+    if let Some(refdes) = &change.refdes {
+        let existing = find_refdes_by_line_id(&app.conn, &line.id).expect("Error loading refdes");
+
+        let designators: Vec<(String, Option<String>)> = split_refdes(refdes)
+            .into_iter()
+            .map(|(name, _)| {
+                let val = existing
+                    .iter()
+                    .find(|r| r.refdes == name)
+                    .and_then(|r| r.val.clone());
+                (name, val)
+            })
+            .collect();
+
+        set_bom_line_refdes(&app.conn, &line.id, &designators).expect("Unable to update line!");
+    }
+
+    println!(
+        "{} on {} version {}: {}x ({}) no stuff: {}",
+        part.pn, bom.pn, ver, update.quantity, update.refdes, update.nostuff
+    );
+
+    if line.manual == 0 {
+        println!("Note: importing the schematic over this version replaces this change.");
+    }
+}
+
+/// A part that can be used instead of the one on a BOM line
+#[derive(Debug, Default)]
+pub struct Substitute {
+    pub part_number: String,
+    /// Last day it can be used. YYYY-MM-DD
+    pub expires: Option<String>,
+    /// Only this build can use it
+    pub build_id: Option<i32>,
+}
+
+/// Allows another part to be used on a BOM line. Open builds of the version can use it straight away.
+pub fn add_substitution(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    part_number: &str,
+    line_refdes: &Option<String>,
+    new: &Substitute,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    let part = find_line_part(app, part_number);
+    let line = find_bom_line(app, &bom, &ver, &part, line_refdes);
+    let substitute = find_line_part(app, &new.part_number);
+    let build_id = new.build_id;
+
+    if substitute.id == part.id || substitute.id == bom.id {
+        println!("{} can't be a substitute for {}!", substitute.pn, part.pn);
+        std::process::exit(1);
+    }
+
+    let expires = match &new.expires {
+        Some(date) => match super::parts::parse_date(date) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if expires.is_some_and(|d| d < chrono::Local::now().naive_local().date()) {
+        println!(
+            "{} has already passed!",
+            new.expires.as_deref().unwrap_or_default()
+        );
+        std::process::exit(1);
+    }
+
+    // Only open builds of this version
+    if let Some(build_id) = build_id {
+        let open = find_open_builds_by_id_and_ver(&app.conn, &bom.id, &ver)
+            .expect("Unable to get builds!");
+
+        if !open.iter().any(|b| b.id == build_id) {
+            println!(
+                "Build {} is not an open build of {} version {}!",
+                build_id, bom.pn, ver
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let existing = find_bom_substitutions_by_line_id(&app.conn, &line.id)
+        .expect("Unable to get substitutions!");
+
+    if existing
+        .iter()
+        .any(|s| s.part_id == substitute.id && s.build_id == build_id)
+    {
+        println!(
+            "{} is already a substitute for {} on {} version {}",
+            substitute.pn, part.pn, bom.pn, ver
+        );
+        std::process::exit(1);
+    }
+
+    let substitution = models::NewBomSubstitution {
+        expires: expires.as_ref(),
+        line_id: &line.id,
+        part_id: &substitute.id,
+        build_id: build_id.as_ref(),
+    };
+
+    create_bom_substitution(&app.conn, &substitution).expect("Unable to add substitution!");
+
+    println!(
+        "{} can be used for {} on {} version {}",
+        substitute.pn, part.pn, bom.pn, ver
+    );
+}
+
+/// Removes a substitution from a BOM line. Every one for the part unless a build is given.
+pub fn remove_substitution(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    part_number: &str,
+    line_refdes: &Option<String>,
+    substitute_part_number: &str,
+    build_id: Option<i32>,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    let part = find_line_part(app, part_number);
+    let line = find_bom_line(app, &bom, &ver, &part, line_refdes);
+    let substitute = find_line_part(app, substitute_part_number);
+
+    let substitutions: Vec<models::BomSubstitution> =
+        find_bom_substitutions_by_line_id(&app.conn, &line.id)
+            .expect("Unable to get substitutions!")
+            .into_iter()
+            .filter(|s| s.part_id == substitute.id)
+            .filter(|s| build_id.is_none() || s.build_id == build_id)
+            .collect();
+
+    if substitutions.is_empty() {
+        println!(
+            "{} is not a substitute for {} on {} version {}!",
+            substitute.pn, part.pn, bom.pn, ver
+        );
+        std::process::exit(1);
+    }
+
+    for substitution in &substitutions {
+        delete_bom_substitution(&app.conn, &substitution.id)
+            .expect("Unable to remove substitution!");
+    }
+
+    println!(
+        "{} is no longer a substitute for {} on {} version {}",
+        substitute.pn, part.pn, bom.pn, ver
+    );
+}
+
+/// Finds a reference designator in a BOM version. Exits if it's not there.
+fn find_refdes(
+    app: &mut crate::Application,
+    bom: &models::Part,
+    version: &i32,
+    refdes: &str,
+) -> (models::PartsPart, models::BomRefdes) {
+    match find_bom_refdes(&app.conn, &bom.id, version, refdes) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("{} is not on {} version {}!", refdes, bom.pn, version);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Shows what is placed at a reference designator
+pub fn show_refdes(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    refdes: &str,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+    let (line, designator) = find_refdes(app, &bom, &ver, refdes);
+
+    let part = find_part_by_id(&app.conn, &line.part_id).expect("Unable to get details!");
+
+    println!("{} version {}", bom.pn, ver);
+
+    // Create the table
+    let mut table = Table::new();
+
+    table.add_row(row![
+        "REFDES", "PN", "MPN", "DESC", "VALUE", "NO STUFF", "LINE", "MANUAL"
+    ]);
+    table.add_row(row![
+        designator.refdes,
+        part.pn,
+        part.mpn,
+        part.descr,
+        designator.val.unwrap_or_default(),
+        line.nostuff,
+        format!("{}x ({})", line.quantity, line.refdes),
+        line.manual
+    ]);
+    table.printstd();
+}
+
+/// Changes to a single reference designator. Anything left as None stays the same.
+#[derive(Debug, Default)]
+pub struct RefdesChange {
+    pub nostuff: Option<bool>,
+    /// Blank clears the value
+    pub value: Option<String>,
+}
+
+/// Changes the value or no stuff of a single reference designator.
+/// Lines are all stuffed or not so changing no stuff moves it to another line of the same part.
+pub fn set_refdes(
+    app: &mut crate::Application,
+    bom_part_number: &str,
+    version: &Option<i32>,
+    refdes: &str,
+    change: &RefdesChange,
+) {
+    let (bom, ver) = find_bom_part(app, bom_part_number, version);
+
+    check_archived(app, &bom, &ver);
+    check_open_builds(app, &bom, &ver);
+
+    let (line, designator) = find_refdes(app, &bom, &ver, refdes);
+
+    // Only a whole share of the quantity can go along
+    if change.nostuff.is_some_and(|n| n as i32 != line.nostuff) {
+        let designators =
+            find_refdes_by_line_id(&app.conn, &line.id).expect("Unable to get details!");
+
+        if refdes_share(&line, designators.len()).is_none() {
+            println!(
+                "Unable to move {}. The line has {} for {} refdes ({}), which doesn't split evenly. Set its quantity with bom line set first.",
+                designator.refdes,
+                line.quantity,
+                designators.len(),
+                line.refdes
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let result = app.conn.transaction::<_, diesel::result::Error, _>(|| {
+        if let Some(value) = &change.value {
+            let value = Some(value.trim()).filter(|v| !v.is_empty());
+            update_refdes_val(&app.conn, &designator.id, value)?;
+        }
+
+        match change.nostuff {
+            Some(nostuff) if nostuff as i32 != line.nostuff => {
+                move_refdes(&app.conn, &line, &designator.refdes, nostuff as i32)
+            }
+            _ => Ok(()),
+        }
+    });
+
+    if let Err(e) = result {
+        println!("Unable to update {}: {}", refdes, e);
+        std::process::exit(1);
+    }
+
+    let (line, designator) = find_refdes(app, &bom, &ver, refdes);
+
+    println!(
+        "{} on {} version {}: value: {} no stuff: {}",
+        designator.refdes,
+        bom.pn,
+        ver,
+        designator.val.unwrap_or_default(),
+        line.nostuff
+    );
+
+    if line.manual == 0 {
+        println!("Note: importing the schematic over this version replaces this change.");
+    }
+}
+
+/// The quantity each refdes of a line stands for. i.e. MQTY. None if it doesn't split evenly.
+fn refdes_share(line: &models::PartsPart, count: usize) -> Option<i32> {
+    let count = count as i32;
+
+    if count == 0 || line.quantity % count != 0 {
+        return None;
+    }
+
+    Some(line.quantity / count)
+}
+
+/// Moves a reference designator to the line of the same part with the other no stuff.
+/// The quantity for it goes along. Lines that don't split evenly are left alone.
+fn move_refdes(
+    conn: &SqliteConnection,
+    line: &models::PartsPart,
+    refdes: &str,
+    nostuff: i32,
+) -> std::result::Result<(), diesel::result::Error> {
+    let designators = find_refdes_by_line_id(conn, &line.id)?;
+
+    // Each refdes has the same share. set_refdes checks this first.
+    let quantity = match refdes_share(line, designators.len()) {
+        Some(q) => q,
+        None => return Err(diesel::result::Error::RollbackTransaction),
+    };
+
+    let (moved, remaining): (Vec<models::BomRefdes>, Vec<models::BomRefdes>) =
+        designators.into_iter().partition(|r| r.refdes == refdes);
+
+    let to_pairs = |list: Vec<models::BomRefdes>| -> Vec<(String, Option<String>)> {
+        list.into_iter().map(|r| (r.refdes, r.val)).collect()
+    };
+
+    // Take it off the line. Nothing left means the line goes too.
+    if remaining.is_empty() {
+        delete_bom_line_item(conn, &line.id)?;
+    } else {
+        let update = models::NewPartsParts {
+            quantity: &(line.quantity - quantity),
+            bom_ver: &line.bom_ver,
+            refdes: &line.refdes,
+            nostuff: &line.nostuff,
+            bom_part_id: &line.bom_part_id,
+            part_id: &line.part_id,
+            alias_id: line.alias_id.as_ref(),
+            manual: &line.manual,
+        };
+        update_bom_line_item(conn, &line.id, &update)?;
+        set_bom_line_refdes(conn, &line.id, &to_pairs(remaining))?;
+    }
+
+    let target = find_bom_list_by_id_and_ver(conn, &line.bom_part_id, &line.bom_ver)?
+        .into_iter()
+        .find(|l| l.part_id == line.part_id && l.alias_id == line.alias_id && l.nostuff == nostuff);
+
+    match target {
+        Some(target) => {
+            let mut designators = to_pairs(find_refdes_by_line_id(conn, &target.id)?);
+            designators.extend(to_pairs(moved));
+
+            let update = models::NewPartsParts {
+                quantity: &(target.quantity + quantity),
+                bom_ver: &target.bom_ver,
+                refdes: &target.refdes,
+                nostuff: &target.nostuff,
+                bom_part_id: &target.bom_part_id,
+                part_id: &target.part_id,
+                alias_id: target.alias_id.as_ref(),
+                manual: &target.manual,
+            };
+            update_bom_line_item(conn, &target.id, &update)?;
+            set_bom_line_refdes(conn, &target.id, &designators)?;
+        }
+        None => {
+            let new = models::NewPartsParts {
+                quantity: &quantity,
+                bom_ver: &line.bom_ver,
+                refdes,
+                nostuff: &nostuff,
+                bom_part_id: &line.bom_part_id,
+                part_id: &line.part_id,
+                alias_id: line.alias_id.as_ref(),
+                manual: &line.manual,
+            };
+            create_bom_line_with_refdes(conn, &new, &to_pairs(moved))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compresses runs of three or more reference designators. i.e. `R1 R2 R3 R4 R5 R8` is `R1-R5, R8`
+pub fn compress_refdes(refdes: &str) -> String {
+    // Split into the prefix and number. Anything without a number is left as it is.
+    let mut designators: Vec<(String, Option<u32>)> = refdes
+        .split_whitespace()
+        .map(|r| {
+            let prefix = r.trim_end_matches(|c: char| c.is_ascii_digit());
+            (prefix.to_string(), r[prefix.len()..].parse().ok())
+        })
+        .collect();
+
+    designators.sort();
+
+    let name = |prefix: &str, number: Option<u32>| match number {
+        Some(n) => format!("{}{}", prefix, n),
+        None => prefix.to_string(),
+    };
+
+    let mut groups: Vec<String> = Vec::new();
+    let mut index = 0;
+
+    while index < designators.len() {
+        let (prefix, start) = &designators[index];
+
+        // Find the end of the run
+        let mut end = index;
+        while let (Some(s), Some((next_prefix, Some(next)))) = (start, designators.get(end + 1)) {
+            if next_prefix != prefix || *next != s + (end + 1 - index) as u32 {
+                break;
+            }
+            end += 1;
+        }
+
+        if end - index >= 2 {
+            groups.push(format!(
+                "{}-{}",
+                name(prefix, *start),
+                name(prefix, designators[end].1)
+            ));
+        } else {
+            for (prefix, number) in &designators[index..=end] {
+                groups.push(name(prefix, *number));
+            }
+        }
+
+        index = end + 1;
+    }
+
+    groups.join(", ")
+}
+
+/// Archives (or restores) a version of a BOM. Archived versions are kept but can't be shown or built.
+pub fn archive(app: &mut crate::Application, part_number: &str, version: &i32, restore: bool) {
+    let (part, _) = find_bom_version(app, part_number, version);
+
+    let archived = if restore { 0 } else { 1 };
+
+    set_bom_archived_by_id_and_ver(&app.conn, &part.id, version, &archived)
+        .expect("Unable to archive BOM version!");
+
+    if restore {
+        println!("Restored {} version {}", part.pn, version);
+    } else {
+        println!("Archived {} version {}", part.pn, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schematic::{Attribute, Part, Variant};
+
+    /// In-memory schematic. Library entries are (library, pn, attributes)
+    struct TestSource {
+        parts: Vec<Part>,
+        library: Vec<(String, String, Vec<Attribute>)>,
+    }
+
+    impl SchematicSource for TestSource {
+        fn parts(&self) -> Vec<Part> {
+            self.parts.clone()
+        }
+
+        fn attributes(&self) -> Vec<Attribute> {
+            vec![attribute("PN", "ASSY-1"), attribute("DESC", "Assembly")]
+        }
+
+        fn variants(&self) -> Vec<VariantDef> {
+            vec![VariantDef {
+                name: "default".to_string(),
+                current: Some("yes".to_string()),
+            }]
+        }
+
+        fn library_attributes(&self, library: &str, part_number: &str) -> Option<Vec<Attribute>> {
+            self.library
+                .iter()
+                .find(|(l, pn, _)| l == library && pn == part_number)
+                .map(|(_, _, a)| a.clone())
+        }
+    }
+
+    fn attribute(name: &str, value: &str) -> Attribute {
+        Attribute {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn library(name: &str) -> LibraryConfig {
+        LibraryConfig {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn part(name: &str, deviceset: &str) -> Part {
+        Part {
+            name: name.to_string(),
+            deviceset: deviceset.to_string(),
+            technology: None,
+            device: "".to_string(),
+            value: None,
+            variants: Vec::new(),
+            attributes: Vec::new(),
+            library: None,
+        }
+    }
+
+    fn test_source() -> TestSource {
+        let mut r3 = part("R3", "RES-10K");
+        r3.variants.push(Variant {
+            name: "default".to_string(),
+            populate: Some("no".to_string()),
+            value: None,
+            technology: None,
+        });
+
+        TestSource {
+            parts: vec![
+                part("R1", "RES-10K"),
+                part("R2", "RES-10K"),
+                r3,
+                part("GND1", "GND"),
+            ],
+            library: vec![(
+                "lib".to_string(),
+                "RES-10K".to_string(),
+                vec![
+                    attribute("MPN", "RC0402-10K"),
+                    attribute("DESC", "RES 10K 0402"),
+                ],
+            )],
+        }
+    }
+
+    #[test]
+    fn line_items_are_grouped_by_pn_and_nostuff() {
+        let source = test_source();
+        let variant = &source.variants()[0];
+
+        let list = get_line_items_from_parts(&source.parts(), variant, &["GND".to_string()]);
+
+        assert_eq!(2, list.len());
+        assert_eq!("R1 R2", list[0].name);
+        assert_eq!(2, list[0].quantity);
+        assert_eq!(0, list[0].nostuff);
+        assert_eq!("R3", list[1].name);
+        assert_eq!(1, list[1].nostuff);
+    }
+
+    #[test]
+    fn variant_technology_overrides_part() {
+        let mut part = part("C1", "CAP-");
+        part.technology = Some("0.1U".to_string());
+        part.variants.push(Variant {
+            name: "alt".to_string(),
+            populate: None,
+            value: None,
+            technology: Some("1U".to_string()),
+        });
+
+        let variant = VariantDef {
+            name: "alt".to_string(),
+            current: None,
+        };
+
+        let list = get_line_items_from_parts(&[part], &variant, &[]);
+
+        assert_eq!("CAP-1U", list[0].pn);
+    }
+
+    #[test]
+    fn simplepart_uses_library_attributes() {
+        let source = test_source();
+        let variant = &source.variants()[0];
+        let list = get_line_items_from_parts(&source.parts(), variant, &[]);
+
+        let part = get_simplepart_from_library(
+            &list[0],
+            &source,
+            &[library("lib")],
+            &config::default_suppliers(),
+        );
+        assert_eq!("RC0402-10K", part.mpn);
+        assert_eq!("RES 10K 0402", part.descr);
+        assert_eq!(Some("lib".to_string()), part.library);
+
+        // MQTY is per placement. It isn't the number of placements.
+        assert_eq!(2, list[0].quantity);
+        assert_eq!(1, part.mqty);
+
+        // Wrong library doesn't resolve
+        let part = get_simplepart_from_library(
+            &list[0],
+            &source,
+            &[library("other")],
+            &config::default_suppliers(),
+        );
+        assert!(part.mpn.is_empty());
+        assert_eq!(None, part.library);
+    }
+
+    #[test]
+    fn simplepart_searches_libraries_in_order() {
+        let mut source = test_source();
+        source.library.push((
+            "vendor".to_string(),
+            "RES-10K".to_string(),
+            vec![attribute("MFR_PN", "VENDOR-10K")],
+        ));
+
+        let variant = &source.variants()[0];
+        let list = get_line_items_from_parts(&source.parts(), variant, &[]);
+
+        // First library wins
+        let part = get_simplepart_from_library(
+            &list[0],
+            &source,
+            &[library("lib"), library("vendor")],
+            &config::default_suppliers(),
+        );
+        assert_eq!("RC0402-10K", part.mpn);
+
+        // Attribute names are mapped
+        let mut vendor = library("vendor");
+        vendor
+            .attribute_map
+            .insert("MFR_PN".to_string(), "MPN".to_string());
+
+        let part = get_simplepart_from_library(
+            &list[0],
+            &source,
+            &[library("other"), vendor],
+            &config::default_suppliers(),
+        );
+        assert_eq!("VENDOR-10K", part.mpn);
+        assert_eq!(Some("vendor".to_string()), part.library);
+    }
+
+    #[test]
+    fn simplepart_collects_supplier_part_numbers() {
+        let mut source = test_source();
+        source.library[0]
+            .2
+            .push(attribute("DIGIKEYPN", "311-10KLRCT-ND"));
+        source.library[0]
+            .2
+            .push(attribute("MOUSERPN", " 603-RC0402FR-0710KL "));
+        source.library[0].2.push(attribute("LCSCPN", ""));
+        source.library[0]
+            .2
+            .push(attribute("ARROWPN", "RC0402FR-0710KL"));
+        source.library[0].2.push(attribute("Footprint", "R_0402"));
+        source.library[0].2.push(attribute("ki_keywords", "R res"));
+        source.library[0].2.push(attribute("PACKAGE", "0402"));
+
+        let variant = &source.variants()[0];
+        let list = get_line_items_from_parts(&source.parts(), variant, &[]);
+        let suppliers = vec![
+            "DIGIKEY".to_string(),
+            "MOUSER".to_string(),
+            "LCSC".to_string(),
+        ];
+
+        // MPN is not a supplier, blank ones are skipped and unknown suppliers are left out
+        let part = get_simplepart_from_library(&list[0], &source, &[library("lib")], &suppliers);
+        assert_eq!("RC0402-10K", part.mpn);
+        assert_eq!(
+            vec![
+                ("DIGIKEY".to_string(), "311-10KLRCT-ND".to_string()),
+                ("MOUSER".to_string(), "603-RC0402FR-0710KL".to_string())
+            ],
+            part.suppliers
+        );
+
+        // So are the schematic tool's own fields
+        assert_eq!(
+            vec![("package".to_string(), "0402".to_string())],
+            part.attributes
+        );
+
+        // Only Digikey by default
+        let part = get_simplepart_from_library(
+            &list[0],
+            &source,
+            &[library("lib")],
+            &config::default_suppliers(),
+        );
+        assert_eq!(
+            vec![("DIGIKEY".to_string(), "311-10KLRCT-ND".to_string())],
+            part.suppliers
+        );
+        assert_eq!(
+            vec![("package".to_string(), "0402".to_string())],
+            part.attributes
+        );
+    }
+
+    #[test]
+    fn select_variants_names_boms_by_variant() {
+        let variants = vec![
+            VariantDef {
+                name: "A".to_string(),
+                current: Some("yes".to_string()),
+            },
+            VariantDef {
+                name: "B".to_string(),
+                current: None,
+            },
+        ];
+
+        let selected = select_variants(&variants, &VariantSelection::Current, "PN").unwrap();
+        assert_eq!(vec![(variants[0].clone(), "PN".to_string())], selected);
+
+        let selected =
+            select_variants(&variants, &VariantSelection::Named("B".to_string()), "PN").unwrap();
+        assert_eq!(vec![(variants[1].clone(), "PN-B".to_string())], selected);
+
+        let selected = select_variants(&variants, &VariantSelection::All, "PN").unwrap();
+        let names: Vec<&str> = selected.iter().map(|(_, pn)| pn.as_str()).collect();
+        assert_eq!(vec!["PN-A", "PN-B"], names);
+
+        assert!(
+            select_variants(&variants, &VariantSelection::Named("C".to_string()), "PN").is_err()
+        );
+    }
+
+    #[test]
+    fn alias_targets_must_exist() {
+        let conn = test_connection();
+
+        let res = models::NewUpdatePart {
+            pn: "RES-10K",
+            mpn: "RC0402-10K",
+            descr: "RES 10K 0402",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+        create_part(&conn, &res).expect("Unable to create part");
+
+        let mut source = test_source();
+        source.parts.push(part("RN1", "R-PACK"));
+        source.library.push((
+            "lib".to_string(),
+            "R-PACK".to_string(),
+            vec![attribute("ALIAS", "RES-10K"), attribute("MQTY", "4")],
+        ));
+        source.library.push((
+            "lib".to_string(),
+            "R-PACK-BAD".to_string(),
+            vec![attribute("ALIAS", "RES-NOPE")],
+        ));
+
+        let variant = &source.variants()[0];
+        let list = get_line_items_from_parts(&source.parts(), variant, &[]);
+        let alias = list.iter().find(|i| i.pn == "R-PACK").unwrap();
+
+        let alias = get_simplepart_from_library(
+            alias,
+            &source,
+            &[library("lib")],
+            &config::default_suppliers(),
+        );
+        assert_eq!(Some("RES-10K".to_string()), alias.alias);
+        assert_eq!(4, alias.mqty);
+        assert!(find_missing_alias_targets(&conn, &[alias]).is_empty());
+
+        let bad = LineItem {
+            name: "RN2".to_string(),
+            pn: "R-PACK-BAD".to_string(),
+            quantity: 1,
+            ..Default::default()
+        };
+        let bad = get_simplepart_from_library(
+            &bad,
+            &source,
+            &[library("lib")],
+            &config::default_suppliers(),
+        );
+        assert_eq!(
+            vec!["RES-NOPE".to_string()],
+            find_missing_alias_targets(&conn, &[bad])
+        );
+    }
+
+    #[test]
+    fn instance_attributes_override_library() {
+        let mut j1 = part("J1", "RES-10K");
+        j1.value = Some("10k".to_string());
+        j1.attributes = vec![attribute("MQTY", "3"), attribute("MPN", "OTHER-10K")];
+
+        let mut j2 = part("J2", "RES-10K");
+        j2.attributes = vec![attribute("DNP", "yes")];
+
+        let mut source = test_source();
+        source.parts = vec![part("R1", "RES-10K"), j1, j2];
+
+        let variant = &source.variants()[0];
+        let list = get_line_items_from_parts(&source.parts(), variant, &[]);
+
+        // Different overrides are different lines
+        assert_eq!(3, list.len());
+        assert_eq!(Some(3), list[1].mqty);
+        assert_eq!(1, list[2].nostuff);
+
+        let part = get_simplepart_from_library(
+            &list[1],
+            &source,
+            &[library("lib")],
+            &config::default_suppliers(),
+        );
+        assert_eq!("OTHER-10K", part.mpn);
+        assert_eq!("RES 10K 0402", part.descr);
+        assert_eq!(Some("10k".to_string()), part.val);
+
+        // R1 and J1 would be the same part with two MPNs
+        let conn = test_connection();
+        source.parts[2].attributes.push(attribute("MQTY", "two"));
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert_eq!(
+            vec![
+                "J2 has an invalid MQTY: two".to_string(),
+                "RES-10K has a different MPN, DESC or MANUFACTURER on R1 and J1. Use another part number."
+                    .to_string()
+            ],
+            plan.errors
+        );
+    }
+
+    /// Creates a part in the test DB and returns its id
+    fn db_part(conn: &SqliteConnection, pn: &str) -> i32 {
+        let part = models::NewUpdatePart {
+            pn,
+            mpn: pn,
+            descr: pn,
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+        create_part(conn, &part).expect("Unable to create part");
+
+        find_part_by_pn(conn, pn).unwrap().id
+    }
+
+    /// Adds a line to version 1 of a BOM in the test DB
+    fn db_line(conn: &SqliteConnection, bom: i32, part: i32, quantity: i32, refdes: &str) {
+        db_line_ver(conn, bom, 1, part, quantity, refdes);
+    }
+
+    /// Adds a line to a version of a BOM in the test DB
+    fn db_line_ver(
+        conn: &SqliteConnection,
+        bom: i32,
+        ver: i32,
+        part: i32,
+        quantity: i32,
+        refdes: &str,
+    ) {
+        let line = models::NewPartsParts {
+            quantity: &quantity,
+            bom_ver: &ver,
+            refdes,
+            nostuff: &0,
+            bom_part_id: &bom,
+            part_id: &part,
+            alias_id: None,
+            manual: &0,
+        };
+        create_bom_line_item(conn, &line).expect("Unable to add line");
+    }
+
+    #[test]
+    fn multi_level_boms_are_exploded() {
+        let conn = test_connection();
+
+        // A product made of two boards and a case
+        let product = db_part(&conn, "PRODUCT");
+        let board = db_part(&conn, "BOARD");
+        let case = db_part(&conn, "CASE");
+        let res = db_part(&conn, "RES-10K");
+
+        db_line(&conn, product, board, 2, "A1 A2");
+        db_line(&conn, product, case, 1, "M1");
+        db_line(&conn, board, res, 3, "R1 R2 R3");
+
+        let lines = explode(&conn, &product, &1).unwrap();
+        let tree: Vec<(usize, &str, i32, bool)> = lines
+            .iter()
+            .map(|l| (l.level, l.part.pn.as_str(), l.total, l.exploded))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, "BOARD", 2, true),
+                (1, "RES-10K", 6, false),
+                (0, "CASE", 1, false)
+            ],
+            tree
+        );
+
+        // One board already built. Only the other one needs parts.
+        let inventory = models::NewUpdateInventoryEntry {
+            quantity: &1,
+            consumed: &0,
+            unit_price: None,
+            notes: None,
+            part_ver: &1,
+            part_id: &board,
+            alternate_id: None,
+        };
+        create_inventory(&conn, &inventory).unwrap();
+
+        let mut stock = HashMap::new();
+        let requirements = get_requirements(&conn, &product, &1, 1, None, &mut stock).unwrap();
+        assert_eq!(
+            vec![
+                Requirement {
+                    part_id: board,
+                    quantity: 1,
+                    substitutes: Vec::new()
+                },
+                Requirement {
+                    part_id: res,
+                    quantity: 3,
+                    substitutes: Vec::new()
+                },
+                Requirement {
+                    part_id: case,
+                    quantity: 1,
+                    substitutes: Vec::new()
+                },
+            ],
+            requirements
+        );
+
+        // The built board is used up now
+        let requirements = get_requirements(&conn, &product, &1, 1, None, &mut stock).unwrap();
+        assert_eq!(
+            6,
+            requirements
+                .iter()
+                .find(|r| r.part_id == res)
+                .unwrap()
+                .quantity
+        );
+        assert!(requirements.iter().all(|r| r.part_id != board));
+    }
+
+    #[test]
+    fn parts_that_arent_active_are_reported() {
+        let conn = test_connection();
+
+        let product = db_part(&conn, "PRODUCT");
+        let board = db_part(&conn, "BOARD");
+        let res = db_part(&conn, "RES-10K");
+        let cap = db_part(&conn, "CAP-1U");
+
+        db_line(&conn, product, board, 2, "A1 A2");
+        db_line(&conn, board, res, 3, "R1 R2 R3");
+        db_line(&conn, board, cap, 1, "C1");
+
+        let obsolete = models::UpdatePartLifecycle {
+            lifecycle: "obsolete",
+            last_time_buy: chrono::NaiveDate::from_ymd_opt(2026, 1, 31),
+        };
+        set_part_lifecycle(&conn, &res, &obsolete).unwrap();
+
+        let nrnd = models::UpdatePartLifecycle {
+            lifecycle: "nrnd",
+            last_time_buy: None,
+        };
+        set_part_lifecycle(&conn, &cap, &nrnd).unwrap();
+
+        assert_eq!(
+            vec![
+                "CAP-1U is nrnd (C1)".to_string(),
+                "RES-10K is obsolete (last time buy 2026-01-31) (R1 R2 R3)".to_string()
+            ],
+            lifecycle_warnings(&conn, &product, &1).unwrap()
+        );
+
+        // 5 products with one board already built
+        let build = models::NewUpdateBuild {
+            quantity: &5,
+            complete: &0,
+            notes: None,
+            part_ver: &1,
+            part_id: &product,
+        };
+        create_build(&conn, &build).unwrap();
+
+        let inventory = models::NewUpdateInventoryEntry {
+            quantity: &1,
+            consumed: &0,
+            unit_price: None,
+            notes: None,
+            part_ver: &1,
+            part_id: &board,
+            alternate_id: None,
+        };
+        create_inventory(&conn, &inventory).unwrap();
+
+        let report = crate::tables::report::find_obsolescence(&conn).unwrap();
+
+        // Worst first
+        let pns: Vec<&str> = report.iter().map(|r| r.part.pn.as_str()).collect();
+        assert_eq!(vec!["RES-10K", "CAP-1U"], pns);
+
+        assert_eq!(vec!["BOARD v1", "PRODUCT v1"], report[0].boms);
+        assert_eq!(1, report[0].builds.len());
+        assert_eq!("PRODUCT", report[0].builds[0].bom_pn);
+        assert_eq!(27, report[0].needed());
+        assert_eq!(9, report[1].needed());
+
+        // Imports warn too
+        let conn = test_connection();
+        let config = test_config();
+        let plan = plan_import(&conn, &config, &test_source(), &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        let res = find_part_by_pn(&conn, "RES-10K").unwrap();
+        set_part_lifecycle(&conn, &res.id, &nrnd).unwrap();
+
+        let plan = plan_import(&conn, &config, &test_source(), &VariantSelection::Current);
+        assert_eq!(vec!["RES-10K is nrnd (R1 R2)"], plan.boms[0].warnings);
+    }
+
+    #[test]
+    fn where_used_walks_up_to_products() {
+        let conn = test_connection();
+
+        let product = db_part(&conn, "PRODUCT");
+        let board = db_part(&conn, "BOARD");
+        let spare = db_part(&conn, "SPARE-KIT");
+        let res = db_part(&conn, "RES-10K");
+
+        db_line(&conn, product, board, 2, "A1 A2");
+        db_line(&conn, board, res, 3, "R1 R2 R3");
+        db_line_ver(&conn, board, 2, res, 2, "R1 R2");
+        db_line(&conn, spare, res, 10, "");
+
+        // Archived versions don't lead anywhere
+        set_bom_archived_by_id_and_ver(&conn, &spare, &1, &1).unwrap();
+
+        let build = models::NewUpdateBuild {
+            quantity: &5,
+            complete: &0,
+            notes: None,
+            part_ver: &1,
+            part_id: &product,
+        };
+        create_build(&conn, &build).unwrap();
+
+        let usages = where_used(&conn, &res).unwrap();
+        let usages: Vec<(usize, &str, i32, usize, bool)> = usages
+            .iter()
+            .map(|u| {
+                (
+                    u.level,
+                    u.bom.pn.as_str(),
+                    u.entry.bom_ver,
+                    u.open_builds.len(),
+                    u.top_level,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                (0, "BOARD", 1, 0, false),
+                (0, "BOARD", 2, 0, false),
+                (1, "PRODUCT", 1, 1, true),
+                (0, "SPARE-KIT", 1, 0, true),
+            ],
+            usages
+        );
+    }
+
+    #[test]
+    fn assemblies_that_contain_themselves_stop() {
+        let conn = test_connection();
+
+        let board = db_part(&conn, "BOARD");
+        let panel = db_part(&conn, "PANEL");
+
+        db_line(&conn, board, panel, 1, "P1");
+        db_line(&conn, panel, board, 2, "B1");
+
+        assert_eq!(2, explode(&conn, &board, &1).unwrap().len());
+
+        let requirements =
+            get_requirements(&conn, &board, &1, 1, None, &mut HashMap::new()).unwrap();
+        assert_eq!(
+            vec![Requirement {
+                part_id: board,
+                quantity: 2,
+                substitutes: Vec::new()
+            }],
+            requirements
+        );
+    }
+
+    #[test]
+    fn archived_versions_are_skipped() {
+        let conn = test_connection();
+
+        let board = db_part(&conn, "BOARD");
+        let res = db_part(&conn, "RES-10K");
+
+        db_line_ver(&conn, board, 1, res, 2, "R1 R2");
+        db_line_ver(&conn, board, 2, res, 3, "R1 R2 R3");
+
+        let part = find_part_by_id(&conn, &board).unwrap();
+        assert_eq!(2, active_version(&conn, &part).unwrap());
+        assert!(!is_archived(&conn, &board, &2).unwrap());
+
+        set_bom_archived_by_id_and_ver(&conn, &board, &2, &1).unwrap();
+        assert_eq!(1, active_version(&conn, &part).unwrap());
+        assert!(is_archived(&conn, &board, &2).unwrap());
+
+        // Versions without a BOM aren't archived
+        assert!(!is_archived(&conn, &board, &3).unwrap());
+
+        // Restoring brings it back
+        set_bom_archived_by_id_and_ver(&conn, &board, &2, &0).unwrap();
+        assert_eq!(2, active_version(&conn, &part).unwrap());
+
+        // Diffs skip archived and missing versions
+        db_line_ver(&conn, board, 4, res, 4, "R1 R2 R3 R4");
+        assert_eq!(Some(2), previous_version(&conn, &part, &4).unwrap());
+        set_bom_archived_by_id_and_ver(&conn, &board, &2, &1).unwrap();
+        assert_eq!(Some(1), previous_version(&conn, &part, &4).unwrap());
+        assert_eq!(None, previous_version(&conn, &part, &1).unwrap());
+    }
+
+    #[test]
+    fn sub_assemblies_use_their_active_version() {
+        let conn = test_connection();
+
+        let product = db_part(&conn, "PRODUCT");
+        let board = db_part(&conn, "BOARD");
+        let res = db_part(&conn, "RES-10K");
+
+        db_line(&conn, product, board, 1, "");
+        db_line_ver(&conn, board, 1, res, 2, "R1 R2");
+        db_line_ver(&conn, board, 2, res, 3, "R1 R2 R3");
+        diesel::update(crate::schema::parts::dsl::parts.find(board))
+            .set(crate::schema::parts::dsl::ver.eq(2))
+            .execute(&conn)
+            .unwrap();
+        set_bom_archived_by_id_and_ver(&conn, &board, &2, &1).unwrap();
+
+        let needed = |conn: &SqliteConnection| -> Vec<(i32, i32)> {
+            get_requirements(conn, &product, &1, 1, None, &mut HashMap::new())
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.part_id, r.quantity))
+                .collect()
+        };
+
+        // Version 2 is archived so version 1 is built
+        let lines = explode(&conn, &product, &1).unwrap();
+        assert_eq!(2, lines.len());
+        assert_eq!(2, lines[1].total);
+        assert_eq!(vec![(res, 2)], needed(&conn));
+
+        // Nothing left that isn't archived. The board is bought as it is.
+        set_bom_archived_by_id_and_ver(&conn, &board, &1, &1).unwrap();
+
+        let lines = explode(&conn, &product, &1).unwrap();
+        assert_eq!(1, lines.len());
+        assert!(!lines[0].exploded);
+        assert_eq!(vec![(board, 1)], needed(&conn));
+    }
+
+    #[test]
+    fn substitutes_cover_shortages() {
+        let conn = test_connection();
+
+        let board = db_part(&conn, "BOARD");
+        let cap = db_part(&conn, "CAP-1U-16V");
+        let cap_25v = db_part(&conn, "CAP-1U-25V");
+        let cap_50v = db_part(&conn, "CAP-1U-50V");
+        let cap_x7r = db_part(&conn, "CAP-1U-X7R");
+        db_line(&conn, board, cap, 4, "C1 C2 C3 C4");
+
+        let build = models::NewUpdateBuild {
+            quantity: &1,
+            complete: &0,
+            notes: None,
+            part_ver: &1,
+            part_id: &board,
+        };
+        create_build(&conn, &build).unwrap();
+        let build_id = find_open_builds_by_id_and_ver(&conn, &board, &1).unwrap()[0].id;
+
+        // Expired and for another build don't count
+        let line = find_bom_list_by_id_and_ver(&conn, &board, &1).unwrap()[0].id;
+        let expired = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        for (part_id, expires, build) in [
+            (cap_25v, None, None),
+            (cap_50v, Some(&expired), None),
+            (cap_x7r, None, Some(&(build_id + 1))),
+        ] {
+            let substitution = models::NewBomSubstitution {
+                expires,
+                line_id: &line,
+                part_id: &part_id,
+                build_id: build,
+            };
+            create_bom_substitution(&conn, &substitution).unwrap();
+        }
+
+        let requirements =
+            get_requirements(&conn, &board, &1, 1, Some(&build_id), &mut HashMap::new()).unwrap();
+        assert_eq!(
+            vec![Requirement {
+                part_id: cap,
+                quantity: 4,
+                substitutes: vec![cap_25v]
+            }],
+            requirements
+        );
+
+        // One 16V and two 25V on hand. Everything but the 50V is in stock.
+        for (part_id, quantity) in [(cap, 1), (cap_25v, 2), (cap_50v, 10), (cap_x7r, 10)] {
+            let inventory = models::NewUpdateInventoryEntry {
+                quantity: &quantity,
+                consumed: &0,
+                unit_price: None,
+                notes: None,
+                part_ver: &1,
+                part_id: &part_id,
+                alternate_id: None,
+            };
+            create_inventory(&conn, &inventory).unwrap();
+        }
+
+        let shortages = crate::tables::inventory::get_shortages(&conn, true).unwrap();
+        let short: Vec<(&str, i32, i32)> = shortages
+            .iter()
+            .map(|s| (s.pn.as_str(), s.needed, s.short))
+            .collect();
+        assert_eq!(vec![("CAP-1U-16V", 2, 1), ("CAP-1U-25V", 2, 0)], short);
+    }
+
+    #[test]
+    fn substitutions_survive_overwrite() {
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        let assembly = find_part_by_pn(&conn, "ASSY-1").unwrap();
+        let substitute = db_part(&conn, "RES-10K-1%");
+        let line = find_bom_list_by_id_and_ver(&conn, &assembly.id, &1).unwrap()[0].id;
+        let substitution = models::NewBomSubstitution {
+            expires: None,
+            line_id: &line,
+            part_id: &substitute,
+            build_id: None,
+        };
+        create_bom_substitution(&conn, &substitution).unwrap();
+
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        plan.boms[0].action = BomAction::Overwrite(1);
+        apply_plan(&conn, &plan).unwrap();
+
+        // Every line of the part is new but it's still allowed
+        let lines = find_bom_list_by_id_and_ver(&conn, &assembly.id, &1).unwrap();
+        assert!(lines.iter().all(|l| l.id != line));
+
+        let substitutions: Vec<i32> = lines
+            .iter()
+            .flat_map(|l| find_bom_substitutions_by_line_id(&conn, &l.id).unwrap())
+            .map(|s| s.part_id)
+            .collect();
+        assert_eq!(vec![substitute], substitutions);
+    }
+
+    #[test]
+    fn merged_parts_become_aliases() {
+        use crate::tables::parts::{merge_parts, plan_merge};
+
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        let assembly = find_part_by_pn(&conn, "ASSY-1").unwrap();
+        let dropped = find_part_by_pn(&conn, "RES-10K").unwrap();
+        let kept = db_part(&conn, "RES-10K-0402");
+        let line = models::NewPartsParts {
+            quantity: &1,
+            bom_ver: &1,
+            refdes: "R5",
+            nostuff: &0,
+            bom_part_id: &assembly.id,
+            part_id: &kept,
+            alias_id: None,
+            manual: &0,
+        };
+        create_bom_line_with_refdes(&conn, &line, &[("R5".to_string(), None)]).unwrap();
+
+        for (part_id, quantity) in [(kept, 3), (dropped.id, 5)] {
+            let inventory = models::NewUpdateInventoryEntry {
+                quantity: &quantity,
+                consumed: &0,
+                unit_price: None,
+                notes: None,
+                part_ver: &1,
+                part_id: &part_id,
+                alternate_id: None,
+            };
+            create_inventory(&conn, &inventory).unwrap();
+        }
+
+        // A part can't be merged with a BOM it's on
+        assert_eq!(
+            "RES-10K-0402 is used by ASSY-1",
+            plan_merge(&conn, "ASSY-1", "RES-10K-0402")
+                .err()
+                .unwrap()
+                .to_string()
+        );
+
+        let merge = plan_merge(&conn, "RES-10K-0402", "RES-10K").unwrap();
+        assert_eq!(2, merge.lines.len());
+        merge_parts(&conn, &merge).unwrap();
+
+        assert!(find_part_by_pn(&conn, "RES-10K").is_err());
+
+        // The stuffed lines are combined
+        let lines: Vec<(i32, i32, String, i32)> =
+            find_bom_list_by_id_and_ver(&conn, &assembly.id, &1)
+                .unwrap()
+                .into_iter()
+                .map(|l| (l.part_id, l.quantity, l.refdes, l.nostuff))
+                .collect();
+        assert_eq!(
+            vec![
+                (kept, 1, "R3".to_string(), 1),
+                (kept, 3, "R5 R1 R2".to_string(), 0)
+            ],
+            lines
+        );
+
+        // Its stock is the old MPN as an alternate
+        let alternates = find_part_alternates_by_part_id(&conn, &kept).unwrap();
+        assert_eq!("RC0402-10K", alternates[0].mpn);
+
+        let stock: Vec<(i32, Option<i32>)> = find_inventories_in_use_order(&conn, &kept)
+            .unwrap()
+            .iter()
+            .map(|i| (i.quantity, i.alternate_id))
+            .collect();
+        assert_eq!(vec![(3, None), (5, Some(alternates[0].id))], stock);
+
+        // Schematics that still use the old PN import as the kept part
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+        assert!(plan.boms[0].parts.is_empty());
+        assert!(plan.boms[0]
+            .lines
+            .iter()
+            .all(|(_, part)| part.alias == Some("RES-10K-0402".to_string())));
+    }
+
+    #[test]
+    fn deleting_parts_removes_references() {
+        use crate::tables::parts::{delete_part_and_references, find_references};
+
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        let assembly = find_part_by_pn(&conn, "ASSY-1").unwrap();
+        let resistor = find_part_by_pn(&conn, "RES-10K").unwrap();
+
+        let inventory = models::NewUpdateInventoryEntry {
+            quantity: &5,
+            consumed: &0,
+            unit_price: None,
+            notes: None,
+            part_ver: &1,
+            part_id: &resistor.id,
+            alternate_id: None,
+        };
+        create_inventory(&conn, &inventory).unwrap();
+
+        let build = models::NewUpdateBuild {
+            quantity: &1,
+            complete: &0,
+            notes: None,
+            part_ver: &1,
+            part_id: &assembly.id,
+        };
+        create_build(&conn, &build).unwrap();
+
+        let references = find_references(&conn, &resistor.id).unwrap();
+        assert_eq!(2, references.lines.len());
+        assert_eq!(1, references.inventories.len());
+        assert!(references.builds.is_empty());
+
+        // Archived parts are still there but flagged on BOMs
+        set_part_archived(&conn, &resistor.id, &1).unwrap();
+        let resistor = find_part_by_pn(&conn, "RES-10K").unwrap();
+        assert_eq!(Some("archived".to_string()), lifecycle_status(&resistor));
+
+        delete_part_and_references(&conn, &resistor).unwrap();
+        assert!(find_bom_list_by_id_and_ver(&conn, &assembly.id, &1)
+            .unwrap()
+            .is_empty());
+        assert!(find_inventories_by_part_id(&conn, &resistor.id)
+            .unwrap()
+            .is_empty());
+
+        // An assembly takes its BOM and builds with it
+        delete_part_and_references(&conn, &assembly).unwrap();
+        assert!(find_references(&conn, &assembly.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn kicad_symbols_use_their_own_library() {
+        let conn = test_connection();
+
+        let input = r#"(kicad_sch
+  (title_block (comment 1 "PN=ASSY-1") (comment 2 "DESC=Assembly"))
+  (symbol (lib_id "Device:R") (property "Reference" "R1") (property "Value" "10k")
+    (property "MPN" "RC0402-10K") (property "DIGIKEYPN" "311-10K"))
+  (symbol (lib_id "Device:R") (property "Reference" "R2") (property "Value" "10k")
+    (property "MPN" "RC0402-10K") (property "DIGIKEYPN" "311-10K"))
+)"#;
+
+        // None of the configured libraries are called Device
+        let source = crate::kicad::Kicad::from_sch(input.as_bytes()).unwrap();
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+
+        let part = &plan.boms[0].parts[0].part;
+        assert_eq!("R", part.pn);
+        assert_eq!(Some("Device".to_string()), part.library);
+        assert_eq!(
+            vec![("DIGIKEY".to_string(), "311-10K".to_string())],
+            part.suppliers
+        );
+
+        // The same name in two libraries isn't the same part
+        let input = input.replacen("Device:R", "Connector:R", 1);
+        let source = crate::kicad::Kicad::from_sch(input.as_bytes()).unwrap();
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert_eq!(
+            vec!["R is in more than one library (Connector and Device)".to_string()],
+            plan.errors
+        );
+    }
+
+    fn test_config() -> config::Config {
+        config::Config {
+            database_name: "test.db".to_string(),
+            library_name: Some("lib".to_string()),
+            part_number_ignore_list: vec!["GND".to_string()],
+            suppliers: config::default_suppliers(),
+            libraries: Vec::new(),
+            import_policy: Default::default(),
+            csv_columns: Default::default(),
+            attrition_config: config::AttritionConfig {
+                entries: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn import_plan_collects_every_error() {
+        let conn = test_connection();
+
+        // Something else already has the MPN
+        let other = models::NewUpdatePart {
+            pn: "RES-10K-OLD",
+            mpn: "RC0402-10K",
+            descr: "RES 10K 0402",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+        create_part(&conn, &other).unwrap();
+
+        let mut source = test_source();
+        source.parts.push(part("U1", "MCU"));
+        source.parts.push(part("RN1", "R-PACK"));
+        source.library.push((
+            "lib".to_string(),
+            "R-PACK".to_string(),
+            vec![attribute("ALIAS", "RES-NOPE"), attribute("MQTY", "four")],
+        ));
+
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+
+        assert_eq!(
+            vec![
+                "R-PACK has an invalid MQTY in lib: four".to_string(),
+                "RES-10K has MPN RC0402-10K which is already used by RES-10K-OLD".to_string(),
+                "Unable to find MCU in any library".to_string(),
+                "Alias target RES-NOPE not found! Create it first.".to_string(),
+            ],
+            plan.errors
+        );
+
+        // Planning doesn't write anything
+        assert!(find_part_by_pn(&conn, "ASSY-1").is_err());
+    }
+
+    #[test]
+    fn import_plan_is_applied() {
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+        assert_eq!(BomAction::Create, plan.boms[0].action);
+        assert_eq!(PartAction::Create, plan.boms[0].parts[0].action);
+
+        apply_plan(&conn, &plan).unwrap();
+
+        let bom = find_part_by_pn(&conn, "ASSY-1").unwrap();
+        assert_eq!(2, lines_from_db(&conn, &bom.id, &1).unwrap().len());
+
+        // Importing again finds everything
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(BomAction::Undecided(1), plan.boms[0].action);
+        assert_eq!(PartAction::Unchanged, plan.boms[0].parts[0].action);
+
+        plan.boms[0].action = BomAction::Revise(2);
+        apply_plan(&conn, &plan).unwrap();
+
+        assert_eq!(2, find_part_by_pn(&conn, "ASSY-1").unwrap().ver);
+        assert_eq!(2, lines_from_db(&conn, &bom.id, &2).unwrap().len());
+        assert_eq!(2, lines_from_db(&conn, &bom.id, &1).unwrap().len());
+    }
+
+    #[test]
+    fn import_policies_decide_without_asking() {
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        // Change a part so the next import wants to update it
+        let res = find_part_by_pn(&conn, "RES-10K").unwrap();
+        let changed = models::NewUpdatePart {
+            pn: &res.pn,
+            mpn: &res.mpn,
+            descr: "RES 10K OLD",
+            ver: &res.ver,
+            val: None,
+            mqty: &res.mqty,
+            manufacturer_id: None,
+        };
+        update_part(&conn, &res.id, &changed).unwrap();
+
+        let plan_with = |policy: ImportPolicy| {
+            let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+            apply_policy(&mut plan, &policy);
+            plan
+        };
+
+        let update = |plan: &ImportPlan| {
+            plan.boms[0]
+                .parts
+                .iter()
+                .find(|p| p.part.pn == "RES-10K")
+                .map(|p| p.action.clone())
+                .unwrap()
+        };
+
+        // Ask leaves everything for the prompts
+        let plan = plan_with(ImportPolicy::default());
+        assert!(plan.errors.is_empty());
+        assert_eq!(BomAction::Undecided(1), plan.boms[0].action);
+        assert!(matches!(update(&plan), PartAction::Update(_)));
+
+        let plan = plan_with(ImportPolicy {
+            on_existing_bom: OnExistingBom::Uprev,
+            on_part_change: OnPartChange::Keep,
+        });
+        assert!(plan.errors.is_empty());
+        assert_eq!(BomAction::Revise(2), plan.boms[0].action);
+        assert_eq!(PartAction::Keep, update(&plan));
+
+        let plan = plan_with(ImportPolicy {
+            on_existing_bom: OnExistingBom::Overwrite,
+            on_part_change: OnPartChange::Update,
+        });
+        assert!(plan.errors.is_empty());
+        assert_eq!(BomAction::Overwrite(1), plan.boms[0].action);
+        assert!(matches!(update(&plan), PartAction::Update(_)));
+
+        // Abort and fail are errors so nothing gets written
+        let plan = plan_with(ImportPolicy {
+            on_existing_bom: OnExistingBom::Abort,
+            on_part_change: OnPartChange::Fail,
+        });
+        assert_eq!(2, plan.errors.len());
+        assert!(plan.errors[0].contains("ASSY-1"));
+        assert!(plan.errors[1].contains("RES-10K"));
+    }
+
+    #[test]
+    fn value_changes_are_detected_on_import() {
+        let conn = test_connection();
+        let config = test_config();
+        let mut source = test_source();
+        source.library[0].2.push(attribute("VALUE", "10k"));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+        let part = find_part_by_pn(&conn, "RES-10K").unwrap();
+        assert_eq!(Some("10k".to_string()), part.val);
+
+        // Only the value is different
+        source.library[0].2.pop();
+        source.library[0].2.push(attribute("VALUE", "10k 1%"));
+
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        let action = &plan.boms[0].parts[0].action;
+        assert_eq!(
+            &PartAction::Update(vec![("val", "10k".to_string(), "10k 1%".to_string())]),
+            action
+        );
+
+        plan.boms[0].action = BomAction::Overwrite(1);
+        apply_plan(&conn, &plan).unwrap();
+        let part = find_part_by_pn(&conn, "RES-10K").unwrap();
+        assert_eq!(Some("10k 1%".to_string()), part.val);
+
+        // The schematic value wins over the library
+        source.parts[0].value = Some("10k 0.1%".to_string());
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(
+            PartAction::Update(vec![("val", "10k 1%".to_string(), "10k 0.1%".to_string())]),
+            plan.boms[0].parts[0].action
+        );
+        source.parts[0].value = None;
+
+        // Parts without a value keep theirs
+        source.library[0].2.pop();
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(PartAction::Unchanged, plan.boms[0].parts[0].action);
+    }
+
+    #[test]
+    fn supplier_and_attribute_changes_are_detected_on_import() {
+        let conn = test_connection();
+        let config = test_config();
+        let mut source = test_source();
+        source.library[0].2.push(attribute("DIGIKEYPN", "311-10K"));
+        source.library[0].2.push(attribute("PACKAGE", "0402"));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+        let part = find_part_by_pn(&conn, "RES-10K").unwrap();
+
+        let stored = |conn: &SqliteConnection| -> (String, String) {
+            let supplier = &find_part_suppliers_by_part_id(conn, &part.id).unwrap()[0];
+            let attribute = &find_part_attributes_by_part_id(conn, &part.id).unwrap()[0];
+            (supplier.supplier_pn.clone(), attribute.value.clone())
+        };
+        assert_eq!(("311-10K".to_string(), "0402".to_string()), stored(&conn));
+
+        // Nothing changed
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(PartAction::Unchanged, plan.boms[0].parts[0].action);
+
+        source.library[0].2.pop();
+        source.library[0].2.pop();
+        source.library[0]
+            .2
+            .push(attribute("DIGIKEYPN", "311-10KLR"));
+        source.library[0].2.push(attribute("PACKAGE", "0603"));
+
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(
+            PartAction::Update(vec![
+                (
+                    "supplier",
+                    "DIGIKEY 311-10K".to_string(),
+                    "DIGIKEY 311-10KLR".to_string()
+                ),
+                (
+                    "attribute",
+                    "package 0402".to_string(),
+                    "package 0603".to_string()
+                ),
+            ]),
+            plan.boms[0].parts[0].action
+        );
+
+        // Kept parts aren't written
+        plan.boms[0].action = BomAction::Overwrite(1);
+        plan.boms[0].parts[0].action = PartAction::Keep;
+        apply_plan(&conn, &plan).unwrap();
+        assert_eq!(("311-10K".to_string(), "0402".to_string()), stored(&conn));
+
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        plan.boms[0].action = BomAction::Overwrite(1);
+        apply_plan(&conn, &plan).unwrap();
+        assert_eq!(("311-10KLR".to_string(), "0603".to_string()), stored(&conn));
+    }
+
+    #[test]
+    fn mpns_are_unique_per_manufacturer() {
+        let conn = test_connection();
+        let config = test_config();
+
+        // Same MPN from a different manufacturer
+        let yageo = find_or_create_manufacturer(&conn, "Yageo").unwrap();
+        let other = models::NewUpdatePart {
+            pn: "RES-10K-OLD",
+            mpn: "RC0402-10K",
+            descr: "RES 10K 0402",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: Some(&yageo.id),
+        };
+        create_part(&conn, &other).unwrap();
+
+        let mut source = test_source();
+        source.library[0]
+            .2
+            .push(attribute("MANUFACTURER", "Vishay"));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+        apply_plan(&conn, &plan).unwrap();
+
+        let part = find_part_by_pn(&conn, "RES-10K").unwrap();
+        assert_eq!(
+            "Vishay",
+            find_manufacturer_name(&conn, &part.manufacturer_id).unwrap()
+        );
+
+        // Manufacturers are matched without case
+        source.library[0].2.pop();
+        source.library[0].2.push(attribute("MANUFACTURER", "YAGEO"));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(
+            vec!["RES-10K has MPN RC0402-10K which is already used by RES-10K-OLD".to_string()],
+            plan.errors
+        );
+
+        // The instance overrides the library
+        source.parts[0]
+            .attributes
+            .push(attribute("MANUFACTURER", "Panasonic"));
+        source.parts[1]
+            .attributes
+            .push(attribute("MANUFACTURER", "Panasonic"));
+        source.parts[2]
+            .attributes
+            .push(attribute("MANUFACTURER", "Panasonic"));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+        assert_eq!(
+            PartAction::Update(vec![(
+                "manufacturer",
+                "Vishay".to_string(),
+                "Panasonic".to_string()
+            )]),
+            plan.boms[0].parts[0].action
+        );
+    }
+
+    #[test]
+    fn unmapped_library_attributes_are_parametric() {
+        let conn = test_connection();
+        let mut lib = library("lib");
+        lib.attribute_map
+            .insert("PKG".to_string(), "Package".to_string());
+
+        let mut config = test_config();
+        config.libraries = vec![lib];
+
+        let mut source = test_source();
+        source.library[0].2.push(attribute("PKG", "0402"));
+        source.library[0].2.push(attribute("VOLTAGE", " 50V "));
+        source.library[0].2.push(attribute("DNP", "no"));
+        source.library[0].2.push(attribute("TOLERANCE", ""));
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(
+            vec![
+                ("package".to_string(), "0402".to_string()),
+                ("voltage".to_string(), "50V".to_string())
+            ],
+            plan.boms[0].parts[0].part.attributes
+        );
+
+        apply_plan(&conn, &plan).unwrap();
+
+        let part = find_part_by_pn(&conn, "RES-10K").unwrap();
+        let attributes: Vec<(String, String)> = find_part_attributes_by_part_id(&conn, &part.id)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.name, a.value))
+            .collect();
+        assert_eq!(plan.boms[0].parts[0].part.attributes, attributes);
+
+        // Setting it again replaces the value
+        set_part_attribute(&conn, &part.id, "voltage", "25V").unwrap();
+        let attributes = find_part_attributes_by_part_id(&conn, &part.id).unwrap();
+        assert_eq!(2, attributes.len());
+        assert_eq!("25V", attributes[1].value);
+    }
+
+    #[test]
+    fn csv_rows_use_parts_in_the_database() {
+        let conn = test_connection();
+
+        // Existing part with an MQTY that the CSV doesn't know about
+        let existing = models::NewUpdatePart {
+            pn: "RES-10K",
+            mpn: "RC0402-10K",
+            descr: "RES 10K",
+            ver: &1,
+            val: None,
+            mqty: &2,
+            manufacturer_id: None,
+        };
+        create_part(&conn, &existing).unwrap();
+
+        let csv = "\
+Refdes,Qty,PN,MPN,Description
+R1 R2,2,RES-10K,,
+R3,,,RC0402-10K,
+,4,SCREW-M3,ISO7380-M3x6,Screw M3x6
+,1,,MISSING-MPN,
+";
+        let mut source = CsvBom::from_reader(
+            csv.as_bytes(),
+            &config::CsvColumns::default(),
+            "MECH-1",
+            "Mechanical",
+        )
+        .unwrap();
+
+        let errors = fill_csv_rows(&conn, &mut source.rows);
+        assert_eq!(
+            vec!["Line 5 has no part number and MISSING-MPN isn't in the database"],
+            errors
+        );
+        source.rows.pop();
+
+        assert_eq!("RES-10K", source.rows[1].pn);
+        assert_eq!(Some("RES 10K".to_string()), source.rows[0].descr);
+
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+        assert_eq!(BomAction::Create, plan.boms[0].action);
+
+        let actions: Vec<(&str, &PartAction)> = plan.boms[0]
+            .parts
+            .iter()
+            .map(|p| (p.part.pn.as_str(), &p.action))
+            .collect();
+        assert_eq!(
+            vec![
+                ("RES-10K", &PartAction::Unchanged),
+                ("SCREW-M3", &PartAction::Create)
+            ],
+            actions
+        );
+
+        apply_plan(&conn, &plan).unwrap();
+
+        let bom = find_part_by_pn(&conn, "MECH-1").unwrap();
+        let lines = lines_from_db(&conn, &bom.id, &1).unwrap();
+        let lines: Vec<(&str, i32, &str)> = lines
+            .iter()
+            .map(|l| (l.pn.as_str(), l.quantity, l.refdes.as_str()))
+            .collect();
+
+        // The CSV quantity isn't multiplied by MQTY
+        assert_eq!(vec![("RES-10K", 3, "R1 R2 R3"), ("SCREW-M3", 4, "")], lines);
+    }
+
+    #[test]
+    fn manual_lines_are_kept_on_import() {
+        let conn = test_connection();
+        let config = test_config();
+        let source = test_source();
+
+        let plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        apply_plan(&conn, &plan).unwrap();
+
+        let bom = find_part_by_pn(&conn, "ASSY-1").unwrap();
+        let screw = db_part(&conn, "SCREW-M3");
+        let res = find_part_by_pn(&conn, "RES-10K").unwrap();
+
+        // Added by hand. The schematic has the resistor too.
+        for (part, quantity) in &[(screw, 4), (res.id, 5)] {
+            let line = models::NewPartsParts {
+                quantity,
+                bom_ver: &1,
+                refdes: "",
+                nostuff: &0,
+                bom_part_id: &bom.id,
+                part_id: part,
+                alias_id: None,
+                manual: &1,
+            };
+            create_bom_line_item(&conn, &line).unwrap();
+        }
+
+        let manual_pns = |plan: &ImportPlan| -> Vec<String> {
+            plan.boms[0].manual.iter().map(|m| m.pn.clone()).collect()
+        };
+
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        assert_eq!(vec!["SCREW-M3", "RES-10K"], manual_pns(&plan));
+
+        // The resistor's manual line isn't dropped without saying so
+        assert_eq!(1, plan.boms[0].warnings.len());
+        assert!(plan.boms[0].warnings[0].starts_with("RES-10K is in the schematic"));
+
+        plan.boms[0].action = BomAction::Revise(2);
+        apply_plan(&conn, &plan).unwrap();
+
+        let lines = find_bom_list_by_id_and_ver(&conn, &bom.id, &2).unwrap();
+        assert_eq!(4, lines.len());
+        assert!(lines
+            .iter()
+            .any(|l| l.part_id == screw && l.manual == 1 && l.quantity == 4));
+        assert!(lines
+            .iter()
+            .any(|l| l.part_id == res.id && l.manual == 1 && l.quantity == 5));
+        assert!(lines
+            .iter()
+            .any(|l| l.part_id == res.id && l.manual == 0 && l.quantity == 2));
+
+        // Overwriting keeps them too. Only once.
+        let mut plan = plan_import(&conn, &config, &source, &VariantSelection::Current);
+        plan.boms[0].action = BomAction::Overwrite(2);
+        apply_plan(&conn, &plan).unwrap();
+
+        let lines = find_bom_list_by_id_and_ver(&conn, &bom.id, &2).unwrap();
+        assert_eq!(4, lines.len());
+        assert_eq!(2, lines.iter().filter(|l| l.manual == 1).count());
+    }
+
+    #[test]
+    fn refdes_are_stored_one_per_row() {
+        let conn = test_connection();
+
+        let mut source = test_source();
+        source.parts[0].value = Some("10k".to_string());
+        source.parts[1].value = Some("10k 1%".to_string());
+
+        // Different values make it two parts
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert_eq!(
+            vec!["RES-10K has the value 10k on R1 and 10k 1% on R2. Use another part number."],
+            plan.errors
+        );
+        apply_plan(&conn, &plan).unwrap();
+
+        let bom = find_part_by_pn(&conn, "ASSY-1").unwrap();
+
+        let (line, r2) = find_bom_refdes(&conn, &bom.id, &1, "R2").unwrap();
+        assert_eq!("R1 R2", line.refdes);
+        assert_eq!(Some("10k 1%".to_string()), r2.val);
+
+        // Moving one over splits the quantity
+        move_refdes(&conn, &line, "R2", 1).unwrap();
+
+        let lines: Vec<(i32, String, i32)> = find_bom_list_by_id_and_ver(&conn, &bom.id, &1)
+            .unwrap()
+            .into_iter()
+            .map(|l| (l.quantity, l.refdes, l.nostuff))
+            .collect();
+        assert_eq!(
+            vec![(1, "R1".to_string(), 0), (2, "R3 R2".to_string(), 1)],
+            lines
+        );
+
+        // The value goes with it
+        let (_, r2) = find_bom_refdes(&conn, &bom.id, &1, "R2").unwrap();
+        assert_eq!(Some("10k 1%".to_string()), r2.val);
+
+        // Nothing left on the line removes it
+        let (line, _) = find_bom_refdes(&conn, &bom.id, &1, "R1").unwrap();
+        move_refdes(&conn, &line, "R1", 1).unwrap();
+
+        let lines = find_bom_list_by_id_and_ver(&conn, &bom.id, &1).unwrap();
+        assert_eq!(1, lines.len());
+        assert_eq!(3, lines[0].quantity);
+
+        // Deleting the version deletes the refdes
+        delete_bom_list_by_id_and_ver(&conn, &bom.id, &1).unwrap();
+        assert!(find_refdes_by_line_id(&conn, &lines[0].id)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn refdes_with_uneven_quantity_are_not_moved() {
+        let conn = test_connection();
+
+        let bom = db_part(&conn, "ASSY-1");
+        let part = db_part(&conn, "CONN-2");
+
+        let line = models::NewPartsParts {
+            quantity: &10,
+            bom_ver: &1,
+            refdes: "",
+            nostuff: &0,
+            bom_part_id: &bom,
+            part_id: &part,
+            alias_id: None,
+            manual: &1,
+        };
+        let designators: Vec<(String, Option<String>)> = ["J1", "J2", "J3"]
+            .iter()
+            .map(|r| (r.to_string(), None))
+            .collect();
+        create_bom_line_with_refdes(&conn, &line, &designators).unwrap();
+
+        let (line, _) = find_bom_refdes(&conn, &bom, &1, "J2").unwrap();
+        assert_eq!(None, refdes_share(&line, 3));
+        assert!(move_refdes(&conn, &line, "J2", 1).is_err());
+
+        // Nothing changed
+        let lines: Vec<(i32, String, i32)> = find_bom_list_by_id_and_ver(&conn, &bom, &1)
+            .unwrap()
+            .into_iter()
+            .map(|l| (l.quantity, l.refdes, l.nostuff))
+            .collect();
+        assert_eq!(vec![(10, "J1 J2 J3".to_string(), 0)], lines);
+    }
+
+    #[test]
+    fn refdes_ranges_are_compressed() {
+        assert_eq!("R1-R5, R8", compress_refdes("R3 R1 R2 R8 R5 R4"));
+        assert_eq!(
+            "C1, C2, C10-C12, R1",
+            compress_refdes("C10 C1 C11 R1 C2 C12")
+        );
+        assert_eq!("J1, TP", compress_refdes("TP J1"));
+        assert_eq!("", compress_refdes(""));
+    }
+
+    #[test]
+    fn failed_import_changes_nothing() {
+        let conn = test_connection();
+
+        let mut source = test_source();
+        source.parts.push(part("C1", "CAP-1U"));
+        source.library.push((
+            "lib".to_string(),
+            "CAP-1U".to_string(),
+            vec![attribute("MPN", "GRM-1U"), attribute("DESC", "CAP 1U")],
+        ));
+
+        let plan = plan_import(&conn, &test_config(), &source, &VariantSelection::Current);
+        assert!(plan.errors.is_empty());
+
+        // Something takes the MPN after planning
+        let other = models::NewUpdatePart {
+            pn: "CAP-1U-OLD",
+            mpn: "GRM-1U",
+            descr: "CAP 1U",
+            ver: &1,
+            val: None,
+            mqty: &1,
+            manufacturer_id: None,
+        };
+        create_part(&conn, &other).unwrap();
+
+        assert!(apply_plan(&conn, &plan).is_err());
+        assert!(find_part_by_pn(&conn, "ASSY-1").is_err());
+        assert!(find_part_by_pn(&conn, "RES-10K").is_err());
+    }
+}
@@ -7,18 +7,20 @@ use self::diesel::prelude::*;
 use crate::{models::*, *};
 
 // Borrowing shortage generation from inventory
-use super::inventory;
+use super::{bom, inventory};
 
 use serde::{Deserialize, Serialize};
 
 use std::{
+    collections::HashMap,
     fs::File,
     io::{self, BufWriter},
 };
 
-pub fn create(app: &mut crate::Application) {
+pub fn create(app: &mut crate::Application, variant: &Option<String>) {
     // Get the input from stdin
     let part_number = app.prompt.ask_text_entry("Part Number: ");
+    let part_number = bom::part_number_for_variant(&part_number, variant);
     let version = app.prompt.ask_text_entry("Version: ");
     let version: i32 = version.trim().parse().expect("Invalid version number!");
     let quantity = app.prompt.ask_text_entry("Quantity: ");
@@ -34,10 +36,18 @@ pub fn create(app: &mut crate::Application) {
     // Transform the response into a Part
     let part = part.unwrap();
 
-    if part.ver != version {
+    if bom::is_archived(&app.conn, &part.id, &version).expect("Unable to get BOM versions!") {
+        println!("{} version {} is archived!", part_number, version);
+        std::process::exit(1);
+    }
+
+    // Archived versions are skipped
+    let latest = bom::active_version(&app.conn, &part).expect("Unable to get BOM versions!");
+
+    if latest != version {
         println!(
             "{} version {} was not found! Latest is: {}",
-            part_number, version, part.ver
+            part_number, version, latest
         );
         std::process::exit(1);
     }
@@ -56,6 +66,8 @@ pub fn create(app: &mut crate::Application) {
         "Created build of {} ver: {} with qty: {}",
         part.pn, part.ver, quantity
     );
+
+    bom::print_lifecycle_warnings(&app.conn, &part.id, &version);
 }
 
 pub fn show(app: &mut crate::Application, show_all: bool) {
@@ -103,20 +115,22 @@ pub fn delete(app: &mut crate::Application, build_id: i32) {
 }
 
 pub fn complete(app: &mut crate::Application, build_id: i32) {
-    use crate::schema::*;
-
     // Get the build
     let build = find_build_by_id(&app.conn, &build_id).expect("Unable to find build!");
 
-    // Get partslist
-    let bom_list = parts_parts::dsl::parts_parts
-        .filter(parts_parts::dsl::bom_part_id.eq(build.part_id))
-        .filter(parts_parts::dsl::bom_ver.eq(build.part_ver))
-        .load::<PartsPart>(&app.conn)
-        .expect("Error loading parts");
+    // Everything used by the build. Built sub-assemblies are used before building more.
+    let requirements = bom::get_requirements(
+        &app.conn,
+        &build.part_id,
+        &build.part_ver,
+        build.quantity,
+        Some(&build.id),
+        &mut HashMap::new(),
+    )
+    .expect("Error loading parts");
 
     // Get the shortages. Shorts only.
-    let shortages = inventory::get_shortages(app, false).expect("Unable to get shortages.");
+    let shortages = inventory::get_shortages(&app.conn, false).expect("Unable to get shortages.");
 
     // Still track if we're short.
     let mut still_short = false;
@@ -154,67 +168,37 @@ pub fn complete(app: &mut crate::Application, build_id: i32) {
         // Used to calculate total cost
         let mut total_cost = 0.0;
 
-        // Iterate though every bom list entry
+        // Iterate though everything used
         // Do the math to modify the inventory
-        for bom_list_entry in &bom_list {
-            // Skip if nostuff is set
-            if bom_list_entry.nostuff == 1 {
-                println!("{} is no stuff.", bom_list_entry.refdes);
-                continue;
-            }
-
-            // Track the quantity
-            let mut quantity = bom_list_entry.quantity;
-
-            // Inventory entries
-            let inventory_entries = find_inventories_by_part_id(&app.conn, &bom_list_entry.part_id)
-                .expect("Unable to query for inventory");
-
-            // Calculate the quantity
-            for entry in inventory_entries {
-                let new_qty;
-                let used;
-
-                // Calculate quantities
-                if entry.quantity >= quantity {
-                    new_qty = entry.quantity - quantity;
-                    used = quantity;
-                    quantity = 0;
-                } else {
-                    new_qty = 0;
-                    used = entry.quantity;
-                    quantity -= entry.quantity
+        for requirement in &requirements {
+            let mut quantity = use_inventory(
+                app,
+                &requirement.part_id,
+                requirement.quantity,
+                &mut total_cost,
+            );
+
+            // Then any substitutes the BOM allows
+            for substitute in &requirement.substitutes {
+                if quantity == 0 {
+                    break;
                 }
 
-                // Get string from entry.notes
-                let notes = match entry.notes {
-                    Some(x) => x,
-                    None => "".to_string(),
-                };
-
-                // Create update
-                let update = NewUpdateInventoryEntry {
-                    quantity: &new_qty,
-                    consumed: &used,
-                    unit_price: entry.unit_price.as_ref(),
-                    notes: Some(&notes),
-                    part_ver: &entry.part_ver,
-                    part_id: &entry.part_id,
-                };
-
-                // Push this inventory item
-                update_inventory_by_id(&app.conn, &entry.id, &update)
-                    .expect("Unable to create inventory.");
-
-                // Add the cost used to total_cost
-                if entry.unit_price.is_some() {
-                    total_cost += used as f32 * entry.unit_price.unwrap();
+                let remaining = use_inventory(app, substitute, quantity, &mut total_cost);
+
+                if remaining < quantity {
+                    let part = find_part_by_id(&app.conn, substitute).expect("Unable to get part.");
+                    let original = find_part_by_id(&app.conn, &requirement.part_id)
+                        .expect("Unable to get part.");
+                    println!(
+                        "Used {} {} in place of {}",
+                        quantity - remaining,
+                        part.pn,
+                        original.pn
+                    );
                 }
 
-                // Break once we get the necessary quantity
-                if quantity == 0 {
-                    break;
-                }
+                quantity = remaining;
             }
 
             // Repeat until complete!
@@ -235,6 +219,7 @@ pub fn complete(app: &mut crate::Application, build_id: i32) {
             notes: Some(&build_name),
             part_ver: &build.part_ver,
             part_id: &build.part_id,
+            alternate_id: None,
         };
 
         // Get string from entry.notes
@@ -260,6 +245,71 @@ pub fn complete(app: &mut crate::Application, build_id: i32) {
     }
 }
 
+/// Takes up to `quantity` of a part out of inventory. Alternates are used in order of preference.
+/// Returns how many are still needed.
+fn use_inventory(
+    app: &mut crate::Application,
+    part_id: &i32,
+    quantity: i32,
+    total_cost: &mut f32,
+) -> i32 {
+    // Track the quantity
+    let mut quantity = quantity;
+
+    // Inventory entries
+    let inventory_entries =
+        find_inventories_in_use_order(&app.conn, part_id).expect("Unable to query for inventory");
+
+    // Calculate the quantity
+    for entry in inventory_entries {
+        let new_qty;
+        let used;
+
+        // Calculate quantities
+        if entry.quantity >= quantity {
+            new_qty = entry.quantity - quantity;
+            used = quantity;
+            quantity = 0;
+        } else {
+            new_qty = 0;
+            used = entry.quantity;
+            quantity -= entry.quantity
+        }
+
+        // Get string from entry.notes
+        let notes = match entry.notes {
+            Some(x) => x,
+            None => "".to_string(),
+        };
+
+        // Create update
+        let update = NewUpdateInventoryEntry {
+            quantity: &new_qty,
+            consumed: &used,
+            unit_price: entry.unit_price.as_ref(),
+            notes: Some(&notes),
+            part_ver: &entry.part_ver,
+            part_id: &entry.part_id,
+            alternate_id: entry.alternate_id.as_ref(),
+        };
+
+        // Push this inventory item
+        update_inventory_by_id(&app.conn, &entry.id, &update).expect("Unable to create inventory.");
+
+        // Add the cost used to total_cost
+        if let Some(price) = entry.unit_price {
+            *total_cost += used as f32 * price;
+        }
+
+        // Break once we get the necessary quantity
+        if quantity == 0 {
+            break;
+        }
+    }
+
+    quantity
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct BuildExport {
     pn: String,
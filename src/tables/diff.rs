@@ -0,0 +1,347 @@
+extern crate diesel;
+
+use prettytable::{row, Table};
+use serde::Serialize;
+
+use crate::*;
+
+use super::bom::{self, BomLine, VariantSelection};
+use crate::schematic::SchematicSource;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Output formats for a diff
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(DiffFormat::Table),
+            "csv" => Ok(DiffFormat::Csv),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "Unknown format {}. Use table, csv or json",
+                s
+            )),
+        }
+    }
+}
+
+/// The kinds of changes between two BOMs
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Quantity,
+    Refdes,
+    Nostuff,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Quantity => "quantity",
+            ChangeKind::Refdes => "refdes",
+            ChangeKind::Nostuff => "nostuff",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A single change to a part between two BOMs
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct BomChange {
+    pub pn: String,
+    pub change: ChangeKind,
+    pub from: String,
+    pub to: String,
+}
+
+/// Everything about one part in a BOM
+#[derive(Default)]
+struct PartSummary {
+    quantity: i32,
+    refdes: BTreeSet<String>,
+    nostuff: BTreeSet<String>,
+}
+
+impl PartSummary {
+    fn describe(&self) -> String {
+        format!("{} ({})", self.quantity, join(&self.refdes))
+    }
+}
+
+fn join(set: &BTreeSet<String>) -> String {
+    set.iter().cloned().collect::<Vec<String>>().join(" ")
+}
+
+/// Groups BOM lines by part number. No stuff lines don't count towards the quantity.
+fn summarize(lines: &[BomLine]) -> BTreeMap<String, PartSummary> {
+    let mut parts: BTreeMap<String, PartSummary> = BTreeMap::new();
+
+    for line in lines {
+        let summary = parts.entry(line.pn.clone()).or_default();
+
+        for refdes in line.refdes.split_whitespace() {
+            summary.refdes.insert(refdes.to_string());
+
+            if line.nostuff == 1 {
+                summary.nostuff.insert(refdes.to_string());
+            }
+        }
+
+        if line.nostuff == 0 {
+            summary.quantity += line.quantity;
+        }
+    }
+
+    parts
+}
+
+/// Compares two sets of BOM lines. Changes are sorted by part number.
+pub fn compare(from: &[BomLine], to: &[BomLine]) -> Vec<BomChange> {
+    let from = summarize(from);
+    let to = summarize(to);
+
+    let pns: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+
+    let mut changes = Vec::new();
+
+    for pn in pns {
+        let change = |change, from: String, to: String| BomChange {
+            pn: pn.clone(),
+            change,
+            from,
+            to,
+        };
+
+        match (from.get(pn), to.get(pn)) {
+            (None, Some(new)) => {
+                changes.push(change(ChangeKind::Added, String::new(), new.describe()))
+            }
+            (Some(old), None) => {
+                changes.push(change(ChangeKind::Removed, old.describe(), String::new()))
+            }
+            (Some(old), Some(new)) => {
+                if old.quantity != new.quantity {
+                    changes.push(change(
+                        ChangeKind::Quantity,
+                        old.quantity.to_string(),
+                        new.quantity.to_string(),
+                    ));
+                }
+
+                if old.refdes != new.refdes {
+                    changes.push(change(
+                        ChangeKind::Refdes,
+                        join(&old.refdes),
+                        join(&new.refdes),
+                    ));
+                }
+
+                if old.nostuff != new.nostuff {
+                    changes.push(change(
+                        ChangeKind::Nostuff,
+                        join(&old.nostuff),
+                        join(&new.nostuff),
+                    ));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    changes
+}
+
+/// Loads a BOM version. Exits if it doesn't exist.
+fn load_version(app: &mut crate::Application, part: &models::Part, ver: i32) -> Vec<BomLine> {
+    let lines = bom::lines_from_db(&app.conn, &part.id, &ver).expect("Error loading parts");
+
+    if lines.is_empty() {
+        println!("{} version {} has no BOM!", part.pn, ver);
+        std::process::exit(1);
+    }
+
+    lines
+}
+
+/// Compares two versions of a BOM. Defaults to the latest and the newest one before it that isn't archived.
+pub fn versions(
+    app: &mut crate::Application,
+    part_number: &str,
+    from: &Option<i32>,
+    to: &Option<i32>,
+    format: DiffFormat,
+) {
+    let part = match find_part_by_pn(&app.conn, part_number) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("{} was not found!", part_number);
+            std::process::exit(1);
+        }
+    };
+
+    let to = to.unwrap_or(part.ver);
+
+    // Skip versions that were deleted or archived
+    let previous = || bom::previous_version(&app.conn, &part, &to).expect("Error loading parts");
+    let from = match from.or_else(previous) {
+        Some(from) => from,
+        None => {
+            println!(
+                "{} has no version before {} to compare to. Use --from to pick one.",
+                part.pn, to
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let old = load_version(app, &part, from);
+    let new = load_version(app, &part, to);
+
+    let title = format!("{} version {} → {}", part.pn, from, to);
+    print(&title, &compare(&old, &new), format);
+}
+
+/// Compares a schematic against a BOM in the database. Defaults to the latest version.
+pub fn file(
+    app: &mut crate::Application,
+    source: &dyn SchematicSource,
+    filename: &str,
+    part_number: &Option<String>,
+    from: &Option<i32>,
+    selection: &VariantSelection,
+    format: DiffFormat,
+) {
+    let (bom_pn, mut new) = match bom::lines_from_source(app, source, selection) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // The schematic's PN unless another one is given
+    let part_number = part_number.clone().unwrap_or(bom_pn);
+
+    // Everything is new if it hasn't been imported yet
+    let (title, old) = match find_part_by_pn(&app.conn, &part_number) {
+        Ok(part) => {
+            let from = from.unwrap_or(part.ver);
+            let title = format!("{} version {} → {}", part.pn, from, filename);
+
+            // Importing keeps manual lines
+            let manual =
+                bom::manual_lines_from_db(&app.conn, &part.id, &from).expect("Error loading parts");
+            new.extend(manual);
+
+            (title, load_version(app, &part, from))
+        }
+        Err(_) => (format!("{} (new) → {}", part_number, filename), Vec::new()),
+    };
+
+    print(&title, &compare(&old, &new), format);
+}
+
+/// Prints the changes in the requested format
+fn print(title: &str, changes: &[BomChange], format: DiffFormat) {
+    match format {
+        DiffFormat::Table => {
+            println!("{}", title);
+
+            if changes.is_empty() {
+                println!("No changes.");
+                return;
+            }
+
+            // Create the table
+            let mut table = Table::new();
+
+            table.add_row(row!["PN", "CHANGE", "FROM", "TO"]);
+            for change in changes {
+                table.add_row(row![change.pn, change.change, change.from, change.to]);
+            }
+            table.printstd();
+        }
+        DiffFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(std::io::stdout());
+
+            for change in changes {
+                wtr.serialize(change).expect("Unable to serialize.");
+            }
+
+            wtr.flush().expect("Unable to flush");
+        }
+        DiffFormat::Json => {
+            let json = serde_json::to_string_pretty(changes).expect("Unable to serialize.");
+            println!("{}", json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(pn: &str, quantity: i32, refdes: &str, nostuff: i32) -> BomLine {
+        BomLine {
+            pn: pn.to_string(),
+            quantity,
+            refdes: refdes.to_string(),
+            nostuff,
+        }
+    }
+
+    #[test]
+    fn identical_boms_have_no_changes() {
+        let lines = vec![line("RES-10K", 2, "R1 R2", 0), line("CAP-1U", 1, "C1", 1)];
+
+        assert!(compare(&lines, &lines).is_empty());
+    }
+
+    #[test]
+    fn changes_are_reported_by_part() {
+        let from = vec![
+            line("RES-10K", 2, "R1 R2", 0),
+            line("CAP-1U", 2, "C1 C2", 0),
+            line("LED-RED", 1, "D1", 0),
+        ];
+        let to = vec![
+            line("RES-10K", 3, "R1 R2 R3", 0),
+            line("CAP-1U", 1, "C1", 0),
+            line("CAP-1U", 1, "C2", 1),
+            line("LED-GREEN", 1, "D1", 0),
+        ];
+
+        let changes = compare(&from, &to);
+        let changes: Vec<(&str, ChangeKind, &str, &str)> = changes
+            .iter()
+            .map(|c| (c.pn.as_str(), c.change, c.from.as_str(), c.to.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("CAP-1U", ChangeKind::Quantity, "2", "1"),
+                ("CAP-1U", ChangeKind::Nostuff, "", "C2"),
+                ("LED-GREEN", ChangeKind::Added, "", "1 (D1)"),
+                ("LED-RED", ChangeKind::Removed, "1 (D1)", ""),
+                ("RES-10K", ChangeKind::Quantity, "2", "3"),
+                ("RES-10K", ChangeKind::Refdes, "R1 R2", "R1 R2 R3"),
+            ],
+            changes
+        );
+    }
+}
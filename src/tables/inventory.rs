@@ -7,6 +7,9 @@ use anyhow::anyhow;
 
 use self::diesel::prelude::*;
 
+use super::bom;
+
+use std::collections::HashMap;
 use std::io::{BufReader, BufWriter};
 use std::{fmt::Debug, fs::File};
 
@@ -15,6 +18,9 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 #[derive(Debug, Deserialize)]
 struct NewInventoryRecord {
     mpn: String,
+    /// Only needed if more than one manufacturer makes the MPN
+    #[serde(default)]
+    manufacturer: Option<String>,
     quantity: Option<i32>,
     notes: Option<String>,
     unit_price: Option<f32>,
@@ -30,6 +36,9 @@ pub struct InventoryEntry {
     pub notes: Option<String>,
     pub part_ver: i32,
     pub part_id: i32,
+    /// Set if the MPN is an approved alternate
+    #[serde(default)]
+    pub alternate_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -37,6 +46,7 @@ pub struct Shortage {
     pub pid: i32,
     pub pn: String,
     pub mpn: String,
+    pub manufacturer: String,
     pub desc: String,
     pub have: i32,
     pub needed: i32,
@@ -99,6 +109,7 @@ pub fn update_from_file(app: &mut crate::Application, filename: &str) {
             notes,
             part_ver: &record.part_ver,
             part_id: &record.part_id,
+            alternate_id: record.alternate_id.as_ref(),
         };
 
         // Then update the entry as needed
@@ -110,6 +121,26 @@ pub fn update_from_file(app: &mut crate::Application, filename: &str) {
     }
 }
 
+/// Finds what an MPN was received for. Either a part's own MPN or one of its approved alternates.
+fn find_received(
+    conn: &SqliteConnection,
+    mpn: &str,
+    manufacturer: Option<&str>,
+) -> std::result::Result<Vec<(Part, Option<PartAlternate>)>, diesel::result::Error> {
+    let mut found: Vec<(Part, Option<PartAlternate>)> =
+        find_parts_by_mpn_and_manufacturer(conn, mpn, manufacturer)?
+            .into_iter()
+            .map(|p| (p, None))
+            .collect();
+
+    for alternate in find_part_alternates_by_mpn_and_manufacturer(conn, mpn, manufacturer)? {
+        let part = find_part_by_id(conn, &alternate.part_id)?;
+        found.push((part, Some(alternate)));
+    }
+
+    Ok(found)
+}
+
 pub fn create_from_file(app: &mut crate::Application, filename: &str) {
     println!("{:?}", app.config);
     println!("{:?}", filename);
@@ -128,10 +159,10 @@ pub fn create_from_file(app: &mut crate::Application, filename: &str) {
 
         // Check if part number exists
         // Uses MPN as it's the common denominator between this and Digikey/Arrow/Mouser etc.
-        let part = find_part_by_mpn(&app.conn, &record.mpn);
+        let parts = find_received(&app.conn, &record.mpn, record.manufacturer.as_deref());
 
         // If theres an error exit so the user can fix the problem.
-        match part {
+        match parts {
             Err(e) => {
                 println!(
                     "{} was not found! No changes were made. Error: {}",
@@ -139,6 +170,17 @@ pub fn create_from_file(app: &mut crate::Application, filename: &str) {
                 );
                 std::process::exit(1);
             }
+            Ok(parts) if parts.is_empty() => {
+                println!("{} was not found! No changes were made.", record.mpn);
+                std::process::exit(1);
+            }
+            Ok(parts) if parts.len() > 1 => {
+                println!(
+                    "{} is made by more than one manufacturer. Add a manufacturer column. No changes were made.",
+                    record.mpn
+                );
+                std::process::exit(1);
+            }
             _ => {
                 continue;
             }
@@ -157,7 +199,12 @@ pub fn create_from_file(app: &mut crate::Application, filename: &str) {
         let notes = record.notes.as_deref();
 
         // Check if part number exists
-        let part = find_part_by_mpn(&app.conn, &record.mpn).expect("Unable to get part.");
+        let (part, alternate) =
+            find_received(&app.conn, &record.mpn, record.manufacturer.as_deref())
+                .expect("Unable to get part.")
+                .remove(0);
+
+        let alternate_id = alternate.map(|a| a.id);
 
         // Commits change
         let entry = NewUpdateInventoryEntry {
@@ -167,13 +214,17 @@ pub fn create_from_file(app: &mut crate::Application, filename: &str) {
             quantity: &quantity,
             consumed: &0,
             notes,
+            alternate_id: alternate_id.as_ref(),
         };
 
         // Finally create the inventory if all look ok!
         create_inventory(&app.conn, &entry).expect("Unable to create inventory item.");
 
         // Print out that it was successful
-        println!("Created inventory for {}!", part.pn);
+        match alternate_id {
+            Some(_) => println!("Created inventory for {} ({})!", part.pn, record.mpn),
+            None => println!("Created inventory for {}!", part.pn),
+        }
     }
 }
 
@@ -193,6 +244,27 @@ pub fn create(app: &mut crate::Application) {
         }
     };
 
+    // Which MPN was received. Blank is the part's own.
+    let alternates =
+        find_part_alternates_by_part_id(&app.conn, &part.id).expect("Unable to get alternates!");
+
+    let mut alternate = None;
+    if !alternates.is_empty() {
+        let question = format!("Enter MPN (blank for {}): ", part.mpn);
+        let mpn = app.prompt.ask_text_entry(&question);
+        let mpn = mpn.trim();
+
+        if !mpn.is_empty() && mpn != part.mpn {
+            alternate = match alternates.iter().find(|a| a.mpn == mpn) {
+                Some(x) => Some(x),
+                None => {
+                    println!("{} is not an approved alternate of {}", mpn, part.pn);
+                    std::process::exit(1);
+                }
+            };
+        }
+    }
+
     // Then an ajustment value
     let adj = app.prompt.ask_text_entry("Enter adjustment value: ");
     let adj: i32 = adj.trim().parse().expect("Invalid adjustment!");
@@ -205,6 +277,9 @@ pub fn create(app: &mut crate::Application) {
     let notes = app.prompt.ask_text_entry("Enter notes: ");
 
     println!("Part number: {}", part.pn);
+    if let Some(alternate) = alternate {
+        println!("Alternate: {}", alternate.mpn);
+    }
     println!("Ajustment: {}", adj);
     println!("Price: ${}", price);
     println!("Notes: {}", notes);
@@ -220,6 +295,7 @@ pub fn create(app: &mut crate::Application) {
             quantity: &adj,
             consumed: &0,
             notes: Some(&notes),
+            alternate_id: alternate.map(|a| &a.id),
         };
 
         create_inventory(&app.conn, &entry).expect("Unable to create inventory item.");
@@ -238,6 +314,7 @@ pub fn show(app: &mut crate::Application, show_all_entries: bool) {
 
     table.add_row(row![
         "PN",
+        "MPN",
         "Desc",
         "Qty",
         "Consumed",
@@ -253,9 +330,11 @@ pub fn show(app: &mut crate::Application, show_all_entries: bool) {
 
         // Check if part number exists
         let part = find_part_by_id(&app.conn, &inventory.part_id).expect("Unable to get part.");
+        let mpn = inventory_mpn(&app.conn, &part, &inventory).expect("Unable to get alternate.");
 
         table.add_row(row![
             part.pn,
+            mpn,
             part.descr,
             inventory.quantity,
             inventory.consumed,
@@ -274,6 +353,18 @@ pub fn show(app: &mut crate::Application, show_all_entries: bool) {
     }
 }
 
+/// The MPN that was received. Either the part's or an approved alternate's.
+fn inventory_mpn(
+    conn: &SqliteConnection,
+    part: &Part,
+    inventory: &Inventory,
+) -> std::result::Result<String, diesel::result::Error> {
+    match inventory.alternate_id {
+        Some(id) => Ok(find_part_alternate_by_id(conn, &id)?.mpn),
+        None => Ok(part.mpn.clone()),
+    }
+}
+
 // TODO: show shortage by build ID
 // Defualt hide non-short items. Option to view all.
 pub fn show_shortage(app: &mut crate::Application, show_all_entries: bool) {
@@ -281,9 +372,18 @@ pub fn show_shortage(app: &mut crate::Application, show_all_entries: bool) {
     let mut table = Table::new();
 
     // Print out the shortages in table format.
-    table.add_row(row!["PID", "PN", "MPN", "Desc", "Have", "Needed", "Short",]);
+    table.add_row(row![
+        "PID",
+        "PN",
+        "MPN",
+        "Manufacturer",
+        "Desc",
+        "Have",
+        "Needed",
+        "Short",
+    ]);
 
-    let shortages = get_shortages(app, show_all_entries);
+    let shortages = get_shortages(&app.conn, show_all_entries);
 
     let shortages = match shortages {
         Ok(x) => x,
@@ -298,6 +398,7 @@ pub fn show_shortage(app: &mut crate::Application, show_all_entries: bool) {
             entry.pid,
             entry.pn,
             entry.mpn,
+            entry.manufacturer,
             entry.desc,
             entry.have,
             entry.needed,
@@ -333,17 +434,19 @@ pub fn export_to_file(app: &mut crate::Application, filename: &str, export_all:
 
         // Grabs the part information
         let part = find_part_by_id(&app.conn, &entry.part_id).unwrap();
+        let mpn = inventory_mpn(&app.conn, &part, &entry).unwrap();
 
         // Create a new entry
         let inventory_entry = InventoryEntry {
             id: entry.id,
-            mpn: part.mpn,
+            mpn,
             quantity: entry.quantity,
             consumed: entry.consumed,
             unit_price: entry.unit_price,
             notes: entry.notes,
             part_ver: entry.part_ver,
             part_id: entry.part_id,
+            alternate_id: entry.alternate_id,
         };
 
         wtr.serialize(inventory_entry)
@@ -356,7 +459,7 @@ pub fn export_to_file(app: &mut crate::Application, filename: &str, export_all:
 
 // Export shortages to csv
 pub fn export_shortages_to_file(app: &mut crate::Application, filename: &str) {
-    let shortages = get_shortages(app, false).expect("Unable to get shortage report.");
+    let shortages = get_shortages(&app.conn, false).expect("Unable to get shortage report.");
 
     let file = File::create(filename).unwrap();
     let file = BufWriter::new(file);
@@ -364,24 +467,126 @@ pub fn export_shortages_to_file(app: &mut crate::Application, filename: &str) {
     // Create CSV writer
     let mut wtr = csv::Writer::from_writer(file);
 
+    // One column per supplier so the part numbers can go straight into a cart
+    let suppliers = find_suppliers(&app.conn).expect("Unable to get suppliers.");
+
+    let mut header: Vec<String> = [
+        "pid",
+        "pn",
+        "mpn",
+        "manufacturer",
+        "desc",
+        "have",
+        "needed",
+        "short",
+        "quantity",
+        "notes",
+        "unit_price",
+    ]
+    .iter()
+    .map(|x| x.to_string())
+    .collect();
+
+    for supplier in &suppliers {
+        header.push(format!("{}_pn", supplier.name.to_lowercase()));
+    }
+
+    wtr.write_record(&header).expect("Unable to write header.");
+
     // Iterate and add to csv
     for shortage in shortages {
-        wtr.serialize(shortage).expect("Unable to serialize.");
+        let mut record = vec![
+            shortage.pid.to_string(),
+            shortage.pn,
+            shortage.mpn,
+            shortage.manufacturer,
+            shortage.desc,
+            shortage.have.to_string(),
+            shortage.needed.to_string(),
+            shortage.short.to_string(),
+            shortage.quantity.map(|x| x.to_string()).unwrap_or_default(),
+            shortage.notes.unwrap_or_default(),
+            shortage
+                .unit_price
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+        ];
+
+        // Supplier part numbers. Blank if there isn't one
+        let entries = find_part_suppliers_by_part_id(&app.conn, &shortage.pid)
+            .expect("Unable to get supplier part numbers.");
+
+        for supplier in &suppliers {
+            let supplier_pn = entries
+                .iter()
+                .find(|e| e.supplier_id == supplier.id)
+                .map(|e| e.supplier_pn.clone())
+                .unwrap_or_default();
+
+            record.push(supplier_pn);
+        }
+
+        wtr.write_record(&record).expect("Unable to serialize.");
         wtr.flush().expect("Unable to flush");
     }
 
     println!("Shortages exported to {}", filename);
 }
 
+/// Adds to the need for a part, creating its entry if it's not in the list yet
+fn add_shortage(
+    conn: &SqliteConnection,
+    shortages: &mut Vec<Shortage>,
+    part_id: &i32,
+    quantity: i32,
+) -> std::result::Result<(), diesel::result::Error> {
+    // Check in shortage list, add to the need if that item exists
+    if let Some(entry) = shortages.iter_mut().find(|entry| entry.pid == *part_id) {
+        entry.needed += quantity;
+        entry.short = (entry.needed - entry.have).max(0);
+        return Ok(());
+    }
+
+    // Serach for part in inventory. Do calculations as necessary.
+    // Stock of approved alternates is stored against the part so it counts too.
+    let inventory_quantity: i32 = find_inventories_by_part_id(conn, part_id)?
+        .iter()
+        .map(|entry| entry.quantity)
+        .sum();
+
+    // Get the part for more info
+    let part = find_part_by_id(conn, part_id)?;
+
+    // Create shortage item
+    let shortage = Shortage {
+        pid: *part_id,
+        manufacturer: find_manufacturer_name(conn, &part.manufacturer_id)?,
+        pn: part.pn,
+        mpn: part.mpn,
+        desc: part.descr,
+        have: inventory_quantity,
+        needed: quantity,
+        short: (quantity - inventory_quantity).max(0),
+        unit_price: None,
+        notes: None,
+        quantity: None,
+    };
+
+    // Add to shortage list
+    shortages.push(shortage);
+
+    Ok(())
+}
+
 pub fn get_shortages(
-    app: &mut crate::Application,
+    conn: &SqliteConnection,
     show_all_entries: bool,
 ) -> std::result::Result<Vec<Shortage>, diesel::result::Error> {
     use crate::schema::*;
 
     let results = builds::dsl::builds
         .filter(builds::dsl::complete.eq(0)) // Only show un-finished builds
-        .load::<Build>(&app.conn);
+        .load::<Build>(conn);
 
     // Return the error if there was an issue
     let results = match results {
@@ -391,105 +596,86 @@ pub fn get_shortages(
 
     let mut shortages: Vec<Shortage> = Vec::new();
 
+    // BOM lines that allow substitutes
+    let mut substitutable: Vec<bom::Requirement> = Vec::new();
+
+    // Sub-assembly stock shared between all the builds
+    let mut stock = HashMap::new();
+
     // Iterate though the builds,
     // Create a table of all parts and computed inventory
     // and shortages (indicated in - or + numbers)
     for build in results {
-        // First get the parts.
-        let bom_list = parts_parts::dsl::parts_parts
-            .filter(parts_parts::dsl::bom_part_id.eq(build.part_id))
-            .filter(parts_parts::dsl::bom_ver.eq(build.part_ver))
-            .load::<PartsPart>(&app.conn);
-
-        // Return the error if there was an issue
-        let bom_list = match bom_list {
-            Ok(x) => x,
-            Err(e) => return Err(e),
-        };
+        // Everything needed, with sub-assemblies exploded as necessary
+        let requirements = bom::get_requirements(
+            conn,
+            &build.part_id,
+            &build.part_ver,
+            build.quantity,
+            Some(&build.id),
+            &mut stock,
+        )?;
 
         // Iterate though the results and check inventory
-        for bom_list_entry in bom_list {
-            // Skip if nostuff is set
-            if bom_list_entry.nostuff == 1 {
-                continue;
+        for requirement in requirements {
+            add_shortage(
+                conn,
+                &mut shortages,
+                &requirement.part_id,
+                requirement.quantity,
+            )?;
+
+            if !requirement.substitutes.is_empty() {
+                substitutable.push(requirement);
             }
+        }
+    }
 
-            // Serach for part in inventory. Do calculations as necessary.
-            let mut inventory_quantity = 0;
-
-            // Get the inventory entries
-            let inventory_entries = find_inventories_by_part_id(&app.conn, &bom_list_entry.part_id);
+    // Substitutes cover what's still short once everything else is counted
+    for requirement in substitutable {
+        let mut remaining = requirement.quantity;
 
-            // Return the error if there was an issue
-            let inventory_entries = match inventory_entries {
-                Ok(x) => x,
-                Err(e) => return Err(e),
-            };
+        for substitute in &requirement.substitutes {
+            let short = shortages
+                .iter()
+                .find(|entry| entry.pid == requirement.part_id)
+                .map_or(0, |entry| entry.short)
+                .min(remaining);
 
-            // Calculate the quantity
-            for entry in inventory_entries {
-                inventory_quantity += entry.quantity;
+            if short == 0 {
+                break;
             }
 
-            // This struct has, inventory quantity (+/-), quantity needed, part name
-            let mut found_in_shortage_list = false;
-
-            // Check in shortage list, do some calculations if that item exists
-            for mut entry in &mut shortages {
-                if entry.pid == bom_list_entry.part_id {
-                    // Calculate shortage based on known need plus new quantity
-                    let mut short = entry.needed + bom_list_entry.quantity - inventory_quantity;
-
-                    // Set short to 0 if > 0
-                    if short < 0 {
-                        short = 0;
-                    }
-
-                    // Then set the variables
-                    entry.needed += build.quantity * bom_list_entry.quantity;
-                    entry.short = short;
-                    found_in_shortage_list = true;
-                    break;
-                }
-            }
+            add_shortage(conn, &mut shortages, substitute, 0)?;
 
-            if !found_in_shortage_list {
-                // Get the part for more info
-                let part = find_part_by_id(&app.conn, &bom_list_entry.part_id);
+            let entry = shortages
+                .iter_mut()
+                .find(|entry| entry.pid == *substitute)
+                .expect("Substitute was just added");
+            let used = (entry.have - entry.needed).max(0).min(short);
 
-                let part = match part {
-                    Ok(x) => x,
-                    Err(e) => return Err(e),
-                };
+            if used == 0 {
+                continue;
+            }
 
-                // Calculate the amount short
-                let mut short = (build.quantity * bom_list_entry.quantity) - inventory_quantity;
+            // Move the need over to the substitute
+            entry.needed += used;
+            entry.short = (entry.needed - entry.have).max(0);
 
-                // To 0 if not short
-                if short < 0 {
-                    short = 0;
-                }
+            let entry = shortages
+                .iter_mut()
+                .find(|entry| entry.pid == requirement.part_id)
+                .expect("Part was already added");
+            entry.needed -= used;
+            entry.short = (entry.needed - entry.have).max(0);
 
-                // Create shortage item
-                let shortage = Shortage {
-                    pid: bom_list_entry.part_id,
-                    pn: part.pn,
-                    mpn: part.mpn,
-                    desc: part.descr,
-                    have: inventory_quantity,
-                    needed: build.quantity * bom_list_entry.quantity,
-                    short,
-                    unit_price: None,
-                    notes: None,
-                    quantity: None,
-                };
-
-                // Add to shortage list
-                shortages.push(shortage);
-            }
+            remaining -= used;
         }
     }
 
+    // Substitutes that weren't needed
+    shortages.retain(|entry| entry.needed > 0);
+
     // Remove items that are short = 0
     if !show_all_entries {
         let mut only_shortages: Vec<Shortage> = Vec::new();
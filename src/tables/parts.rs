@@ -3,6 +3,9 @@ extern crate diesel;
 use prettytable::{row, Table};
 use serde::Deserialize;
 
+use chrono::NaiveDate;
+
+use crate::config::OnPartChange;
 use crate::{models::*, *};
 use diesel::prelude::*;
 
@@ -14,23 +17,93 @@ struct Record {
     pn: String,
     mpn: String,
     desc: String,
+    #[serde(default)]
+    manufacturer: Option<String>,
+    #[serde(default)]
+    val: Option<String>,
+    #[serde(default)]
+    lifecycle: Option<String>,
+    #[serde(default)]
+    last_time_buy: Option<String>,
+}
+
+/// A lifecycle state with its last time buy date
+type LifecycleEntry = (Lifecycle, Option<NaiveDate>);
+
+impl Record {
+    /// A last time buy date on its own means the part is `ltb`
+    fn lifecycle(&self) -> anyhow::Result<Option<LifecycleEntry>> {
+        let last_time_buy = match &self.last_time_buy {
+            Some(date) => Some(parse_date(date)?),
+            None => None,
+        };
+
+        let lifecycle = match (&self.lifecycle, last_time_buy) {
+            (Some(lifecycle), _) => lifecycle.parse()?,
+            (None, Some(_)) => Lifecycle::Ltb,
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some((lifecycle, last_time_buy)))
+    }
+
+    /// Checks if anything in the record is different from the database
+    fn differs(
+        &self,
+        conn: &SqliteConnection,
+        found: &Part,
+        lifecycle: &Option<LifecycleEntry>,
+    ) -> bool {
+        let manufacturer = find_manufacturer_name(conn, &found.manufacturer_id).unwrap_or_default();
+
+        found.mpn != self.mpn
+            || found.descr != self.desc
+            || found.ver != 1
+            || (self.val.is_some() && found.val != self.val)
+            || self
+                .manufacturer
+                .as_ref()
+                .is_some_and(|m| !m.eq_ignore_ascii_case(&manufacturer))
+            || lifecycle.is_some_and(|(l, d)| found.lifecycle() != l || found.last_time_buy != d)
+    }
+}
+
+/// Dates are YYYY-MM-DD
+pub fn parse_date(date: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid date {}. Use YYYY-MM-DD", date))
+}
+
+/// Gets the id of a manufacturer, creating it if it's new. Blank is no manufacturer.
+fn find_manufacturer_id(app: &mut crate::Application, name: &Option<String>) -> Option<i32> {
+    let name = name.as_deref().map(str::trim).filter(|n| !n.is_empty())?;
+
+    let manufacturer =
+        find_or_create_manufacturer(&app.conn, name).expect("Unable to create manufacturer!");
+
+    Some(manufacturer.id)
 }
 
 pub fn create(app: &mut crate::Application) {
     // Get the input from stdin
     let pn = app.prompt.ask_text_entry("Part Number: ");
     let mpn = app.prompt.ask_text_entry("Manufacturer Part Number: ");
+    let manufacturer = app.prompt.ask_text_entry("Manufacturer (optional): ");
     let desc = app.prompt.ask_text_entry("Description: ");
     let ver = app.prompt.ask_text_entry("Version: ");
     let ver: i32 = ver.trim().parse().expect("Invalid version number!");
 
+    let manufacturer_id = find_manufacturer_id(app, &Some(manufacturer));
+
     // Create the part
     let part = NewUpdatePart {
         pn: &pn,
         mpn: &mpn,
         descr: &desc,
         ver: &ver,
+        val: None,
         mqty: &1,
+        manufacturer_id: manufacturer_id.as_ref(),
     };
 
     let found = find_part_by_pn(&app.conn, &pn);
@@ -60,12 +133,12 @@ pub fn rename(app: &mut crate::Application) {
     rename_part(&app.conn, &pn, &newpn).expect("Unable to change pn");
 }
 
-pub fn create_by_csv(app: &mut crate::Application, filename: &str) {
+pub fn create_by_csv(app: &mut crate::Application, filename: &str, on_part_change: OnPartChange) {
     // Open the file
     let file = File::open(filename).unwrap();
     let file = BufReader::new(file);
 
-    let mut records: Vec<Record> = Vec::new();
+    let mut records: Vec<(Record, Option<LifecycleEntry>)> = Vec::new();
 
     let mut rdr = csv::Reader::from_reader(file);
 
@@ -76,100 +149,1352 @@ pub fn create_by_csv(app: &mut crate::Application, filename: &str) {
         // deserialization.
         let record: Record = result.expect("Unable to deserialize.");
         println!("Processing: {:?}", record);
-        records.push(record);
+
+        let lifecycle = match record.lifecycle() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("{}: {}. No changes have been made.", record.pn, e);
+                std::process::exit(1);
+            }
+        };
+
+        records.push((record, lifecycle));
+    }
+
+    // Check everything first so nothing is written if it fails
+    if on_part_change == OnPartChange::Fail {
+        let changed: Vec<&str> = records
+            .iter()
+            .filter(|(record, lifecycle)| {
+                find_part_by_pn(&app.conn, &record.pn)
+                    .map(|found| record.differs(&app.conn, &found, lifecycle))
+                    .unwrap_or(false)
+            })
+            .map(|(record, _)| record.pn.as_str())
+            .collect();
+
+        if !changed.is_empty() {
+            println!(
+                "Parts are different in the database: {}. No changes have been made.",
+                changed.join(", ")
+            );
+            std::process::exit(1);
+        }
     }
 
     // Iterate through all the records.
-    for record in records {
+    for (record, lifecycle) in records {
+        let found = find_part_by_pn(&app.conn, &record.pn);
+
+        // If already found ask if it should be updated
+        if let Ok(found) = &found {
+            // Compare the two make sure they're different
+            if !record.differs(&app.conn, found, &lifecycle) {
+                continue;
+            }
+
+            let question = format!("{} already exists! Would you like to update it?", record.pn);
+
+            let update = match on_part_change {
+                OnPartChange::Update => true,
+                OnPartChange::Keep | OnPartChange::Fail => false,
+                OnPartChange::Ask => {
+                    let manufacturer = find_manufacturer_name(&app.conn, &found.manufacturer_id)
+                        .expect("Unable to get manufacturer!");
+
+                    // Create the table
+                    let mut table = Table::new();
+                    table.add_row(row![
+                        "Current:",
+                        found.pn,
+                        found.mpn,
+                        manufacturer,
+                        found.descr,
+                        found.val.as_deref().unwrap_or_default(),
+                        found.ver
+                    ]);
+                    table.add_row(row![
+                        "Change to:",
+                        record.pn,
+                        record.mpn,
+                        record.manufacturer.as_deref().unwrap_or_default(),
+                        record.desc,
+                        record.val.as_deref().unwrap_or_default(),
+                        1
+                    ]);
+                    table.printstd();
+
+                    app.prompt.ask_yes_no_question(&question)
+                }
+            };
+
+            if !update {
+                continue;
+            }
+        }
+
+        let manufacturer_id = find_manufacturer_id(app, &record.manufacturer);
+
         // Create a new part from the CSV file
         let part = models::NewUpdatePart {
             pn: &record.pn,
             mpn: &record.mpn,
             descr: &record.desc,
             ver: &1,
+            val: record.val.as_deref(),
             mqty: &1,
+            manufacturer_id: manufacturer_id.as_ref(),
         };
 
-        let found = find_part_by_pn(&app.conn, &part.pn);
-
-        // If already found ask if it should be updated
         if let Ok(found) = found {
-            // Compare the two make sure they're different
-            if found.mpn != part.mpn || found.descr != part.descr || found.ver != *part.ver {
-                let question = format!("{} already exists! Would you like to update it?", part.pn);
-
-                // Create the table
-                let mut table = Table::new();
-                table.add_row(row![
-                    "Current:",
-                    found.pn,
-                    found.mpn,
-                    found.descr,
-                    found.ver
-                ]);
-                table.add_row(row!["Change to:", part.pn, part.mpn, part.descr, part.ver]);
-                table.printstd();
-
-                let update = app.prompt.ask_yes_no_question(&question);
-
-                // Update if they said yes.
-                if update {
-                    update_part(&app.conn, &found.id, &part).expect("Unable to update part!");
-
-                    // Check for success
-                    println!("{} updated!", part.pn);
-                }
-            }
+            update_part(&app.conn, &found.id, &part).expect("Unable to update part!");
+            save_lifecycle(app, &found.id, &lifecycle);
+
+            // Check for success
+            println!("{} updated!", part.pn);
         } else {
             println!("Creating: {:?}", part);
             create_part(&app.conn, &part).expect("Unable to create part!");
+
+            let created = find_part_by_pn(&app.conn, part.pn).expect("Unable to find part!");
+            save_lifecycle(app, &created.id, &lifecycle);
+        }
+    }
+}
+
+/// Saves the lifecycle from a CSV record if it had one
+fn save_lifecycle(app: &mut crate::Application, id: &i32, lifecycle: &Option<LifecycleEntry>) {
+    if let Some((lifecycle, last_time_buy)) = lifecycle {
+        let entry = UpdatePartLifecycle {
+            lifecycle: lifecycle.as_str(),
+            last_time_buy: *last_time_buy,
+        };
+
+        set_part_lifecycle(&app.conn, id, &entry).expect("Unable to set lifecycle!");
+    }
+}
+
+/// Sets where a part is in its life. The last time buy date is kept unless it's active again.
+pub fn set_lifecycle(
+    app: &mut crate::Application,
+    pn: &str,
+    lifecycle: Lifecycle,
+    last_time_buy: &Option<String>,
+) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
         }
+    };
+
+    let last_time_buy = match last_time_buy {
+        Some(date) => match parse_date(date) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None if lifecycle == Lifecycle::Active => None,
+        None => part.last_time_buy,
+    };
+
+    let entry = UpdatePartLifecycle {
+        lifecycle: lifecycle.as_str(),
+        last_time_buy,
+    };
+
+    set_part_lifecycle(&app.conn, &part.id, &entry).expect("Unable to set lifecycle!");
+
+    match last_time_buy {
+        Some(date) => println!("{} is {} (last time buy {})", pn, lifecycle, date),
+        None => println!("{} is {}", pn, lifecycle),
     }
 }
 
-pub fn delete(app: &mut crate::Application) {
+/// Everything that refers to a part
+pub struct References {
+    /// BOM lines using the part
+    pub lines: Vec<PartsPart>,
+    /// The part's own BOM
+    pub bom: Vec<PartsPart>,
+    pub inventories: Vec<Inventory>,
+    pub builds: Vec<Build>,
+    /// Lines it can stand in for
+    pub substitutions: Vec<BomSubstitution>,
+}
+
+impl References {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+            && self.bom.is_empty()
+            && self.inventories.is_empty()
+            && self.builds.is_empty()
+            && self.substitutions.is_empty()
+    }
+}
+
+/// Finds the BOMs, inventory and builds that refer to a part
+pub fn find_references(
+    conn: &SqliteConnection,
+    part_id: &i32,
+) -> std::result::Result<References, diesel::result::Error> {
+    use crate::schema::{bom_substitutions, builds, parts_parts};
+
+    Ok(References {
+        lines: find_bom_lines_by_part_id(conn, part_id)?,
+        bom: parts_parts::dsl::parts_parts
+            .filter(parts_parts::dsl::bom_part_id.eq(part_id))
+            .load::<PartsPart>(conn)?,
+        inventories: find_inventories_by_part_id(conn, part_id)?,
+        builds: builds::dsl::builds
+            .filter(builds::dsl::part_id.eq(part_id))
+            .load::<Build>(conn)?,
+        substitutions: bom_substitutions::dsl::bom_substitutions
+            .filter(bom_substitutions::dsl::part_id.eq(part_id))
+            .load::<BomSubstitution>(conn)?,
+    })
+}
+
+/// Deletes a part along with everything that refers to it so nothing is left behind
+pub fn delete_part_and_references(
+    conn: &SqliteConnection,
+    part: &Part,
+) -> std::result::Result<(), diesel::result::Error> {
+    use crate::schema::*;
+
+    let references = find_references(conn, &part.id)?;
+
+    conn.transaction(|| {
+        for line in &references.lines {
+            delete_bom_line_item(conn, &line.id)?;
+        }
+
+        let mut versions: Vec<i32> = references.bom.iter().map(|l| l.bom_ver).collect();
+        versions.sort_unstable();
+        versions.dedup();
+        for version in versions {
+            delete_bom_list_by_id_and_ver(conn, &part.id, &version)?;
+        }
+
+        for substitution in &references.substitutions {
+            delete_bom_substitution(conn, &substitution.id)?;
+        }
+
+        // Substitutions only for its builds go with them
+        let build_ids: Vec<i32> = references.builds.iter().map(|b| b.id).collect();
+        diesel::delete(
+            bom_substitutions::dsl::bom_substitutions
+                .filter(bom_substitutions::dsl::build_id.eq_any(&build_ids)),
+        )
+        .execute(conn)?;
+
+        for build in &references.builds {
+            delete_build(conn, &build.id)?;
+        }
+
+        diesel::delete(inventories::dsl::inventories.filter(inventories::dsl::part_id.eq(part.id)))
+            .execute(conn)?;
+        diesel::delete(aliases::dsl::aliases.filter(aliases::dsl::part_id.eq(part.id)))
+            .execute(conn)?;
+        diesel::delete(
+            part_suppliers::dsl::part_suppliers.filter(part_suppliers::dsl::part_id.eq(part.id)),
+        )
+        .execute(conn)?;
+        diesel::delete(
+            part_attributes::dsl::part_attributes.filter(part_attributes::dsl::part_id.eq(part.id)),
+        )
+        .execute(conn)?;
+        diesel::delete(
+            part_alternates::dsl::part_alternates.filter(part_alternates::dsl::part_id.eq(part.id)),
+        )
+        .execute(conn)?;
+
+        delete_part(conn, &part.id)?;
+
+        Ok(())
+    })
+}
+
+/// Finds the open builds of the BOM versions that have these lines. (BOM PN, version, build ids)
+fn find_open_builds_using(
+    conn: &SqliteConnection,
+    lines: &[PartsPart],
+) -> std::result::Result<Vec<(String, i32, Vec<String>)>, diesel::result::Error> {
+    let mut versions: Vec<(i32, i32)> = lines.iter().map(|l| (l.bom_part_id, l.bom_ver)).collect();
+    versions.sort_unstable();
+    versions.dedup();
+
+    let mut open = Vec::new();
+
+    for (bom_part_id, bom_ver) in versions {
+        let builds = find_open_builds_by_id_and_ver(conn, &bom_part_id, &bom_ver)?;
+
+        if !builds.is_empty() {
+            let bom = find_part_by_id(conn, &bom_part_id)?;
+            let ids = builds.iter().map(|b| b.id.to_string()).collect();
+            open.push((bom.pn, bom_ver, ids));
+        }
+    }
+
+    Ok(open)
+}
+
+/// Deletes a part. Parts that are used anywhere are only deleted with force, along with
+/// everything that uses them. Archiving keeps the history instead.
+pub fn delete(app: &mut crate::Application, force: bool) {
     let part = app.prompt.ask_text_entry("Part Number: ");
 
     // First find the parts.
     let part = find_part_by_pn(&app.conn, &part).expect("Unable to find part!");
 
+    let references = find_references(&app.conn, &part.id).expect("Unable to get references!");
+
+    if !references.is_empty() {
+        if !references.lines.is_empty() {
+            where_used(app, &part.pn);
+        }
+
+        let on_hand: i32 = references.inventories.iter().map(|i| i.quantity).sum();
+        let mut versions: Vec<i32> = references.bom.iter().map(|l| l.bom_ver).collect();
+        versions.sort_unstable();
+        versions.dedup();
+
+        let mut table = Table::new();
+        table.add_row(row!["REFERENCE", "COUNT"]);
+        table.add_row(row!["BOM lines", references.lines.len()]);
+        table.add_row(row!["Own BOM versions", versions.len()]);
+        table.add_row(row![
+            format!("Inventory entries ({} on hand)", on_hand),
+            references.inventories.len()
+        ]);
+        table.add_row(row!["Builds", references.builds.len()]);
+        table.add_row(row!["Substitutions", references.substitutions.len()]);
+        table.printstd();
+
+        if !force {
+            println!(
+                "{} is still in use. Archive it with `parts archive {}` or use --force to delete it and everything above.",
+                part.pn, part.pn
+            );
+            std::process::exit(1);
+        }
+
+        // Not even with force. Versions used by open builds can't be changed.
+        let open =
+            find_open_builds_using(&app.conn, &references.lines).expect("Unable to get builds!");
+
+        // Neither can its own builds in progress
+        let own: Vec<String> = references
+            .builds
+            .iter()
+            .filter(|b| b.complete == 0)
+            .map(|b| b.id.to_string())
+            .collect();
+
+        if !open.is_empty() || !own.is_empty() {
+            for (bom, version, ids) in &open {
+                println!(
+                    "{} version {} is used by open build(s) {}.",
+                    bom,
+                    version,
+                    ids.join(", ")
+                );
+            }
+            if !own.is_empty() {
+                println!("{} has open build(s) {}.", part.pn, own.join(", "));
+            }
+            println!(
+                "Unable to delete {}. Complete or delete those builds first.",
+                part.pn
+            );
+            std::process::exit(1);
+        }
+    }
+
     // Then ask the user to confirm they want to delete
-    let question = format!("Would you like to delete {}?", part.pn);
+    let question = if references.is_empty() {
+        format!("Would you like to delete {}?", part.pn)
+    } else {
+        format!("Would you like to delete {} and everything above?", part.pn)
+    };
     let delete = app.prompt.ask_yes_no_question(&question);
 
     // THEN, delete if they said yes.
     if delete {
         // Delete the part
-        let res = delete_part(&app.conn, &part.id);
+        if let Err(e) = delete_part_and_references(&app.conn, &part) {
+            println!("Unable to delete {}: {}", part.pn, e);
+            std::process::exit(1);
+        }
 
-        // Depending on the result show the feedback
-        if res.is_err() {
-            panic!("Error deleting part {}.", part.pn);
+        println!("Deleted {}", part.pn);
+    }
+}
+
+/// Archives a part. It's kept for history but hidden from `parts show` and `parts search`.
+pub fn archive(app: &mut crate::Application, pn: &str, restore: bool) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let archived = if restore { 0 } else { 1 };
+
+    set_part_archived(&app.conn, &part.id, &archived).expect("Unable to archive part!");
+
+    if restore {
+        println!("Restored {}", part.pn);
+    } else {
+        println!("Archived {}", part.pn);
+    }
+}
+
+/// Everything that moves when one part is merged into another
+pub struct MergePlan {
+    pub keep: Part,
+    pub drop: Part,
+    /// BOM lines using the dropped part
+    pub lines: Vec<PartsPart>,
+    /// The dropped part's own BOM. Only if the kept part doesn't have one.
+    pub bom: Vec<PartsPart>,
+    pub inventories: Vec<Inventory>,
+    pub builds: Vec<Build>,
+    pub alternates: Vec<PartAlternate>,
+    /// The dropped part's MPN becomes an alternate unless it's the same as the kept part's
+    pub keeps_mpn: bool,
+}
+
+/// Checks two parts can be merged and gets what would move
+pub fn plan_merge(conn: &SqliteConnection, keep: &str, drop: &str) -> anyhow::Result<MergePlan> {
+    let keep =
+        find_part_by_pn(conn, keep).map_err(|_| anyhow::anyhow!("Unable to find part {}", keep))?;
+    let drop =
+        find_part_by_pn(conn, drop).map_err(|_| anyhow::anyhow!("Unable to find part {}", drop))?;
+
+    if keep.id == drop.id {
+        return Err(anyhow::anyhow!("A part can't be merged into itself"));
+    }
+
+    // Neither can end up on its own BOM
+    for (a, b) in [(&keep, &drop), (&drop, &keep)].iter() {
+        if tables::bom::where_used(conn, &a.id)?
+            .iter()
+            .any(|u| u.bom.id == b.id)
+        {
+            return Err(anyhow::anyhow!("{} is used by {}", a.pn, b.pn));
+        }
+    }
+
+    let references = find_references(conn, &drop.id)?;
+    if !references.bom.is_empty() && !find_references(conn, &keep.id)?.bom.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} and {} both have a BOM. Only one can.",
+            keep.pn,
+            drop.pn
+        ));
+    }
+
+    Ok(MergePlan {
+        lines: references.lines,
+        bom: references.bom,
+        inventories: references.inventories,
+        builds: references.builds,
+        alternates: find_part_alternates_by_part_id(conn, &drop.id)?,
+        keeps_mpn: drop.mpn == keep.mpn && drop.manufacturer_id == keep.manufacturer_id,
+        keep,
+        drop,
+    })
+}
+
+/// Moves everything from the dropped part to the kept part and deletes it. Its PN is kept
+/// as an alias so schematics that still use it import as the kept part.
+pub fn merge_parts(
+    conn: &SqliteConnection,
+    plan: &MergePlan,
+) -> std::result::Result<(), diesel::result::Error> {
+    use crate::schema::*;
+
+    let keep = &plan.keep;
+    let drop = &plan.drop;
+
+    conn.transaction(|| {
+        // BOM lines. Ones next to a line of the kept part are combined with it.
+        for line in &plan.lines {
+            let target = find_bom_list_by_id_and_ver(conn, &line.bom_part_id, &line.bom_ver)?
+                .into_iter()
+                .find(|l| {
+                    l.part_id == keep.id
+                        && l.alias_id == line.alias_id
+                        && l.nostuff == line.nostuff
+                        && l.manual == line.manual
+                });
+
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    diesel::update(parts_parts::dsl::parts_parts.find(line.id))
+                        .set(parts_parts::dsl::part_id.eq(keep.id))
+                        .execute(conn)?;
+                    continue;
+                }
+            };
+
+            let mut designators: Vec<(String, Option<String>)> = Vec::new();
+            for id in [target.id, line.id].iter() {
+                for designator in find_refdes_by_line_id(conn, id)? {
+                    designators.push((designator.refdes, designator.val));
+                }
+            }
+
+            let update = NewPartsParts {
+                quantity: &(target.quantity + line.quantity),
+                bom_ver: &target.bom_ver,
+                refdes: &target.refdes,
+                nostuff: &target.nostuff,
+                bom_part_id: &target.bom_part_id,
+                part_id: &target.part_id,
+                alias_id: target.alias_id.as_ref(),
+                manual: &target.manual,
+            };
+            update_bom_line_item(conn, &target.id, &update)?;
+            set_bom_line_refdes(conn, &target.id, &designators)?;
+
+            diesel::update(
+                bom_substitutions::dsl::bom_substitutions
+                    .filter(bom_substitutions::dsl::line_id.eq(line.id)),
+            )
+            .set(bom_substitutions::dsl::line_id.eq(target.id))
+            .execute(conn)?;
+
+            delete_bom_line_item(conn, &line.id)?;
+        }
+
+        // Substitutions of the dropped part. A part can't stand in for itself.
+        diesel::update(
+            bom_substitutions::dsl::bom_substitutions
+                .filter(bom_substitutions::dsl::part_id.eq(drop.id)),
+        )
+        .set(bom_substitutions::dsl::part_id.eq(keep.id))
+        .execute(conn)?;
+
+        let line_ids: Vec<i32> = find_bom_lines_by_part_id(conn, &keep.id)?
+            .iter()
+            .map(|l| l.id)
+            .collect();
+
+        let mut seen: Vec<(i32, i32, Option<i32>)> = Vec::new();
+        for substitution in bom_substitutions::dsl::bom_substitutions
+            .filter(
+                bom_substitutions::dsl::part_id
+                    .eq(keep.id)
+                    .or(bom_substitutions::dsl::line_id.eq_any(&line_ids)),
+            )
+            .order(bom_substitutions::dsl::id)
+            .load::<BomSubstitution>(conn)?
+        {
+            let key = (
+                substitution.line_id,
+                substitution.part_id,
+                substitution.build_id,
+            );
+
+            let own = line_ids.contains(&substitution.line_id) && substitution.part_id == keep.id;
+
+            if own || seen.contains(&key) {
+                delete_bom_substitution(conn, &substitution.id)?;
+            } else {
+                seen.push(key);
+            }
+        }
+
+        // Its own BOM and the builds of it
+        if !plan.bom.is_empty() {
+            diesel::update(
+                parts_parts::dsl::parts_parts.filter(parts_parts::dsl::bom_part_id.eq(drop.id)),
+            )
+            .set(parts_parts::dsl::bom_part_id.eq(keep.id))
+            .execute(conn)?;
+
+            diesel::update(parts::dsl::parts.find(keep.id))
+                .set(parts::dsl::ver.eq(keep.ver.max(drop.ver)))
+                .execute(conn)?;
+        }
+
+        diesel::update(builds::dsl::builds.filter(builds::dsl::part_id.eq(drop.id)))
+            .set(builds::dsl::part_id.eq(keep.id))
+            .execute(conn)?;
+
+        // Alternates go after the kept part's own
+        let mut priority = find_part_alternates_by_part_id(conn, &keep.id)?
+            .iter()
+            .map(|a| a.priority)
+            .max()
+            .unwrap_or(0);
+
+        let own = if plan.keeps_mpn {
+            None
         } else {
-            println!("Deleted {}", part.pn);
+            priority += 1;
+            let alternate = NewUpdatePartAlternate {
+                mpn: &drop.mpn,
+                priority: &priority,
+                part_id: &keep.id,
+                manufacturer_id: drop.manufacturer_id.as_ref(),
+            };
+            create_part_alternate(conn, &alternate)?;
+
+            find_part_alternates_by_part_id(conn, &keep.id)?
+                .into_iter()
+                .find(|a| a.mpn == drop.mpn && a.manufacturer_id == drop.manufacturer_id)
+                .map(|a| a.id)
+        };
+
+        for alternate in &plan.alternates {
+            priority += 1;
+            diesel::update(part_alternates::dsl::part_alternates.find(alternate.id))
+                .set((
+                    part_alternates::dsl::part_id.eq(keep.id),
+                    part_alternates::dsl::priority.eq(priority),
+                ))
+                .execute(conn)?;
+        }
+
+        // Stock of the dropped part's own MPN is stock of that alternate now
+        for entry in &plan.inventories {
+            let alternate_id = entry.alternate_id.or(own);
+            let part_ver = if plan.bom.is_empty() {
+                keep.ver
+            } else {
+                entry.part_ver
+            };
+
+            diesel::update(inventories::dsl::inventories.find(entry.id))
+                .set((
+                    inventories::dsl::part_id.eq(keep.id),
+                    inventories::dsl::part_ver.eq(part_ver),
+                    inventories::dsl::alternate_id.eq(alternate_id),
+                ))
+                .execute(conn)?;
+        }
+
+        // Supplier PNs and attributes the kept part doesn't have
+        let suppliers = find_part_suppliers_by_part_id(conn, &keep.id)?;
+        for entry in find_part_suppliers_by_part_id(conn, &drop.id)? {
+            if suppliers.iter().any(|s| s.supplier_id == entry.supplier_id) {
+                delete_part_supplier(conn, &entry.id)?;
+            } else {
+                diesel::update(part_suppliers::dsl::part_suppliers.find(entry.id))
+                    .set(part_suppliers::dsl::part_id.eq(keep.id))
+                    .execute(conn)?;
+            }
+        }
+
+        let attributes = find_part_attributes_by_part_id(conn, &keep.id)?;
+        for entry in find_part_attributes_by_part_id(conn, &drop.id)? {
+            if attributes.iter().any(|a| a.name == entry.name) {
+                delete_part_attribute(conn, &drop.id, &entry.name)?;
+            } else {
+                diesel::update(part_attributes::dsl::part_attributes.find(entry.id))
+                    .set(part_attributes::dsl::part_id.eq(keep.id))
+                    .execute(conn)?;
+            }
+        }
+
+        // Aliases of it and then the dropped PN itself
+        diesel::update(aliases::dsl::aliases.filter(aliases::dsl::part_id.eq(drop.id)))
+            .set(aliases::dsl::part_id.eq(keep.id))
+            .execute(conn)?;
+
+        delete_part(conn, &drop.id)?;
+
+        let alias = NewUpdateAlias {
+            pn: &drop.pn,
+            quantity: &1,
+            part_id: &keep.id,
+        };
+
+        match find_alias_by_pn(conn, &drop.pn) {
+            Ok(existing) => update_alias(conn, &existing.id, &alias)?,
+            Err(_) => create_alias(conn, &alias)?,
+        };
+
+        Ok(())
+    })
+}
+
+/// Merges a duplicate part into the one that's kept after showing what moves
+pub fn merge(app: &mut crate::Application, keep: &str, drop: &str) {
+    let plan = match plan_merge(&app.conn, keep, drop) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Unable to merge {} into {}: {}", drop, keep, e);
+            std::process::exit(1);
         }
+    };
+
+    let mut table = Table::new();
+    table.add_row(row!["MOVING", "WHERE", "QUANTITY", "REFDES"]);
+
+    for line in &plan.lines {
+        let bom = find_part_by_id(&app.conn, &line.bom_part_id).expect("Unable to find BOM!");
+        table.add_row(row![
+            "BOM line",
+            format!("{} v{}", bom.pn, line.bom_ver),
+            line.quantity,
+            line.refdes
+        ]);
+    }
+
+    let mut versions: Vec<i32> = plan.bom.iter().map(|l| l.bom_ver).collect();
+    versions.sort_unstable();
+    versions.dedup();
+    for version in versions {
+        let count = plan.bom.iter().filter(|l| l.bom_ver == version).count();
+        table.add_row(row!["BOM", format!("v{}", version), count, ""]);
+    }
+
+    for entry in &plan.inventories {
+        let mpn = match entry.alternate_id {
+            Some(id) => {
+                find_part_alternate_by_id(&app.conn, &id)
+                    .expect("Unable to get alternate!")
+                    .mpn
+            }
+            None => plan.drop.mpn.clone(),
+        };
+        table.add_row(row![
+            "Inventory",
+            format!("{} ({})", entry.id, mpn),
+            entry.quantity,
+            ""
+        ]);
+    }
+
+    for build in &plan.builds {
+        table.add_row(row![
+            "Build",
+            format!("{} (v{})", build.id, build.part_ver),
+            build.quantity,
+            ""
+        ]);
     }
+
+    for alternate in &plan.alternates {
+        table.add_row(row!["Alternate", alternate.mpn, "", ""]);
+    }
+
+    if table.len() > 1 {
+        println!("Merging {} into {}:", plan.drop.pn, plan.keep.pn);
+        table.printstd();
+    } else {
+        println!("Nothing uses {}", plan.drop.pn);
+    }
+
+    // Stock of both is counted together after
+    let on_hand = |id: &i32| -> i32 {
+        find_inventories_by_part_id(&app.conn, id)
+            .expect("Unable to get inventory!")
+            .iter()
+            .map(|i| i.quantity)
+            .sum()
+    };
+    let (kept, dropped) = (on_hand(&plan.keep.id), on_hand(&plan.drop.id));
+    println!(
+        "On hand: {} {} + {} {} = {}",
+        kept,
+        plan.keep.pn,
+        dropped,
+        plan.drop.pn,
+        kept + dropped
+    );
+
+    if !plan.keeps_mpn {
+        println!("{} will be an alternate of {}", plan.drop.mpn, plan.keep.pn);
+    }
+    println!(
+        "{} will be deleted and kept as an alias of {}",
+        plan.drop.pn, plan.keep.pn
+    );
+
+    let question = format!("Merge {} into {}?", plan.drop.pn, plan.keep.pn);
+    if !app.prompt.ask_yes_no_question(&question) {
+        return;
+    }
+
+    if let Err(e) = merge_parts(&app.conn, &plan) {
+        println!(
+            "Unable to merge {} into {}: {}",
+            plan.drop.pn, plan.keep.pn, e
+        );
+        std::process::exit(1);
+    }
+
+    println!("Merged {} into {}", plan.drop.pn, plan.keep.pn);
 }
 
-pub fn show(app: &mut crate::Application) {
+/// Shows every part. Archived ones only if asked for.
+pub fn show(app: &mut crate::Application, show_archived: bool) {
     use crate::schema::*;
 
     // Create the table
     let mut table = Table::new();
 
-    let results = parts::dsl::parts
+    let results: Vec<models::Part> = parts::dsl::parts
         .load::<models::Part>(&app.conn)
-        .expect("Error loading parts");
+        .expect("Error loading parts")
+        .into_iter()
+        .filter(|p| show_archived || p.archived == 0)
+        .collect();
 
     println!("Displaying {} parts", results.len());
-    table.add_row(row!["PN", "MPN", "Desc", "Mqty", "Ver"]);
+    table.add_row(row![
+        "PN",
+        "MPN",
+        "Manufacturer",
+        "Desc",
+        "Val",
+        "Mqty",
+        "Ver",
+        "Lifecycle"
+    ]);
     for part in results {
-        table.add_row(row![part.pn, part.mpn, part.descr, part.mqty, part.ver]);
+        let manufacturer = find_manufacturer_name(&app.conn, &part.manufacturer_id)
+            .expect("Unable to get manufacturer!");
+
+        let lifecycle = if part.archived == 1 {
+            format!("{} (archived)", part.lifecycle())
+        } else {
+            part.lifecycle().to_string()
+        };
+
+        table.add_row(row![
+            part.pn,
+            part.mpn,
+            manufacturer,
+            part.descr,
+            part.val.as_deref().unwrap_or_default(),
+            part.mqty,
+            part.ver,
+            lifecycle
+        ]);
+    }
+    table.printstd();
+}
+
+/// Shows everything about one part: suppliers, parametric attributes and what's on hand
+pub fn show_part(app: &mut crate::Application, pn: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let mut table = Table::new();
+    let last_time_buy = part.last_time_buy.map(|d| d.to_string());
+    let manufacturer = find_manufacturer_name(&app.conn, &part.manufacturer_id)
+        .expect("Unable to get manufacturer!");
+
+    table.add_row(row![
+        "PN",
+        "MPN",
+        "Manufacturer",
+        "Desc",
+        "Val",
+        "Mqty",
+        "Ver",
+        "Lifecycle",
+        "Last time buy"
+    ]);
+    table.add_row(row![
+        part.pn,
+        part.mpn,
+        manufacturer,
+        part.descr,
+        part.val.as_deref().unwrap_or_default(),
+        part.mqty,
+        part.ver,
+        part.lifecycle(),
+        last_time_buy.unwrap_or_default()
+    ]);
+    table.printstd();
+
+    let suppliers =
+        find_part_suppliers_by_part_id(&app.conn, &part.id).expect("Unable to get suppliers!");
+    if !suppliers.is_empty() {
+        let mut table = Table::new();
+        table.add_row(row!["Supplier", "Supplier PN"]);
+        for entry in suppliers {
+            let supplier = find_supplier_by_id(&app.conn, &entry.supplier_id)
+                .expect("Unable to find supplier!");
+            table.add_row(row![supplier.name, entry.supplier_pn]);
+        }
+        table.printstd();
+    }
+
+    let attributes =
+        find_part_attributes_by_part_id(&app.conn, &part.id).expect("Unable to get attributes!");
+    if !attributes.is_empty() {
+        let mut table = Table::new();
+        table.add_row(row!["Attribute", "Value"]);
+        for attribute in attributes {
+            table.add_row(row![attribute.name, attribute.value]);
+        }
+        table.printstd();
+    }
+
+    // Tally inventory
+    let on_hand: i32 = find_inventories_by_part_id(&app.conn, &part.id)
+        .expect("Unable to get inventory!")
+        .iter()
+        .map(|i| i.quantity)
+        .sum();
+
+    println!("On hand: {}", on_hand);
+
+    if part.archived == 1 {
+        println!("{} is archived", part.pn);
+    }
+}
+
+/// Adds (or changes) a parametric attribute of a part
+pub fn set_attribute(app: &mut crate::Application, pn: &str, name: &str, value: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    // Names are stored in lower case so they're easy to search
+    let name = name.trim().to_lowercase();
+
+    set_part_attribute(&app.conn, &part.id, &name, value.trim()).expect("Unable to set attribute!");
+
+    println!("{} {} set to {}", pn, name, value.trim());
+}
+
+/// Removes a parametric attribute from a part
+pub fn remove_attribute(app: &mut crate::Application, pn: &str, name: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let name = name.trim().to_lowercase();
+
+    let removed =
+        delete_part_attribute(&app.conn, &part.id, &name).expect("Unable to remove attribute!");
+
+    if removed == 0 {
+        println!("{} has no attribute {}", pn, name);
+        std::process::exit(1);
+    }
+
+    println!("Removed {} from {}", name, pn);
+}
+
+/// Adds (or changes) a supplier part number for a part
+pub fn add_supplier(app: &mut crate::Application, pn: &str, supplier: &str, supplier_pn: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    // Supplier names are stored the same way they're named in the library. i.e. DIGIKEY
+    let supplier = supplier.trim().to_uppercase();
+
+    set_part_supplier_pn(&app.conn, &part.id, &supplier, supplier_pn.trim())
+        .expect("Unable to set supplier part number!");
+
+    println!("{} {} set to {}", pn, supplier, supplier_pn.trim());
+}
+
+/// Approves another MPN for a part. Stock of it counts towards the part.
+pub fn add_alternate(
+    app: &mut crate::Application,
+    pn: &str,
+    mpn: &str,
+    manufacturer: &Option<String>,
+    priority: Option<i32>,
+) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let mpn = mpn.trim();
+    if mpn.is_empty() {
+        println!("MPN can't be blank");
+        std::process::exit(1);
+    }
+
+    let manufacturer_id = find_manufacturer_id(app, manufacturer);
+
+    // An MPN can only be used for one part
+    let owner = find_parts_by_mpn(&app.conn, mpn)
+        .expect("Unable to get parts!")
+        .into_iter()
+        .find(|p| p.manufacturer_id == manufacturer_id);
+
+    if let Some(owner) = owner {
+        println!("{} is already the MPN of {}", mpn, owner.pn);
+        std::process::exit(1);
+    }
+
+    let approved = find_part_alternates_by_mpn_and_manufacturer(&app.conn, mpn, None)
+        .expect("Unable to get alternates!")
+        .into_iter()
+        .find(|a| a.manufacturer_id == manufacturer_id);
+
+    if let Some(approved) = approved {
+        let owner = find_part_by_id(&app.conn, &approved.part_id).expect("Unable to find part!");
+        println!("{} is already an alternate of {}", mpn, owner.pn);
+        std::process::exit(1);
+    }
+
+    // After the last one unless told otherwise
+    let priority = priority.unwrap_or_else(|| {
+        find_part_alternates_by_part_id(&app.conn, &part.id)
+            .expect("Unable to get alternates!")
+            .iter()
+            .map(|a| a.priority)
+            .max()
+            .unwrap_or(0)
+            + 1
+    });
+
+    let alternate = models::NewUpdatePartAlternate {
+        mpn,
+        priority: &priority,
+        part_id: &part.id,
+        manufacturer_id: manufacturer_id.as_ref(),
+    };
+
+    create_part_alternate(&app.conn, &alternate).expect("Unable to add alternate!");
+
+    println!("Added {} as an alternate of {}", mpn, pn);
+}
+
+/// Removes an approved alternate. Only once none of it is on hand.
+pub fn remove_alternate(
+    app: &mut crate::Application,
+    pn: &str,
+    mpn: &str,
+    manufacturer: &Option<String>,
+) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let mpn = mpn.trim();
+
+    let manufacturer_id =
+        manufacturer
+            .as_deref()
+            .map(|name| match find_manufacturer_by_name(&app.conn, name) {
+                Ok(x) => x.id,
+                Err(_) => {
+                    println!("Unable to find manufacturer {}", name);
+                    std::process::exit(1);
+                }
+            });
+
+    let mut alternates: Vec<models::PartAlternate> =
+        find_part_alternates_by_part_id(&app.conn, &part.id)
+            .expect("Unable to get alternates!")
+            .into_iter()
+            .filter(|a| a.mpn == mpn)
+            .filter(|a| manufacturer_id.is_none() || a.manufacturer_id == manufacturer_id)
+            .collect();
+
+    if alternates.is_empty() {
+        println!("{} is not an alternate of {}", mpn, pn);
+        std::process::exit(1);
+    } else if alternates.len() > 1 {
+        println!(
+            "{} is made by more than one manufacturer. Use --manufacturer",
+            mpn
+        );
+        std::process::exit(1);
+    }
+
+    let alternate = alternates.remove(0);
+
+    let on_hand: i32 = find_inventories_by_part_id(&app.conn, &part.id)
+        .expect("Unable to get inventory!")
+        .iter()
+        .filter(|i| i.alternate_id == Some(alternate.id))
+        .map(|i| i.quantity)
+        .sum();
+
+    if on_hand > 0 {
+        println!("{} {} still on hand. Use them first.", on_hand, mpn);
+        std::process::exit(1);
     }
+
+    clear_inventory_alternate(&app.conn, &alternate.id).expect("Unable to update inventory!");
+    delete_part_alternate(&app.conn, &alternate.id).expect("Unable to remove alternate!");
+
+    println!("Removed {} from {}", mpn, pn);
+}
+
+/// Shows the approved MPNs of a part in the order they're used
+pub fn show_alternates(app: &mut crate::Application, pn: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let alternates =
+        find_part_alternates_by_part_id(&app.conn, &part.id).expect("Unable to get alternates!");
+    let inventory =
+        find_inventories_by_part_id(&app.conn, &part.id).expect("Unable to get inventory!");
+
+    let on_hand = |alternate_id: Option<i32>| -> i32 {
+        inventory
+            .iter()
+            .filter(|i| i.alternate_id == alternate_id)
+            .map(|i| i.quantity)
+            .sum()
+    };
+
+    let mut table = Table::new();
+    table.add_row(row!["Priority", "MPN", "Manufacturer", "On hand"]);
+
+    // The part's own MPN
+    let manufacturer = find_manufacturer_name(&app.conn, &part.manufacturer_id)
+        .expect("Unable to get manufacturer!");
+    table.add_row(row![0, part.mpn, manufacturer, on_hand(None)]);
+
+    for alternate in &alternates {
+        let manufacturer = find_manufacturer_name(&app.conn, &alternate.manufacturer_id)
+            .expect("Unable to get manufacturer!");
+
+        table.add_row(row![
+            alternate.priority,
+            alternate.mpn,
+            manufacturer,
+            on_hand(Some(alternate.id))
+        ]);
+    }
+
+    println!("Displaying {} alternates of {}", alternates.len(), part.pn);
     table.printstd();
 }
 
-// pub fn search() {
-//   // TODO: use a partial/pattern to search
-//   // TODO: default is to search by PN but options to search by mpn, desc, etc.
-// }
+/// Shows the supplier part numbers for one or all parts
+pub fn show_suppliers(app: &mut crate::Application, pn: &Option<String>) {
+    use crate::schema::*;
+
+    let results = match pn {
+        Some(pn) => {
+            let part = match find_part_by_pn(&app.conn, pn) {
+                Ok(x) => x,
+                Err(_) => {
+                    println!("Unable to find part {}", pn);
+                    std::process::exit(1);
+                }
+            };
+
+            find_part_suppliers_by_part_id(&app.conn, &part.id)
+        }
+        None => part_suppliers::dsl::part_suppliers.load::<models::PartSupplier>(&app.conn),
+    }
+    .expect("Error loading supplier part numbers");
+
+    // Create the table
+    let mut table = Table::new();
+
+    println!("Displaying {} supplier part numbers", results.len());
+    table.add_row(row!["PN", "Supplier", "Supplier PN"]);
+    for entry in results {
+        let part = find_part_by_id(&app.conn, &entry.part_id).expect("Unable to find part!");
+        let supplier =
+            find_supplier_by_id(&app.conn, &entry.supplier_id).expect("Unable to find supplier!");
+
+        table.add_row(row![part.pn, supplier.name, entry.supplier_pn]);
+    }
+    table.printstd();
+}
+
+/// Shows every BOM that uses a part, up to the top-level products, and the builds in progress that need it
+pub fn where_used(app: &mut crate::Application, pn: &str) {
+    let part = match find_part_by_pn(&app.conn, pn) {
+        Ok(x) => x,
+        Err(_) => {
+            println!("Unable to find part {}", pn);
+            std::process::exit(1);
+        }
+    };
+
+    let usages = tables::bom::where_used(&app.conn, &part.id).expect("Unable to get BOMs!");
+
+    if usages.is_empty() {
+        println!("{} isn't used by any BOM", part.pn);
+        return;
+    }
+
+    println!("{} is used by:", part.pn);
+
+    // Create the table
+    let mut table = Table::new();
+
+    table.add_row(row![
+        "BOM",
+        "VER",
+        "QUANTITY",
+        "REFDES",
+        "NO STUFF",
+        "STATUS",
+        "OPEN BUILDS"
+    ]);
+    for usage in &usages {
+        let bom = format!("{}{}", "  ".repeat(usage.level), usage.bom.pn);
+
+        let status = if usage.entry.archived == 1 {
+            "archived"
+        } else if usage.entry.bom_ver == usage.bom.ver {
+            "current"
+        } else {
+            ""
+        };
+
+        let builds: Vec<String> = usage.open_builds.iter().map(|b| b.id.to_string()).collect();
+
+        table.add_row(row![
+            bom,
+            usage.entry.bom_ver,
+            usage.entry.quantity,
+            usage.entry.refdes,
+            usage.entry.nostuff,
+            status,
+            builds.join(", ")
+        ]);
+    }
+    table.printstd();
+
+    // Only list each product once
+    let mut products: Vec<&str> = Vec::new();
+    for usage in usages.iter().filter(|u| u.top_level) {
+        if !products.contains(&usage.bom.pn.as_str()) {
+            products.push(&usage.bom.pn);
+        }
+    }
+
+    println!("Top-level products: {}", products.join(", "));
+}
+
+/// Shows the parts matching every term with their supplier PNs and what's on hand
+pub fn search(app: &mut crate::Application, query: &[String], show_archived: bool) {
+    use crate::schema::*;
+
+    let query = match search::Query::parse(query) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let results = parts::dsl::parts
+        .load::<models::Part>(&app.conn)
+        .expect("Error loading parts");
+
+    // Create the table
+    let mut table = Table::new();
+    table.add_row(row![
+        "PN",
+        "MPN",
+        "MANUFACTURER",
+        "DESC",
+        "VAL",
+        "SUPPLIER PNS",
+        "ATTRIBUTES",
+        "ON HAND"
+    ]);
+
+    let mut count = 0;
+
+    for part in results.iter().filter(|p| show_archived || p.archived == 0) {
+        let suppliers = find_part_suppliers_by_part_id(&app.conn, &part.id)
+            .expect("Unable to get supplier part numbers!");
+        let attributes = find_part_attributes_by_part_id(&app.conn, &part.id)
+            .expect("Unable to get attributes!");
+        let manufacturer = find_manufacturer_name(&app.conn, &part.manufacturer_id)
+            .expect("Unable to get manufacturer!");
+
+        let searchable = search::Searchable {
+            pn: &part.pn,
+            mpn: &part.mpn,
+            manufacturer: Some(manufacturer.as_str()).filter(|m| !m.is_empty()),
+            descr: &part.descr,
+            val: part.val.as_deref(),
+            suppliers: suppliers.iter().map(|s| s.supplier_pn.as_str()).collect(),
+            attributes: attributes
+                .iter()
+                .map(|a| (a.name.as_str(), a.value.as_str()))
+                .collect(),
+        };
+
+        if !query.matches(&searchable) {
+            continue;
+        }
+
+        // Tally inventory
+        let on_hand: i32 = find_inventories_by_part_id(&app.conn, &part.id)
+            .expect("Unable to get inventory!")
+            .iter()
+            .map(|i| i.quantity)
+            .sum();
+
+        table.add_row(row![
+            part.pn,
+            part.mpn,
+            manufacturer,
+            part.descr,
+            part.val.as_deref().unwrap_or_default(),
+            searchable.suppliers.join(", "),
+            attributes
+                .iter()
+                .map(|a| format!("{}={}", a.name, a.value))
+                .collect::<Vec<String>>()
+                .join(", "),
+            on_hand
+        ]);
+
+        count += 1;
+    }
+
+    if count == 0 {
+        println!("No parts found");
+        return;
+    }
+
+    println!("Found {} parts", count);
+    table.printstd();
+}
@@ -0,0 +1,173 @@
+extern crate diesel;
+
+use prettytable::Table;
+
+use self::diesel::prelude::*;
+use crate::{models::*, *};
+
+use super::bom;
+
+use std::collections::HashMap;
+
+/// An open build that needs a part
+pub struct BuildNeed {
+    pub build_id: i32,
+    pub bom_pn: String,
+    pub bom_ver: i32,
+    pub quantity: i32,
+    /// How many of the part the build uses, after sub-assemblies in stock
+    pub needed: i32,
+}
+
+/// A part that isn't active and everything that uses it
+pub struct Obsolescence {
+    pub part: Part,
+    /// BOM versions using it, directly or through sub-assemblies. i.e. `PN v2`
+    pub boms: Vec<String>,
+    pub builds: Vec<BuildNeed>,
+    pub on_hand: i32,
+}
+
+impl Obsolescence {
+    pub fn needed(&self) -> i32 {
+        self.builds.iter().map(|b| b.needed).sum()
+    }
+}
+
+/// Finds every part that isn't active, worst first, with the BOMs and open builds it affects.
+/// Archived versions and no stuff lines aren't built so they're left out.
+pub fn find_obsolescence(
+    conn: &SqliteConnection,
+) -> std::result::Result<Vec<Obsolescence>, diesel::result::Error> {
+    use crate::schema::parts;
+
+    let mut found = parts::dsl::parts
+        .load::<Part>(conn)?
+        .into_iter()
+        .filter(|p| p.lifecycle() != Lifecycle::Active)
+        .collect::<Vec<Part>>();
+
+    found.sort_by_key(|p| (std::cmp::Reverse(p.lifecycle()), p.pn.clone()));
+
+    let mut report = Vec::new();
+
+    for part in found {
+        let mut boms: Vec<String> = Vec::new();
+        let mut builds: Vec<BuildNeed> = Vec::new();
+
+        for usage in bom::where_used(conn, &part.id)? {
+            if usage.entry.archived == 1 || usage.entry.nostuff == 1 {
+                continue;
+            }
+
+            let name = format!("{} v{}", usage.bom.pn, usage.entry.bom_ver);
+            if !boms.contains(&name) {
+                boms.push(name);
+            }
+
+            for build in usage.open_builds {
+                if builds.iter().any(|b| b.build_id == build.id) {
+                    continue;
+                }
+
+                let needed = bom::get_requirements(
+                    conn,
+                    &build.part_id,
+                    &build.part_ver,
+                    build.quantity,
+                    Some(&build.id),
+                    &mut HashMap::new(),
+                )?
+                .iter()
+                .filter(|r| r.part_id == part.id)
+                .map(|r| r.quantity)
+                .sum();
+
+                builds.push(BuildNeed {
+                    build_id: build.id,
+                    bom_pn: usage.bom.pn.clone(),
+                    bom_ver: build.part_ver,
+                    quantity: build.quantity,
+                    needed,
+                });
+            }
+        }
+
+        let on_hand = find_inventories_by_part_id(conn, &part.id)?
+            .iter()
+            .map(|i| i.quantity)
+            .sum();
+
+        report.push(Obsolescence {
+            part,
+            boms,
+            builds,
+            on_hand,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Lists parts that aren't active with the BOMs they're on and what open builds still need
+pub fn obsolescence(app: &mut crate::Application) {
+    let report = find_obsolescence(&app.conn).expect("Unable to get obsolescence report!");
+
+    if report.is_empty() {
+        println!("All parts are active");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "PN",
+        "MPN",
+        "LIFECYCLE",
+        "LAST TIME BUY",
+        "BOMS",
+        "NEEDED",
+        "ON HAND",
+        "SHORT"
+    ]);
+
+    for entry in &report {
+        let last_time_buy = entry.part.last_time_buy.map(|d| d.to_string());
+        let needed = entry.needed();
+
+        table.add_row(row![
+            entry.part.pn,
+            entry.part.mpn,
+            entry.part.lifecycle(),
+            last_time_buy.unwrap_or_default(),
+            entry.boms.join("\n"),
+            needed,
+            entry.on_hand,
+            (needed - entry.on_hand).max(0)
+        ]);
+    }
+    table.printstd();
+
+    // The builds in progress that are affected
+    let mut table = Table::new();
+    table.add_row(row!["BUILD", "BOM", "VER", "QUANTITY", "PN", "NEEDED"]);
+
+    let mut count = 0;
+    for entry in &report {
+        for build in &entry.builds {
+            table.add_row(row![
+                build.build_id,
+                build.bom_pn,
+                build.bom_ver,
+                build.quantity,
+                entry.part.pn,
+                build.needed
+            ]);
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        println!("Open builds:");
+        table.printstd();
+    }
+}